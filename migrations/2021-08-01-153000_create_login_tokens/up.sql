@@ -0,0 +1,8 @@
+create table login_tokens (
+    id uuid primary key default gen_random_uuid(),
+    token_hash text unique not null,
+    email text not null check (email != ''),
+    expires_at timestamptz not null,
+    used_at timestamptz,
+    created_at timestamptz not null default current_timestamp
+);
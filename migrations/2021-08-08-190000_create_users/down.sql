@@ -0,0 +1,9 @@
+alter table settings rename column user_id to user_uuid;
+alter table settings add column user_id text;
+update settings set user_id = users.email from users where users.id = settings.user_uuid;
+alter table settings drop column user_uuid;
+alter table settings alter column user_id set not null;
+alter table settings add constraint settings_user_id_key unique (user_id);
+alter table settings add constraint settings_user_id_check check (user_id != '');
+
+drop table if exists users;
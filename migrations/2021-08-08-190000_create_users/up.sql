@@ -0,0 +1,19 @@
+create table users (
+    id uuid primary key default gen_random_uuid(),
+    email text unique not null check (email != ''),
+    display_name text,
+    created_at timestamptz not null default current_timestamp,
+    last_login_at timestamptz
+);
+
+-- Until now, settings.user_id was the email address from the login token.
+insert into users (email)
+select user_id from settings
+on conflict do nothing;
+
+alter table settings rename column user_id to user_email;
+alter table settings add column user_id uuid references users (id) on delete cascade;
+update settings set user_id = users.id from users where users.email = settings.user_email;
+alter table settings drop column user_email;
+alter table settings alter column user_id set not null;
+alter table settings add constraint settings_user_id_key unique (user_id);
@@ -0,0 +1,10 @@
+create table sessions (
+    id uuid primary key default gen_random_uuid(),
+    user_id uuid not null references users (id) on delete cascade,
+    user_agent text,
+    created_at timestamptz not null default current_timestamp,
+    last_seen_at timestamptz not null default current_timestamp,
+    expires_at timestamptz not null
+);
+
+create index sessions_user_id_idx on sessions (user_id);
@@ -0,0 +1,3 @@
+drop trigger if exists set_updated_at on settings;
+
+alter table settings drop column revision;
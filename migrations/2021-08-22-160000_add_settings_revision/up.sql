@@ -0,0 +1,3 @@
+alter table settings add column revision bigint not null default 0;
+
+select diesel_manage_updated_at('settings');
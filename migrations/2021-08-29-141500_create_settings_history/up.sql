@@ -0,0 +1,9 @@
+create table settings_history (
+    id uuid primary key default gen_random_uuid(),
+    user_id uuid not null references users (id) on delete cascade,
+    revision bigint not null,
+    data jsonb not null,
+    tile_count integer not null,
+    created_at timestamptz not null default current_timestamp,
+    unique (user_id, revision)
+);
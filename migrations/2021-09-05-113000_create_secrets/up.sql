@@ -0,0 +1,11 @@
+create table secrets (
+    user_id uuid not null references users (id) on delete cascade,
+    name text not null,
+    nonce bytea not null,
+    ciphertext bytea not null,
+    created_at timestamptz not null default current_timestamp,
+    updated_at timestamptz not null default current_timestamp,
+    primary key (user_id, name)
+);
+
+select diesel_manage_updated_at('secrets');
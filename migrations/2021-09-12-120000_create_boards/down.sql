@@ -0,0 +1,22 @@
+alter table users drop column default_board_id;
+
+-- Only each user's oldest board can be kept.
+delete from boards where exists (
+    select 1 from boards older
+    where older.user_id = boards.user_id
+    and (older.created_at, older.id) < (boards.created_at, boards.id)
+);
+
+alter table settings_history add column user_id uuid references users (id) on delete cascade;
+update settings_history set user_id = boards.user_id from boards where boards.id = settings_history.board_id;
+alter table settings_history drop column board_id;
+alter table settings_history alter column user_id set not null;
+alter table settings_history add constraint settings_history_user_id_revision_key unique (user_id, revision);
+
+alter table boards drop constraint boards_user_id_slug_key;
+alter table boards drop column slug;
+alter table boards drop column name;
+alter table boards add constraint settings_user_id_key unique (user_id);
+alter table boards rename constraint boards_user_id_fkey to settings_user_id_fkey;
+alter table boards rename constraint boards_pkey to settings_pkey;
+alter table boards rename to settings;
@@ -0,0 +1,24 @@
+-- Each row of settings becomes a user's "Default" board, and users can add more.
+alter table settings rename to boards;
+alter table boards rename constraint settings_pkey to boards_pkey;
+alter table boards rename constraint settings_user_id_fkey to boards_user_id_fkey;
+alter table boards drop constraint settings_user_id_key;
+alter table boards add column slug text;
+alter table boards add column name text;
+update boards set slug = 'default', name = 'Default';
+alter table boards alter column slug set not null;
+alter table boards alter column name set not null;
+alter table boards add constraint boards_slug_check check (slug ~ '^[a-z0-9]+(-[a-z0-9]+)*$');
+alter table boards add constraint boards_name_check check (name != '');
+alter table boards add constraint boards_user_id_slug_key unique (user_id, slug);
+
+-- History is kept per board.
+alter table settings_history add column board_id uuid references boards (id) on delete cascade;
+update settings_history set board_id = boards.id from boards where boards.user_id = settings_history.user_id;
+delete from settings_history where board_id is null;
+alter table settings_history alter column board_id set not null;
+alter table settings_history drop column user_id;
+alter table settings_history add constraint settings_history_board_id_revision_key unique (board_id, revision);
+
+alter table users add column default_board_id uuid references boards (id) on delete set null;
+update users set default_board_id = boards.id from boards where boards.user_id = users.id;
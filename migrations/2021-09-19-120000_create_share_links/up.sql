@@ -0,0 +1,10 @@
+create table share_links (
+    id uuid primary key default gen_random_uuid(),
+    board_id uuid not null references boards (id) on delete cascade,
+    token_hash text unique not null,
+    label text not null default '',
+    created_at timestamptz not null default current_timestamp,
+    last_viewed_at timestamptz
+);
+
+create index share_links_board_id_idx on share_links (board_id);
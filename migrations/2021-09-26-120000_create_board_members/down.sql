@@ -0,0 +1,2 @@
+drop table if exists board_invites;
+drop table if exists board_members;
@@ -0,0 +1,22 @@
+-- Owners aren't listed here. A board's owner is still the user it belongs to.
+create table board_members (
+    board_id uuid not null references boards (id) on delete cascade,
+    user_id uuid not null references users (id) on delete cascade,
+    role text not null check (role in ('editor', 'viewer')),
+    created_at timestamptz not null default current_timestamp,
+    primary key (board_id, user_id)
+);
+
+create index board_members_user_id_idx on board_members (user_id);
+
+-- Invites are accepted the next time someone logs in with the email address.
+create table board_invites (
+    id uuid primary key default gen_random_uuid(),
+    board_id uuid not null references boards (id) on delete cascade,
+    email text not null check (email != ''),
+    role text not null check (role in ('editor', 'viewer')),
+    created_at timestamptz not null default current_timestamp,
+    unique (board_id, email)
+);
+
+create index board_invites_email_idx on board_invites (email);
@@ -0,0 +1,3 @@
+drop index if exists login_tokens_email_idx;
+drop index if exists sessions_expires_at_idx;
+drop index if exists login_tokens_expires_at_idx;
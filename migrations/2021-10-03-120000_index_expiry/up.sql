@@ -0,0 +1,6 @@
+-- Expired login tokens and sessions are deleted every so often.
+create index login_tokens_expires_at_idx on login_tokens (expires_at);
+create index sessions_expires_at_idx on sessions (expires_at);
+
+-- Logging in counts the unexpired tokens for the email address.
+create index login_tokens_email_idx on login_tokens (email);
@@ -0,0 +1,148 @@
+use super::board::Role;
+use super::config::{Config, ValidationError};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A copy of a board along with the revision it was read at. The server sends this from
+/// `/v1/boards/<slug>/load` and, when a save is rejected as stale, with the `409 Conflict`
+/// response. Clients send it to `/v1/boards/<slug>/save` with the revision their edits were based
+/// on.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    pub revision: i64,
+    pub config: Config,
+}
+
+/// The response to a successful save.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Saved {
+    pub revision: i64,
+}
+
+/// The body of an error response.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    pub message: String,
+    /// Every problem found with the request, for requests that were well-formed but invalid.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub problems: Vec<ValidationError>,
+}
+
+/// A summary of one saved version of a board, as listed by `/v1/boards/<slug>/history`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub id: Uuid,
+    pub revision: i64,
+    pub tile_count: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Which secrets the user has stored, as returned by `/v1/secrets`. The values themselves never
+/// leave the server.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SecretsStatus {
+    pub owm_api_key: bool,
+}
+
+/// One of a user's boards, or a board that someone else lets them use.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Board {
+    /// Identifies the board in URLs. It's made from the board's name, but stays the same when the
+    /// board is renamed so that links keep working. For other users' boards, it's a team key
+    /// instead; see `board::team_key`.
+    pub slug: String,
+    pub name: String,
+    /// What the user can do with the board.
+    #[serde(default)]
+    pub role: Role,
+    /// Who the board belongs to, if it's someone else.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+}
+
+/// The user's boards, as listed by `/v1/boards`. Every user has at least one of their own.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BoardList {
+    /// The user's own boards, oldest first, and then the boards they're a member of.
+    pub boards: Vec<Board>,
+    /// The slug of the board to show when the user doesn't pick one.
+    pub default: String,
+}
+
+/// The body of a request to create or rename a board.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BoardName {
+    pub name: String,
+}
+
+/// A link that shows one of the user's boards to anyone who has it, as listed by
+/// `/v1/boards/<slug>/shares`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ShareLink {
+    pub id: Uuid,
+    /// A reminder of who or what the link is for, like "Office screen". May be empty.
+    pub label: String,
+    pub created_at: DateTime<Utc>,
+    pub last_viewed_at: Option<DateTime<Utc>>,
+}
+
+/// The body of a request to share a board.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct NewShareLink {
+    #[serde(default)]
+    pub label: String,
+}
+
+/// The response to sharing a board. The server only keeps a hash of the token, so this is the one
+/// time it can be seen.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CreatedShareLink {
+    pub link: ShareLink,
+    /// Shows the board at `/s/<token>`.
+    pub token: String,
+}
+
+/// A board as seen through a share link, from `/v1/shared/<token>`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SharedBoard {
+    pub name: String,
+    pub snapshot: Snapshot,
+}
+
+/// Someone other than the owner who can use a board.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Member {
+    pub user_id: Uuid,
+    pub email: String,
+    pub role: Role,
+}
+
+/// Someone who was asked to use a board, but hasn't logged in since.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Invite {
+    pub id: Uuid,
+    pub email: String,
+    pub role: Role,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Who can use a board, as listed by `/v1/boards/<slug>/members`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Members {
+    pub members: Vec<Member>,
+    pub invites: Vec<Invite>,
+}
+
+/// The body of a request to invite someone to a board.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct NewInvite {
+    pub email: String,
+    pub role: Role,
+}
+
+/// The body of a request to change a member's role.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MemberRole {
+    pub role: Role,
+}
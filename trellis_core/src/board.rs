@@ -0,0 +1,155 @@
+//! Names for a user's boards. Each board has its own tiles and preferences, saved separately, and
+//! is found by a slug that's made from its name when it's created. Boards can also be shared with
+//! other users, who find them by a team key instead, since their slugs are only unique per owner.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// What the board that every user starts out with is called.
+pub const DEFAULT_NAME: &str = "Default";
+
+/// The slug of the board that every user starts out with. Settings saved before there were
+/// multiple boards are on this one.
+pub const DEFAULT_SLUG: &str = "default";
+
+const MAX_NAME_LENGTH: usize = 40;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NameError {
+    Empty,
+    TooLong,
+}
+
+impl fmt::Display for NameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "board names can't be empty"),
+            Self::TooLong => write!(
+                f,
+                "board names can't be longer than {} characters",
+                MAX_NAME_LENGTH
+            ),
+        }
+    }
+}
+
+impl std::error::Error for NameError {}
+
+/// Tidies up a board name that a user typed, or says why it can't be used.
+pub fn normalize_name(name: &str) -> Result<String, NameError> {
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    if name.is_empty() {
+        Err(NameError::Empty)
+    } else if name.chars().count() > MAX_NAME_LENGTH {
+        Err(NameError::TooLong)
+    } else {
+        Ok(name)
+    }
+}
+
+/// Makes a slug for use in URLs from a board name, like `wall-display` from "Wall display". Slugs
+/// are lowercase ASCII letters and digits, with single hyphens between words.
+pub fn slugify(name: &str) -> String {
+    let words: Vec<String> = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect();
+    if words.is_empty() {
+        // Names written entirely in other scripts still need something.
+        "board".to_owned()
+    } else {
+        words.join("-")
+    }
+}
+
+/// Whether `slug` could have come from `slugify`.
+pub fn is_slug(slug: &str) -> bool {
+    slug.split('-').all(|word| {
+        !word.is_empty()
+            && word
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit())
+    })
+}
+
+/// Comes before the board's ID in a team key. Slugs never have a `.` in them, so team keys can't be
+/// mistaken for slugs.
+const TEAM_KEY_PREFIX: &str = "team.";
+
+/// Makes the key that members of a board use to find it, like `team.6c9c...`.
+pub fn team_key(board_id: Uuid) -> String {
+    format!("{}{}", TEAM_KEY_PREFIX, board_id.to_simple())
+}
+
+/// Reads the board ID out of a key made by `team_key`.
+pub fn parse_team_key(key: &str) -> Option<Uuid> {
+    let id = key.strip_prefix(TEAM_KEY_PREFIX)?;
+    Uuid::parse_str(id).ok()
+}
+
+/// What a user can do with a board. Each role can do everything the ones before it can.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Can see the board.
+    Viewer,
+    /// Can change the board's tiles and preferences.
+    Editor,
+    /// Made the board. Can also rename, share, or delete it, and choose who else can use it.
+    Owner,
+}
+
+impl Role {
+    /// The roles that other users can be given.
+    pub const MEMBER_ROLES: [Role; 2] = [Role::Editor, Role::Viewer];
+
+    pub fn can_edit(self) -> bool {
+        self >= Self::Editor
+    }
+
+    /// The name of the role, as stored and as sent in requests.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::Editor => "editor",
+            Self::Owner => "owner",
+        }
+    }
+
+    /// The name of the role, for people.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Viewer => "Viewer",
+            Self::Editor => "Editor",
+            Self::Owner => "Owner",
+        }
+    }
+}
+
+impl Default for Role {
+    /// Boards that were listed before there were roles all belonged to the user.
+    fn default() -> Self {
+        Self::Owner
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Self::Viewer, Self::Editor, Self::Owner]
+            .iter()
+            .copied()
+            .find(|r| r.as_str() == s)
+            .ok_or_else(|| format!("unknown role: {}", s))
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
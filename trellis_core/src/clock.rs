@@ -0,0 +1,48 @@
+//! Settings for clock tiles.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// How a clock tile draws the time.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Style {
+    Digital,
+    Analog,
+}
+
+impl Style {
+    pub const ALL: &'static [Style] = &[Self::Digital, Self::Analog];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Digital => "Digital",
+            Self::Analog => "Analog",
+        }
+    }
+}
+
+impl Default for Style {
+    /// Clock tiles were only digital before there was a choice.
+    fn default() -> Self {
+        Self::Digital
+    }
+}
+
+impl FromStr for Style {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|c| c.to_string() == s)
+            .ok_or_else(|| format!("unknown clock style: {}", s))
+    }
+}
+
+impl fmt::Display for Style {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
@@ -1,9 +1,20 @@
+use super::clock;
+use super::feed;
+use super::links::{self, Link};
+use super::migrate;
+use super::note;
+use super::preferences::{self, Overrides, Preferences};
+use super::weather;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use uuid::Uuid;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct Config {
-    pub secrets: Secrets,
+    /// The shape of this document. See `migrate` for how older documents are upgraded.
+    pub schema_version: u32,
+    pub preferences: Preferences,
     pub tiles: Vec<Tile>,
 }
 
@@ -14,28 +25,39 @@ If you think this is cool, have an idea to share, or want to watch development,
 impl Default for Config {
     fn default() -> Self {
         Self {
-            secrets: Secrets { owm_api_key: None },
+            schema_version: migrate::CURRENT_VERSION,
+            preferences: Preferences::default(),
             tiles: vec![
                 Tile {
                     id: Uuid::new_v4(),
                     width: None,
                     height: None,
-                    data: Data::Clock,
+                    overrides: Overrides::default(),
+                    data: Data::Clock {
+                        time_zone: None,
+                        extra_zones: Vec::new(),
+                        style: clock::Style::default(),
+                    },
                 },
                 Tile {
                     id: Uuid::new_v4(),
                     width: None,
                     height: None,
+                    overrides: Overrides::default(),
                     data: Data::Weather {
+                        provider: weather::Provider::default(),
                         location_id: "".to_owned(),
+                        location_name: "".to_owned(),
+                        mode: weather::Mode::default(),
                     },
                 },
                 Tile {
                     id: Uuid::new_v4(),
                     width: None,
                     height: Some(2),
+                    overrides: Overrides::default(),
                     data: Data::Note {
-                        text: STARTER_TEXT.to_owned(),
+                        text: note::Text::new(STARTER_TEXT),
                     },
                 },
             ],
@@ -43,7 +65,9 @@ impl Default for Config {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+/// Values that are stored encrypted on the server, apart from the rest of the config, and never
+/// sent back to clients.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
 pub struct Secrets {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub owm_api_key: Option<String>,
@@ -52,9 +76,59 @@ pub struct Secrets {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 #[serde(tag = "type")]
 pub enum Data {
-    Clock,
-    Weather { location_id: String },
-    Note { text: String },
+    Clock {
+        /// The IANA name of the time zone to show, like `America/New_York`. Without one, the
+        /// clock shows the viewer's own time zone.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        time_zone: Option<String>,
+        /// More time zones to list under the main one.
+        extra_zones: Vec<String>,
+        style: clock::Style,
+    },
+    Weather {
+        provider: weather::Provider,
+        /// Where to report the weather for, in whatever form the provider uses.
+        location_id: String,
+        /// What to call the location, as chosen from a search. Empty for tiles set up before
+        /// locations could be searched for, which use the provider's name for it instead.
+        location_name: String,
+        mode: weather::Mode,
+    },
+    Note {
+        text: note::Text,
+    },
+    Links {
+        /// Shown above the links. Empty for no heading.
+        title: String,
+        links: Vec<Link>,
+        /// Whether following a link opens a new tab, rather than leaving the board.
+        new_tab: bool,
+    },
+    Feed {
+        /// The RSS or Atom feed to show.
+        url: String,
+        /// How many of the latest entries to show.
+        max_items: u32,
+    },
+}
+
+impl Data {
+    /// Combines two concurrent versions of the same tile's data, when they can be combined
+    /// without either side losing anything. So far that's only a note's text. `base` is the
+    /// version both started from, if it's known.
+    pub fn merge(&self, base: Option<&Data>, other: &Data) -> Option<Data> {
+        match (self, other) {
+            (Data::Note { text }, Data::Note { text: other }) => {
+                let mut text = text.clone();
+                match base {
+                    Some(Data::Note { text: base }) => text.merge_since(base, other),
+                    _ => text.merge(other),
+                }
+                Some(Data::Note { text })
+            }
+            _ => None,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -66,6 +140,10 @@ pub struct Tile {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub height: Option<u32>,
 
+    /// How this tile shows things differently from the rest of the board.
+    #[serde(default, skip_serializing_if = "Overrides::is_empty")]
+    pub overrides: Overrides,
+
     pub data: Data,
 }
 
@@ -78,3 +156,424 @@ pub struct WeatherData {
 pub struct NoteData {
     pub text: String,
 }
+
+/// The largest number of grid rows or columns a tile can span.
+pub const MAX_TILE_SPAN: u32 = 12;
+
+/// The most characters a note tile can show.
+pub const MAX_NOTE_CHARS: usize = 64 * 1024;
+
+/// The most deleted characters a note tile can keep as tombstones once it's been compacted. Most
+/// are compacted away, so this only comes up with edits that tangle them together.
+pub const MAX_NOTE_TOMBSTONES: usize = 64 * 1024;
+
+/// A semantic problem with a config that deserialized successfully.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum ValidationError {
+    DuplicateTileId {
+        id: Uuid,
+    },
+    WidthOutOfRange {
+        id: Uuid,
+        width: u32,
+    },
+    HeightOutOfRange {
+        id: Uuid,
+        height: u32,
+    },
+    NoteTooLong {
+        id: Uuid,
+        chars: usize,
+    },
+    NoteTooManyTombstones {
+        id: Uuid,
+        tombstones: usize,
+    },
+    TooManyLinks {
+        id: Uuid,
+        count: usize,
+    },
+    /// A link that isn't an `http` or `https` URL.
+    InvalidLinkUrl {
+        id: Uuid,
+        url: String,
+    },
+    /// A feed that isn't at an `http` or `https` URL.
+    InvalidFeedUrl {
+        id: Uuid,
+        url: String,
+    },
+    MaxItemsOutOfRange {
+        id: Uuid,
+        max_items: u32,
+    },
+    /// The board's locale, or a tile's override of it, isn't a language tag.
+    InvalidLocale {
+        locale: String,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuplicateTileId { id } => write!(f, "tile ID {} is used more than once", id),
+            Self::WidthOutOfRange { id, width } => write!(
+                f,
+                "tile {} has width {}, but it must be between 1 and {}",
+                id, width, MAX_TILE_SPAN
+            ),
+            Self::HeightOutOfRange { id, height } => write!(
+                f,
+                "tile {} has height {}, but it must be between 1 and {}",
+                id, height, MAX_TILE_SPAN
+            ),
+            Self::NoteTooLong { id, chars } => write!(
+                f,
+                "note {} has {} characters, but the limit is {}",
+                id, chars, MAX_NOTE_CHARS
+            ),
+            Self::NoteTooManyTombstones { id, tombstones } => write!(
+                f,
+                "note {} keeps {} deleted characters, but the limit is {}",
+                id, tombstones, MAX_NOTE_TOMBSTONES
+            ),
+            Self::TooManyLinks { id, count } => write!(
+                f,
+                "tile {} has {} links, but the limit is {}",
+                id,
+                count,
+                links::MAX_LINKS
+            ),
+            Self::InvalidLinkUrl { id, url } => write!(
+                f,
+                "tile {} links to {:?}, which is not a web address",
+                id, url
+            ),
+            Self::InvalidFeedUrl { id, url } => {
+                write!(f, "feed {} is at {:?}, which is not a web address", id, url)
+            }
+            Self::MaxItemsOutOfRange { id, max_items } => write!(
+                f,
+                "feed {} shows {} entries, but it must be between 1 and {}",
+                id,
+                max_items,
+                feed::MAX_ITEMS
+            ),
+            Self::InvalidLocale { locale } => {
+                write!(f, "{:?} is not a language tag, like \"en-US\"", locale)
+            }
+        }
+    }
+}
+
+impl Config {
+    /// Drops the deleted characters that notes no longer need. See `note::Text::compact`.
+    pub fn compact_notes(&mut self) {
+        for tile in self.tiles.iter_mut() {
+            if let Data::Note { text } = &mut tile.data {
+                text.compact();
+            }
+        }
+    }
+
+    /// Checks the things that the types alone can't guarantee, returning every problem found.
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        let mut seen = HashSet::new();
+        let span = 1..=MAX_TILE_SPAN;
+
+        if !preferences::is_valid_locale(&self.preferences.locale) {
+            errors.push(ValidationError::InvalidLocale {
+                locale: self.preferences.locale.clone(),
+            });
+        }
+        for tile in self.tiles.iter() {
+            let id = tile.id;
+            if !seen.insert(id) {
+                errors.push(ValidationError::DuplicateTileId { id });
+            }
+            if let Some(width) = tile.width.filter(|w| !span.contains(w)) {
+                errors.push(ValidationError::WidthOutOfRange { id, width });
+            }
+            if let Some(height) = tile.height.filter(|h| !span.contains(h)) {
+                errors.push(ValidationError::HeightOutOfRange { id, height });
+            }
+            match &tile.data {
+                Data::Note { text } => {
+                    let chars = text.len();
+                    if chars > MAX_NOTE_CHARS {
+                        errors.push(ValidationError::NoteTooLong { id, chars });
+                    }
+                    let tombstones = text.stored_len() - chars;
+                    if tombstones > MAX_NOTE_TOMBSTONES {
+                        errors.push(ValidationError::NoteTooManyTombstones { id, tombstones });
+                    }
+                }
+                Data::Links { links, .. } => {
+                    if links.len() > links::MAX_LINKS {
+                        errors.push(ValidationError::TooManyLinks {
+                            id,
+                            count: links.len(),
+                        });
+                    }
+                    for link in links.iter().filter(|l| links::host(&l.url).is_none()) {
+                        errors.push(ValidationError::InvalidLinkUrl {
+                            id,
+                            url: link.url.clone(),
+                        });
+                    }
+                }
+                Data::Feed { url, max_items } => {
+                    if links::host(url).is_none() {
+                        errors.push(ValidationError::InvalidFeedUrl {
+                            id,
+                            url: url.clone(),
+                        });
+                    }
+                    if !(1..=feed::MAX_ITEMS).contains(max_items) {
+                        errors.push(ValidationError::MaxItemsOutOfRange {
+                            id,
+                            max_items: *max_items,
+                        });
+                    }
+                }
+                Data::Clock { .. } | Data::Weather { .. } => {}
+            }
+            if let Some(locale) = &tile.overrides.locale {
+                if !preferences::is_valid_locale(locale) {
+                    errors.push(ValidationError::InvalidLocale {
+                        locale: locale.clone(),
+                    });
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// A change that `merge` could not reconcile automatically.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Conflict {
+    /// Both sides changed the tile's data to different values.
+    Data { id: Uuid },
+    /// Both sides resized the tile to different sizes.
+    Size { id: Uuid },
+    /// One side deleted the tile while the other side changed it. The changed tile is kept.
+    DeleteModify { id: Uuid },
+    /// Both sides overrode the tile's preferences differently.
+    Overrides { id: Uuid },
+    /// Both sides reordered the tiles differently.
+    Order,
+    /// Both sides changed the board's preferences differently.
+    Preferences,
+}
+
+/// The result of a three-way merge.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Merge {
+    pub config: Config,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Merges two sets of concurrent edits (`local` and `remote`) made to the same `base` config.
+///
+/// Tiles are matched up by ID, so adds, deletes, moves, resizes, and data changes made on
+/// different tiles (or to different parts of the same tile) combine cleanly. Notes merge their
+/// text character by character, so edits to the same note never clash. When both sides
+/// changed the same thing in different ways, the local change wins and the clash is reported in
+/// `Merge::conflicts`.
+pub fn merge(base: &Config, local: &Config, remote: &Config) -> Merge {
+    let mut conflicts = Vec::new();
+
+    // Decide which tiles survive, and what each one looks like.
+    let mut merged: HashMap<Uuid, Tile> = HashMap::new();
+    for id in all_ids(&[base, local, remote]) {
+        let b = find_tile(base, id);
+        let l = find_tile(local, id);
+        let r = find_tile(remote, id);
+        let tile = match (b, l, r) {
+            (_, Some(l), Some(r)) => Some(merge_tile(b, l, r, &mut conflicts)),
+            // Added on one side only
+            (None, Some(t), None) | (None, None, Some(t)) => Some(t.clone()),
+            // Deleted on one side, so keep it only if the other side changed it
+            (Some(b), Some(t), None) | (Some(b), None, Some(t)) => {
+                if t == b {
+                    None
+                } else {
+                    conflicts.push(Conflict::DeleteModify { id });
+                    Some(t.clone())
+                }
+            }
+            _ => None,
+        };
+        if let Some(tile) = tile {
+            merged.insert(id, tile);
+        }
+    }
+
+    let order = merge_order(base, local, remote, &merged, &mut conflicts);
+    let tiles = order
+        .into_iter()
+        .filter_map(|id| merged.remove(&id))
+        .collect();
+
+    let (preferences, clash) = merge_value(
+        Some(&base.preferences),
+        &local.preferences,
+        &remote.preferences,
+    );
+    if clash {
+        conflicts.push(Conflict::Preferences);
+    }
+
+    Merge {
+        config: Config {
+            schema_version: local.schema_version,
+            preferences,
+            tiles,
+        },
+        conflicts,
+    }
+}
+
+/// Merges a single value, returning the merged value and whether there was a conflict.
+fn merge_value<T: Clone + PartialEq>(base: Option<&T>, local: &T, remote: &T) -> (T, bool) {
+    if local == remote || Some(remote) == base {
+        (local.clone(), false)
+    } else if Some(local) == base {
+        (remote.clone(), false)
+    } else {
+        (local.clone(), true)
+    }
+}
+
+fn merge_tile(
+    base: Option<&Tile>,
+    local: &Tile,
+    remote: &Tile,
+    conflicts: &mut Vec<Conflict>,
+) -> Tile {
+    let id = local.id;
+
+    let base_size = base.map(|t| (t.width, t.height));
+    let ((width, height), clash) = merge_value(
+        base_size.as_ref(),
+        &(local.width, local.height),
+        &(remote.width, remote.height),
+    );
+    if clash {
+        conflicts.push(Conflict::Size { id });
+    }
+
+    let (overrides, clash) = merge_value(
+        base.map(|t| &t.overrides),
+        &local.overrides,
+        &remote.overrides,
+    );
+    if clash {
+        conflicts.push(Conflict::Overrides { id });
+    }
+
+    let data = match local.data.merge(base.map(|t| &t.data), &remote.data) {
+        Some(data) => data,
+        None => {
+            let (data, clash) = merge_value(base.map(|t| &t.data), &local.data, &remote.data);
+            if clash {
+                conflicts.push(Conflict::Data { id });
+            }
+            data
+        }
+    };
+
+    Tile {
+        id,
+        width,
+        height,
+        overrides,
+        data,
+    }
+}
+
+/// Chooses the order of the merged tiles. If only one side moved tiles around, its order is used
+/// and the other side's new tiles are slotted in after their original neighbors.
+fn merge_order(
+    base: &Config,
+    local: &Config,
+    remote: &Config,
+    merged: &HashMap<Uuid, Tile>,
+    conflicts: &mut Vec<Conflict>,
+) -> Vec<Uuid> {
+    // Only tiles that every version has can show whether a side reordered things.
+    let common = |cfg: &Config| -> Vec<Uuid> {
+        cfg.tiles
+            .iter()
+            .map(|t| t.id)
+            .filter(|id| {
+                find_tile(base, *id).is_some()
+                    && find_tile(local, *id).is_some()
+                    && find_tile(remote, *id).is_some()
+            })
+            .collect()
+    };
+    let base_order = common(base);
+    let local_order = common(local);
+    let remote_order = common(remote);
+
+    let local_moved = local_order != base_order;
+    let remote_moved = remote_order != base_order;
+    if local_moved && remote_moved && local_order != remote_order {
+        conflicts.push(Conflict::Order);
+    }
+
+    let (primary, secondary) = if remote_moved && !local_moved {
+        (remote, local)
+    } else {
+        (local, remote)
+    };
+
+    let mut order: Vec<Uuid> = primary
+        .tiles
+        .iter()
+        .map(|t| t.id)
+        .filter(|id| merged.contains_key(id))
+        .collect();
+
+    let mut anchor: Option<Uuid> = None;
+    for tile in secondary.tiles.iter() {
+        if !merged.contains_key(&tile.id) {
+            continue;
+        }
+        if !order.contains(&tile.id) {
+            let idx = match anchor {
+                None => 0,
+                Some(a) => order.iter().position(|id| *id == a).map_or(0, |i| i + 1),
+            };
+            order.insert(idx, tile.id);
+        }
+        anchor = Some(tile.id);
+    }
+
+    order
+}
+
+fn all_ids(configs: &[&Config]) -> Vec<Uuid> {
+    let mut ids = Vec::new();
+    for cfg in configs {
+        for tile in cfg.tiles.iter() {
+            if !ids.contains(&tile.id) {
+                ids.push(tile.id);
+            }
+        }
+    }
+    ids
+}
+
+fn find_tile(cfg: &Config, id: Uuid) -> Option<&Tile> {
+    cfg.tiles.iter().find(|t| t.id == id)
+}
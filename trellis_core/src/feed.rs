@@ -0,0 +1,30 @@
+//! Feeds as shown on feed tiles, in the same shape whether they were RSS or Atom.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// How many entries a feed tile shows unless it's told otherwise.
+pub const DEFAULT_ITEMS: u32 = 10;
+
+/// The most entries a feed tile can show. The server never keeps more than this many of a feed.
+pub const MAX_ITEMS: u32 = 50;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Feed {
+    pub title: String,
+    /// The site the feed is from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+    /// Entries in the order the feed lists them, which is nearly always newest first.
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+    /// When the entry was published, or last updated if the feed doesn't say.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub published: Option<DateTime<Utc>>,
+}
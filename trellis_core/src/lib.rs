@@ -1,4 +1,15 @@
+pub mod api;
+pub mod board;
+pub mod clock;
 pub mod config;
+pub mod feed;
+pub mod links;
+pub mod migrate;
+pub mod note;
+pub mod outbox;
+pub mod portable;
+pub mod preferences;
+pub mod weather;
 
 #[cfg(test)]
 mod tests;
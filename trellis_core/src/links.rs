@@ -0,0 +1,56 @@
+//! Settings for link tiles.
+
+use serde::{Deserialize, Serialize};
+
+/// One link on a links tile.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Link {
+    /// What to call the link. Without one, the link shows its URL instead.
+    pub label: String,
+    pub url: String,
+    /// Whether to show the site's favicon next to the label.
+    pub icon: bool,
+}
+
+impl Link {
+    /// What the link shows as its text.
+    pub fn text(&self) -> &str {
+        if self.label.trim().is_empty() {
+            &self.url
+        } else {
+            &self.label
+        }
+    }
+}
+
+/// The most links one tile can hold.
+pub const MAX_LINKS: usize = 100;
+
+/// Finds the host of a link's URL, in lowercase. Only `http` and `https` URLs have one, so that
+/// a board can't hold `javascript:` links that run when someone else clicks them.
+pub fn host(url: &str) -> Option<String> {
+    let (scheme, rest) = url.trim().split_once("://")?;
+    if !scheme.eq_ignore_ascii_case("http") && !scheme.eq_ignore_ascii_case("https") {
+        return None;
+    }
+    let authority = rest.split(['/', '?', '#'].as_ref()).next()?;
+    let authority = authority.rsplit('@').next()?;
+    let host = if authority.starts_with('[') {
+        &authority[..=authority.find(']')?]
+    } else {
+        authority.split(':').next()?
+    };
+    let valid = match host.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+        // An IPv6 address, which has colons of its own.
+        Some(addr) => addr
+            .chars()
+            .all(|c| c.is_ascii_hexdigit() || c == ':' || c == '.'),
+        None => host
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '.'),
+    };
+    if host.is_empty() || host == "[]" || !valid {
+        return None;
+    }
+    Some(host.to_lowercase())
+}
@@ -0,0 +1,195 @@
+use super::config::Config;
+use serde_json::{json, Map, Value};
+use std::fmt;
+
+/// The `schema_version` of config documents written by this version of the code.
+pub const CURRENT_VERSION: u32 = 11;
+
+/// Documents written before versioning existed have no `schema_version` field.
+const UNVERSIONED: u32 = 1;
+
+/// Each step upgrades a document in place by one version: `STEPS[0]` goes from version 1 to 2,
+/// `STEPS[1]` from 2 to 3, and so on. Steps must never change once released, because old
+/// documents may still be sitting in the database or in somebody's LocalStorage.
+const STEPS: &[fn(&mut Map<String, Value>)] = &[
+    v1_to_v2, v2_to_v3, v3_to_v4, v4_to_v5, v5_to_v6, v6_to_v7, v7_to_v8, v8_to_v9, v9_to_v10,
+    v10_to_v11,
+];
+
+#[derive(Debug)]
+pub enum MigrationError {
+    /// The document isn't a JSON object, so it can't be a config at all.
+    NotAnObject,
+    /// The document has a `schema_version` that isn't a version number.
+    InvalidVersion(Value),
+    /// The document was written by a newer version of the code.
+    TooNew(u32),
+    /// The document was upgraded, but still doesn't match the current shape.
+    Deserialize(serde_json::Error),
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAnObject => write!(f, "config is not a JSON object"),
+            Self::InvalidVersion(v) => write!(f, "invalid schema version: {}", v),
+            Self::TooNew(v) => write!(
+                f,
+                "schema version {} is newer than the latest known version ({})",
+                v, CURRENT_VERSION
+            ),
+            Self::Deserialize(err) => write!(f, "could not read config: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Deserialize(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for MigrationError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Deserialize(err)
+    }
+}
+
+/// Upgrades a serialized config from any known version to the current shape.
+pub fn migrate(value: Value) -> Result<Value, MigrationError> {
+    let mut doc = match value {
+        Value::Object(doc) => doc,
+        _ => return Err(MigrationError::NotAnObject),
+    };
+
+    let version = match doc.get("schema_version") {
+        None => UNVERSIONED,
+        Some(v) => match v.as_u64() {
+            Some(n) if n >= UNVERSIONED as u64 && n <= u32::MAX as u64 => n as u32,
+            _ => return Err(MigrationError::InvalidVersion(v.clone())),
+        },
+    };
+    if version > CURRENT_VERSION {
+        return Err(MigrationError::TooNew(version));
+    }
+
+    for step in STEPS[(version - UNVERSIONED) as usize..].iter() {
+        step(&mut doc);
+    }
+    doc.insert("schema_version".to_owned(), Value::from(CURRENT_VERSION));
+
+    Ok(Value::Object(doc))
+}
+
+/// Reads a config of any known version.
+pub fn from_value(value: Value) -> Result<Config, MigrationError> {
+    Ok(serde_json::from_value(migrate(value)?)?)
+}
+
+/// Reads a config of any known version from a JSON string.
+pub fn from_str(s: &str) -> Result<Config, MigrationError> {
+    from_value(serde_json::from_str(s)?)
+}
+
+/// Version 2 added `schema_version` itself, so there is nothing else to change.
+fn v1_to_v2(_doc: &mut Map<String, Value>) {}
+
+/// Version 3 moved secrets out of the config and into separate encrypted storage. Whoever stores
+/// the document is responsible for saving the secrets somewhere else before upgrading it.
+fn v2_to_v3(doc: &mut Map<String, Value>) {
+    doc.remove("secrets");
+}
+
+/// Calls `f` with the data of every tile of the given type.
+fn for_each_tile_data(
+    doc: &mut Map<String, Value>,
+    tile_type: &str,
+    f: impl Fn(&mut Map<String, Value>),
+) {
+    let tiles = match doc.get_mut("tiles").and_then(Value::as_array_mut) {
+        Some(tiles) => tiles,
+        None => return,
+    };
+    for tile in tiles.iter_mut() {
+        let data = match tile.get_mut("data").and_then(Value::as_object_mut) {
+            Some(data) => data,
+            None => continue,
+        };
+        if data.get("type").and_then(Value::as_str) == Some(tile_type) {
+            f(data);
+        }
+    }
+}
+
+/// Version 4 let weather tiles choose a provider. Every existing tile used OpenWeatherMap.
+fn v3_to_v4(doc: &mut Map<String, Value>) {
+    for_each_tile_data(doc, "Weather", |data| {
+        data.entry("provider")
+            .or_insert_with(|| Value::from("OpenWeatherMap"));
+    });
+}
+
+/// Version 5 added forecasts to weather tiles. Every existing tile showed current conditions.
+fn v4_to_v5(doc: &mut Map<String, Value>) {
+    for_each_tile_data(doc, "Weather", |data| {
+        data.entry("mode").or_insert_with(|| Value::from("Current"));
+    });
+}
+
+/// Version 6 added board preferences. Tiles used to show imperial units, 24-hour times, and
+/// English dates.
+fn v5_to_v6(doc: &mut Map<String, Value>) {
+    doc.entry("preferences").or_insert_with(|| {
+        json!({
+            "units": "Imperial",
+            "time_format": "TwentyFourHour",
+            "locale": "en-US",
+        })
+    });
+}
+
+/// Version 7 let weather tiles name their location. Existing tiles have no name for it.
+fn v6_to_v7(doc: &mut Map<String, Value>) {
+    for_each_tile_data(doc, "Weather", |data| {
+        data.entry("location_name")
+            .or_insert_with(|| Value::from(""));
+    });
+}
+
+/// Version 8 let clock tiles choose their time zones and style. Every existing clock showed the
+/// viewer's time zone, digitally.
+fn v7_to_v8(doc: &mut Map<String, Value>) {
+    for_each_tile_data(doc, "Clock", |data| {
+        data.entry("extra_zones").or_insert_with(|| json!([]));
+        data.entry("style")
+            .or_insert_with(|| Value::from("Digital"));
+    });
+}
+
+/// Version 9 made notes mergeable, storing their text as runs of characters. Existing text
+/// becomes a single run, as if it had all been typed at once by nobody in particular.
+fn v8_to_v9(doc: &mut Map<String, Value>) {
+    for_each_tile_data(doc, "Note", |data| {
+        let text = data
+            .get("text")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_owned();
+        let runs = if text.is_empty() {
+            json!([])
+        } else {
+            json!([{ "id": [1, 0], "text": text }])
+        };
+        data.insert("text".to_owned(), runs);
+    });
+}
+
+/// Version 10 added link tiles. Older documents have none, so there is nothing to change, but
+/// older code can't read the new tiles.
+fn v9_to_v10(_doc: &mut Map<String, Value>) {}
+
+/// Version 11 added feed tiles, which older documents don't have either.
+fn v10_to_v11(_doc: &mut Map<String, Value>) {}
@@ -0,0 +1,385 @@
+//! The text of note tiles, stored so that concurrent edits from different devices merge character
+//! by character instead of one copy overwriting the other.
+//!
+//! This is a replicated growable array (RGA). Every character ever typed keeps a unique `Id` and
+//! the `Id` of the character it was typed after, its origin. Deleted characters stay behind as
+//! tombstones so that later edits can still find their place. Two copies merge by taking every
+//! character either one has, so merging is commutative, associative, and idempotent, and copies
+//! that have seen the same edits always read the same.
+//!
+//! Tombstones would otherwise pile up for as long as a note is edited, so the server compacts them
+//! away when a board is saved, and copies that started from the same text merge with
+//! `merge_since` so that the compacted ones don't come back.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
+
+/// Identifies one copy of a note that makes edits, such as a browser tab. Sites should be chosen
+/// at random, so that two copies are very unlikely to share one.
+pub type Site = u32;
+
+/// The site that text which existed before anyone edited it is written by.
+pub const ORIGINAL_SITE: Site = 0;
+
+/// Identifies a single character. `Id`s are ordered first by counter, which is higher than that of
+/// every character the site had seen when it made the edit, and then by site.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Id(pub u64, pub Site);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Char {
+    id: Id,
+    /// The character this one was typed after, or `None` if it was typed at the start.
+    origin: Option<Id>,
+    value: char,
+    deleted: bool,
+}
+
+/// The text of a note, along with what's needed to merge it with other copies.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(try_from = "Vec<Run>", into = "Vec<Run>")]
+pub struct Text {
+    /// Every character, including deleted ones, in document order.
+    chars: Vec<Char>,
+}
+
+/// A spot between two characters that stays put as the text around it changes, for keeping a
+/// cursor in place when someone else's edits are merged in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Anchor(Option<Id>);
+
+impl Text {
+    /// Text that nobody has edited yet.
+    pub fn new(text: &str) -> Self {
+        let mut doc = Self::default();
+        doc.splice(ORIGINAL_SITE, 0, 0, text);
+        doc
+    }
+
+    /// Whether there's no text to show, even if some was deleted.
+    pub fn is_empty(&self) -> bool {
+        self.chars.iter().all(|c| c.deleted)
+    }
+
+    /// The number of characters that haven't been deleted.
+    pub fn len(&self) -> usize {
+        self.visible().count()
+    }
+
+    /// The number of characters stored, including deleted ones.
+    pub fn stored_len(&self) -> usize {
+        self.chars.len()
+    }
+
+    /// The IDs of the characters that haven't been deleted, in order.
+    #[cfg(test)]
+    pub(crate) fn visible_ids(&self) -> Vec<Id> {
+        self.visible().map(|c| c.id).collect()
+    }
+
+    fn visible(&self) -> impl Iterator<Item = &Char> {
+        self.chars.iter().filter(|c| !c.deleted)
+    }
+
+    /// Where in `chars` the character at `index` in the visible text is, or `chars.len()` if it's
+    /// past the end.
+    fn position(&self, index: usize) -> usize {
+        self.chars
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !c.deleted)
+            .nth(index)
+            .map_or(self.chars.len(), |(i, _)| i)
+    }
+
+    /// Deletes `delete` characters starting at `index`, then inserts `insert` in their place, as an
+    /// edit made by `site`. Indexes count characters, not bytes.
+    pub fn splice(&mut self, site: Site, index: usize, delete: usize, insert: &str) {
+        let start = self.position(index);
+        let mut remaining = delete;
+        for c in self.chars[start..].iter_mut() {
+            if remaining == 0 {
+                break;
+            }
+            if !c.deleted {
+                c.deleted = true;
+                remaining -= 1;
+            }
+        }
+
+        // A new character's ID is higher than every ID already here, so it goes right after its
+        // origin, ahead of anything else that was typed after the same character.
+        let mut counter = self.chars.iter().map(|c| c.id.0).max().unwrap_or(0);
+        let mut origin = self.chars[..start]
+            .iter()
+            .rev()
+            .find(|c| !c.deleted)
+            .map(|c| c.id);
+        let at = match origin {
+            None => 0,
+            Some(id) => self.chars.iter().position(|c| c.id == id).unwrap() + 1,
+        };
+        for (i, value) in insert.chars().enumerate() {
+            counter += 1;
+            let id = Id(counter, site);
+            self.chars.insert(
+                at + i,
+                Char {
+                    id,
+                    origin,
+                    value,
+                    deleted: false,
+                },
+            );
+            origin = Some(id);
+        }
+    }
+
+    /// Takes in every edit that `other` has and this copy doesn't.
+    pub fn merge(&mut self, other: &Text) {
+        let mut chars: HashMap<Id, Char> = self.chars.drain(..).map(|c| (c.id, c)).collect();
+        for c in other.chars.iter() {
+            chars
+                .entry(c.id)
+                .and_modify(|mine| mine.deleted |= c.deleted)
+                .or_insert_with(|| c.clone());
+        }
+        self.chars = linearize(chars);
+    }
+
+    /// Like `merge`, but for two copies that both started out as `base`. Anything in `base` that
+    /// one copy no longer has was deleted and compacted away there (see `compact`), so it stays
+    /// gone instead of coming back as a tombstone, unless the other copy typed something after it.
+    pub fn merge_since(&mut self, base: &Text, other: &Text) {
+        let mine: HashMap<Id, Char> = self.chars.drain(..).map(|c| (c.id, c)).collect();
+        let theirs: HashMap<Id, &Char> = other.chars.iter().map(|c| (c.id, c)).collect();
+        let compacted: HashSet<Id> = base
+            .chars
+            .iter()
+            .map(|c| c.id)
+            .filter(|id| mine.contains_key(id) != theirs.contains_key(id))
+            .collect();
+
+        let mut chars: HashMap<Id, Char> = HashMap::new();
+        for c in mine.values().chain(theirs.values().copied()) {
+            chars
+                .entry(c.id)
+                .and_modify(|merged| merged.deleted |= c.deleted)
+                .or_insert_with(|| Char {
+                    deleted: c.deleted || compacted.contains(&c.id),
+                    ..c.clone()
+                });
+        }
+
+        // Compacting moves whatever was typed after a tombstone to the tombstone's origin, so
+        // where the copies disagree about a character's origin, the older one is the compacted
+        // copy's. Those moves only keep things in place while the tombstones they passed are
+        // gone, so if anything still comes after a compacted character, they're all brought back.
+        let origins = |id: &Id| match (
+            mine.get(id).map(|c| c.origin),
+            theirs.get(id).map(|c| c.origin),
+        ) {
+            (Some(a), Some(b)) => (a.min(b), a.max(b)),
+            (a, b) => {
+                let only = a.or(b).flatten();
+                (only, only)
+            }
+        };
+        let restore = chars
+            .keys()
+            .filter(|id| !compacted.contains(id))
+            .any(|id| matches!(origins(id).0, Some(origin) if compacted.contains(&origin)));
+        if restore {
+            for c in chars.values_mut() {
+                c.origin = origins(&c.id).1;
+            }
+        } else {
+            chars.retain(|id, _| !compacted.contains(id));
+            for c in chars.values_mut() {
+                c.origin = origins(&c.id).0;
+            }
+        }
+        self.chars = linearize(chars);
+    }
+
+    /// Drops as many tombstones as can go without changing where anything else ends up, by moving
+    /// what was typed after each one to the tombstone's own origin. Copies that still have the
+    /// tombstones can catch up with `merge_since`.
+    pub fn compact(&mut self) {
+        let mut children: HashMap<Option<Id>, Vec<Id>> = HashMap::new();
+        for c in self.chars.iter() {
+            children.entry(c.origin).or_default().push(c.id);
+        }
+        for siblings in children.values_mut() {
+            siblings.sort_unstable();
+        }
+        let mut chars: HashMap<Id, Char> = self.chars.drain(..).map(|c| (c.id, c)).collect();
+
+        // Newest first, so everything typed after a tombstone has already been compacted.
+        let mut tombstones: Vec<Id> = chars.values().filter(|c| c.deleted).map(|c| c.id).collect();
+        tombstones.sort_unstable_by(|a, b| b.cmp(a));
+        for id in tombstones {
+            let origin = chars[&id].origin;
+            let after = children.remove(&Some(id)).unwrap_or_default();
+            let siblings = children.get_mut(&origin).unwrap();
+            let i = siblings.binary_search(&id).unwrap();
+            // Siblings go newest first, so the tombstone's children only land where it was if
+            // they're all older than the sibling that comes just before it.
+            if let (Some(last), Some(newer)) = (after.last(), siblings.get(i + 1)) {
+                if last > newer {
+                    children.insert(Some(id), after);
+                    continue;
+                }
+            }
+            siblings.remove(i);
+            for child in after {
+                let j = siblings.binary_search(&child).unwrap_err();
+                siblings.insert(j, child);
+                chars.get_mut(&child).unwrap().origin = origin;
+            }
+            chars.remove(&id);
+        }
+        self.chars = linearize(chars);
+    }
+
+    /// The spot just before the character at `index`.
+    pub fn anchor(&self, index: usize) -> Anchor {
+        let before = self.chars[..self.position(index)]
+            .iter()
+            .rev()
+            .find(|c| !c.deleted);
+        Anchor(before.map(|c| c.id))
+    }
+
+    /// Where an anchor is now, as an index into the visible text.
+    pub fn index(&self, anchor: Anchor) -> usize {
+        let end = match anchor.0 {
+            None => return 0,
+            Some(id) => match self.chars.iter().position(|c| c.id == id) {
+                Some(i) => i + 1,
+                None => return 0,
+            },
+        };
+        self.chars[..end].iter().filter(|c| !c.deleted).count()
+    }
+}
+
+impl fmt::Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.visible() {
+            write!(f, "{}", c.value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Puts characters in document order: each character comes right after its origin, and
+/// characters with the same origin go newest first, each followed by whatever was typed after it.
+fn linearize(mut chars: HashMap<Id, Char>) -> Vec<Char> {
+    let mut children: HashMap<Option<Id>, Vec<Id>> = HashMap::new();
+    for c in chars.values() {
+        children.entry(c.origin).or_default().push(c.id);
+    }
+    for siblings in children.values_mut() {
+        siblings.sort_unstable();
+    }
+
+    // Each list of siblings is sorted oldest first, so popping from the end visits newest first.
+    let mut order = Vec::with_capacity(chars.len());
+    let mut stack = children.remove(&None).unwrap_or_default();
+    while let Some(id) = stack.pop() {
+        if let Some(c) = chars.remove(&id) {
+            order.push(c);
+        }
+        if let Some(mut after) = children.remove(&Some(id)) {
+            stack.append(&mut after);
+        }
+    }
+    order
+}
+
+/// How `Text` is written out. Typing usually adds characters one after another, so characters
+/// are grouped into runs where each one has the next `Id` from the same site, was typed after the
+/// one before it, and is deleted or not along with the rest.
+#[derive(Serialize, Deserialize)]
+struct Run {
+    /// The first character's ID.
+    id: Id,
+    /// The first character's origin.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    origin: Option<Id>,
+    text: String,
+    #[serde(default, skip_serializing_if = "is_false")]
+    deleted: bool,
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+impl From<Text> for Vec<Run> {
+    fn from(text: Text) -> Self {
+        let mut runs: Vec<Run> = Vec::new();
+        let mut last: Option<Id> = None;
+        for c in text.chars {
+            match runs.last_mut() {
+                Some(run)
+                    if run.deleted == c.deleted
+                        && c.origin == last
+                        && last.map(|Id(n, site)| Id(n + 1, site)) == Some(c.id) =>
+                {
+                    run.text.push(c.value)
+                }
+                _ => runs.push(Run {
+                    id: c.id,
+                    origin: c.origin,
+                    text: c.value.to_string(),
+                    deleted: c.deleted,
+                }),
+            }
+            last = Some(c.id);
+        }
+        runs
+    }
+}
+
+impl TryFrom<Vec<Run>> for Text {
+    type Error = String;
+
+    fn try_from(runs: Vec<Run>) -> Result<Self, Self::Error> {
+        let mut seen = HashSet::new();
+        let mut chars = HashMap::new();
+        for run in runs {
+            let Id(first, site) = run.id;
+            let mut origin = run.origin;
+            for (i, value) in run.text.chars().enumerate() {
+                let id = Id(first + i as u64, site);
+                // Every origin must come first, which also rules out cycles.
+                if let Some(o) = origin.filter(|o| !seen.contains(o)) {
+                    return Err(format!(
+                        "note character {:?} comes before its origin {:?}",
+                        id, o
+                    ));
+                }
+                if !seen.insert(id) {
+                    return Err(format!("note character {:?} appears more than once", id));
+                }
+                chars.insert(
+                    id,
+                    Char {
+                        id,
+                        origin,
+                        value,
+                        deleted: run.deleted,
+                    },
+                );
+                origin = Some(id);
+            }
+        }
+        Ok(Self {
+            chars: linearize(chars),
+        })
+    }
+}
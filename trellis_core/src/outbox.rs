@@ -0,0 +1,227 @@
+//! Keeps a user's edits to their settings until the server has them, so nothing is lost while
+//! offline or when a save fails. This is only the bookkeeping: whoever owns an `Outbox` sends the
+//! requests it asks for and reports back how each one went.
+
+use super::api::Snapshot;
+use super::config::{self, Config, Conflict};
+use super::migrate::{self, MigrationError};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+
+/// How many times in a row saving can fail before it counts as an error rather than pending.
+pub const FAILURES_BEFORE_ERROR: u32 = 3;
+
+/// How long to wait before the first retry. Each retry after that waits twice as long.
+const FIRST_RETRY: Duration = Duration::from_secs(1);
+
+const MAX_RETRY: Duration = Duration::from_secs(5 * 60);
+
+/// Whether the user's edits have reached the server.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Saved,
+    /// There are edits the server doesn't have yet, which are being sent or will be retried.
+    Pending,
+    /// The server turned the edits down, or saving keeps failing.
+    Error,
+}
+
+/// How a save request went.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum Response {
+    Saved {
+        revision: i64,
+    },
+    /// Someone else saved first. This is their copy.
+    Conflict(Snapshot),
+    /// The server refused the settings, so sending them again won't help.
+    Rejected,
+    /// The request didn't get through, or the server had trouble with it. Worth trying again.
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Outbox {
+    /// The server revision that `base` is.
+    revision: i64,
+    /// The server copy, as last seen.
+    base: Config,
+    /// The user's settings, if they have edits the server doesn't. Later edits replace earlier
+    /// ones, since every save sends the whole config.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pending: Option<Config>,
+    #[serde(default)]
+    rejected: bool,
+    /// What's being saved right now. A save that was interrupted is simply sent again.
+    #[serde(skip)]
+    sending: Option<Config>,
+    /// The newest copy pushed from the server while a save was on its way, to take in once the
+    /// save is back.
+    #[serde(skip)]
+    received: Option<Snapshot>,
+    /// How many saves in a row have failed.
+    #[serde(skip)]
+    failures: u32,
+}
+
+impl Default for Outbox {
+    fn default() -> Self {
+        Self::new(Snapshot {
+            revision: 0,
+            config: Config::default(),
+        })
+    }
+}
+
+impl Outbox {
+    /// Starts out in step with the server.
+    pub fn new(snapshot: Snapshot) -> Self {
+        Self {
+            revision: snapshot.revision,
+            base: snapshot.config,
+            pending: None,
+            rejected: false,
+            sending: None,
+            received: None,
+            failures: 0,
+        }
+    }
+
+    /// Reads an outbox that was stored with `serde_json`, upgrading the configs in it from older
+    /// schema versions.
+    pub fn restore(s: &str) -> Result<Self, MigrationError> {
+        let mut value: Value = serde_json::from_str(s)?;
+        if let Some(doc) = value.as_object_mut() {
+            for key in ["base", "pending"].iter() {
+                if let Some(config) = doc.remove(*key) {
+                    doc.insert((*key).to_owned(), migrate::migrate(config)?);
+                }
+            }
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// The settings as the user should see them, edits and all.
+    pub fn config(&self) -> &Config {
+        self.pending.as_ref().unwrap_or(&self.base)
+    }
+
+    /// The server revision that the settings are based on.
+    pub fn revision(&self) -> i64 {
+        self.revision
+    }
+
+    pub fn status(&self) -> Status {
+        if self.rejected || self.failures >= FAILURES_BEFORE_ERROR {
+            Status::Error
+        } else if self.pending.is_some() {
+            Status::Pending
+        } else {
+            Status::Saved
+        }
+    }
+
+    /// Queues the user's newest settings to be saved.
+    pub fn edit(&mut self, config: Config) {
+        self.pending = Some(config);
+        self.rejected = false;
+    }
+
+    /// The save to send now, if there's anything to save and no other save is on its way. The
+    /// result must be reported with `respond`.
+    pub fn next_save(&mut self) -> Option<Snapshot> {
+        if self.sending.is_some() || self.rejected {
+            return None;
+        }
+        let config = self.pending.clone()?;
+        self.sending = Some(config.clone());
+        Some(Snapshot {
+            revision: self.revision,
+            config,
+        })
+    }
+
+    /// Takes in how the last save from `next_save` went. Returns any clashes that came up merging
+    /// the user's edits with someone else's.
+    pub fn respond(&mut self, response: Response) -> Vec<Conflict> {
+        let sent = self.sending.take();
+        let mut conflicts = match response {
+            Response::Saved { revision } => {
+                self.failures = 0;
+                if let Some(sent) = sent {
+                    self.revision = revision;
+                    // Anything edited while the save was on its way still needs saving.
+                    if self.pending.as_ref() == Some(&sent) {
+                        self.pending = None;
+                    }
+                    self.base = sent;
+                }
+                Vec::new()
+            }
+            Response::Conflict(current) => {
+                self.failures = 0;
+                self.merge(current)
+            }
+            Response::Rejected => {
+                self.failures = 0;
+                self.rejected = true;
+                Vec::new()
+            }
+            Response::Failed => {
+                self.failures += 1;
+                Vec::new()
+            }
+        };
+        // Anything pushed while the save was out that's newer than what it came back with.
+        if let Some(received) = self.received.take() {
+            if received.revision > self.revision {
+                conflicts.extend(self.merge(received));
+            }
+        }
+        conflicts
+    }
+
+    /// Takes in a copy from the server that was loaded or pushed rather than sent back from a
+    /// save. Returns any clashes that came up merging the user's edits into it.
+    pub fn receive(&mut self, current: Snapshot) -> Vec<Conflict> {
+        if current.revision <= self.revision {
+            return Vec::new();
+        }
+        // The save that's on its way was based on the old revision, so wait to see how it went.
+        if self.sending.is_some() {
+            if !matches!(&self.received, Some(received) if received.revision >= current.revision) {
+                self.received = Some(current);
+            }
+            return Vec::new();
+        }
+        self.merge(current)
+    }
+
+    /// How long to wait before trying again, if the last save failed.
+    pub fn retry_after(&self) -> Option<Duration> {
+        if self.failures == 0 || self.pending.is_none() {
+            return None;
+        }
+        Some(backoff(self.failures))
+    }
+
+    /// Bases the user's edits on a newer server copy.
+    fn merge(&mut self, current: Snapshot) -> Vec<Conflict> {
+        let local = self.pending.take().unwrap_or_else(|| self.base.clone());
+        let merged = config::merge(&self.base, &local, &current.config);
+        self.revision = current.revision;
+        self.base = current.config;
+        self.rejected = false;
+        if merged.config != self.base {
+            self.pending = Some(merged.config);
+        }
+        merged.conflicts
+    }
+}
+
+/// How long to wait before trying something again after it has failed this many times in a row.
+pub fn backoff(failures: u32) -> Duration {
+    let doublings = failures.saturating_sub(1).min(16);
+    (FIRST_RETRY * 2u32.pow(doublings)).min(MAX_RETRY)
+}
@@ -0,0 +1,162 @@
+//! Reading and writing whole boards as standalone files, for backups and for moving a board
+//! between accounts.
+
+use super::config::{Config, Secrets, ValidationError};
+use super::migrate::{self, MigrationError};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// A file format that boards can be exported to and imported from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    /// Easier to edit by hand than JSON.
+    Toml,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Toml => "toml",
+        }
+    }
+
+    /// Guesses the format of a file from its name, defaulting to JSON.
+    pub fn from_file_name(name: &str) -> Self {
+        if name.to_ascii_lowercase().ends_with(".toml") {
+            Self::Toml
+        } else {
+            Self::Json
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "toml" => Ok(Self::Toml),
+            _ => Err(format!("unknown format: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    Json(serde_json::Error),
+    Toml(toml::ser::Error),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(err) => write!(f, "could not write JSON: {}", err),
+            Self::Toml(err) => write!(f, "could not write TOML: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+#[derive(Debug)]
+pub enum ImportError {
+    /// The file isn't valid in the format it claims to be.
+    Parse(String),
+    /// The file parsed, but isn't a config of any known version.
+    Migrate(MigrationError),
+    /// The file is a config, but wouldn't pass validation.
+    Invalid(Vec<ValidationError>),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(err) => write!(f, "could not parse file: {}", err),
+            Self::Migrate(err) => write!(f, "{}", err),
+            Self::Invalid(problems) => write!(f, "invalid board ({} problems)", problems.len()),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+impl From<MigrationError> for ImportError {
+    fn from(err: MigrationError) -> Self {
+        Self::Migrate(err)
+    }
+}
+
+/// A board read from a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Imported {
+    pub config: Config,
+    /// The secrets, if the file included them.
+    pub secrets: Option<Secrets>,
+}
+
+/// Writes a board to a file. Secrets are left out unless they are given, so the file is safe to
+/// share by default.
+pub fn export(
+    config: &Config,
+    secrets: Option<&Secrets>,
+    format: Format,
+) -> Result<String, ExportError> {
+    let mut doc = serde_json::to_value(config).map_err(ExportError::Json)?;
+    if let (Some(secrets), Value::Object(doc)) = (secrets, &mut doc) {
+        let secrets = serde_json::to_value(secrets).map_err(ExportError::Json)?;
+        doc.insert("secrets".to_owned(), secrets);
+    }
+    match format {
+        Format::Json => serde_json::to_string_pretty(&doc).map_err(ExportError::Json),
+        // TOML needs plain values written before tables, which toml's own `Value` takes care of.
+        Format::Toml => toml::Value::try_from(&doc)
+            .and_then(|doc| toml::to_string_pretty(&doc))
+            .map_err(ExportError::Toml),
+    }
+}
+
+/// Reads a board from a file, upgrading it from older schema versions and validating the result.
+pub fn import(s: &str, format: Format) -> Result<Imported, ImportError> {
+    let mut value: Value = match format {
+        Format::Json => {
+            serde_json::from_str(s).map_err(|err| ImportError::Parse(err.to_string()))?
+        }
+        Format::Toml => toml::from_str(s).map_err(|err| ImportError::Parse(err.to_string()))?,
+    };
+
+    // Secrets are kept next to the config in the file, not inside it, so take them out before the
+    // rest is migrated.
+    let secrets = match value.as_object_mut().and_then(|doc| doc.remove("secrets")) {
+        Some(secrets) => Some(serde_json::from_value(secrets).map_err(MigrationError::from)?),
+        None => None,
+    };
+    let mut config = migrate::from_value(value)?;
+    config.compact_notes();
+    config.validate().map_err(ImportError::Invalid)?;
+    Ok(Imported { config, secrets })
+}
+
+/// Adds an imported board to an existing one. Tiles already on the current board are left alone,
+/// and new tiles are added at the end.
+pub fn merge_into(current: &Config, imported: Config) -> Config {
+    let mut merged = current.clone();
+    let existing: HashSet<_> = current.tiles.iter().map(|t| t.id).collect();
+    merged.tiles.extend(
+        imported
+            .tiles
+            .into_iter()
+            .filter(|t| !existing.contains(&t.id)),
+    );
+    merged
+}
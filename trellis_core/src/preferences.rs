@@ -0,0 +1,200 @@
+//! How tiles show things like measurements, times, and dates. Each board has its own preferences,
+//! and each tile can override any of them.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A system of units for measurements shown on tiles.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Units {
+    /// Degrees Celsius and millimeters.
+    Metric,
+    /// Degrees Fahrenheit and inches.
+    Imperial,
+    /// Kelvin and millimeters.
+    Kelvin,
+}
+
+impl Units {
+    pub const ALL: &'static [Units] = &[Self::Metric, Self::Imperial, Self::Kelvin];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Metric => "Metric (℃, mm)",
+            Self::Imperial => "Imperial (℉, in)",
+            Self::Kelvin => "Kelvin (K, mm)",
+        }
+    }
+
+    /// Converts a temperature from degrees Celsius into these units.
+    pub fn temperature(self, celsius: f64) -> f64 {
+        match self {
+            Self::Metric => celsius,
+            Self::Imperial => celsius * 9.0 / 5.0 + 32.0,
+            Self::Kelvin => celsius + 273.15,
+        }
+    }
+
+    /// Writes a temperature given in degrees Celsius, rounded to a whole number, with its unit.
+    pub fn format_temperature(self, celsius: f64) -> String {
+        let value = self.temperature(celsius);
+        match self {
+            Self::Metric => format!("{:.0}℃", value),
+            Self::Imperial => format!("{:.0}℉", value),
+            Self::Kelvin => format!("{:.0} K", value),
+        }
+    }
+
+    /// Writes an amount of precipitation given in millimeters, with its unit.
+    pub fn format_precipitation(self, mm: f64) -> String {
+        match self {
+            Self::Metric | Self::Kelvin => format!("{:.1} mm", mm),
+            Self::Imperial => format!("{:.2} in", mm / 25.4),
+        }
+    }
+}
+
+impl FromStr for Units {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|u| u.to_string() == s)
+            .ok_or_else(|| format!("unknown units: {}", s))
+    }
+}
+
+impl fmt::Display for Units {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// How to write the time of day.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeFormat {
+    TwelveHour,
+    TwentyFourHour,
+}
+
+impl TimeFormat {
+    pub const ALL: &'static [TimeFormat] = &[Self::TwelveHour, Self::TwentyFourHour];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::TwelveHour => "12-hour",
+            Self::TwentyFourHour => "24-hour",
+        }
+    }
+
+    /// A `strftime` pattern for the time of day, with or without the seconds.
+    pub fn pattern(self, seconds: bool) -> &'static str {
+        match (self, seconds) {
+            (Self::TwelveHour, false) => "%-I:%M %p",
+            (Self::TwelveHour, true) => "%-I:%M:%S %p",
+            (Self::TwentyFourHour, false) => "%H:%M",
+            (Self::TwentyFourHour, true) => "%H:%M:%S",
+        }
+    }
+}
+
+impl FromStr for TimeFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|t| t.to_string() == s)
+            .ok_or_else(|| format!("unknown time format: {}", s))
+    }
+}
+
+impl fmt::Display for TimeFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Preferences {
+    pub units: Units,
+    pub time_format: TimeFormat,
+    /// A language tag like `en-US`, which decides the names of days and months.
+    pub locale: String,
+}
+
+impl Default for Preferences {
+    /// Tiles looked like this before there were preferences.
+    fn default() -> Self {
+        Self {
+            units: Units::Imperial,
+            time_format: TimeFormat::TwentyFourHour,
+            locale: "en-US".to_owned(),
+        }
+    }
+}
+
+impl Preferences {
+    /// Applies a tile's overrides on top of these preferences.
+    pub fn with_overrides(&self, overrides: &Overrides) -> Self {
+        Self {
+            units: overrides.units.unwrap_or(self.units),
+            time_format: overrides.time_format.unwrap_or(self.time_format),
+            locale: overrides
+                .locale
+                .clone()
+                .unwrap_or_else(|| self.locale.clone()),
+        }
+    }
+
+    /// A `strftime` pattern for a full date, like "Thursday, August 26, 2021".
+    pub fn long_date_pattern(&self) -> &'static str {
+        // Only American English puts the month before the day.
+        if self.locale == "en-US" {
+            "%A, %B %-d, %Y"
+        } else {
+            "%A, %-d %B %Y"
+        }
+    }
+
+    /// A `strftime` pattern for a day without its year or weekday, like "Sep 7".
+    pub fn short_date_pattern(&self) -> &'static str {
+        if self.locale == "en-US" {
+            "%b %-d"
+        } else {
+            "%-d %b"
+        }
+    }
+}
+
+/// The preferences that a single tile shows differently from the rest of its board. Anything left
+/// out comes from the board.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Overrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub units: Option<Units>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_format: Option<TimeFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+}
+
+impl Overrides {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Checks that a locale looks like a language tag, such as `en` or `pt-BR`. Whether there are
+/// day and month names for it is up to whoever shows the dates.
+pub fn is_valid_locale(locale: &str) -> bool {
+    let mut parts = locale.split('-');
+    let language = parts.next().unwrap_or_default();
+    (2..=3).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_alphabetic())
+        && parts.all(|p| (1..=8).contains(&p.len()) && p.chars().all(|c| c.is_ascii_alphanumeric()))
+}
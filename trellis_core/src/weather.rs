@@ -0,0 +1,191 @@
+//! Weather as shown on weather tiles, in the same shape whichever service it came from.
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A weather service that weather tiles can get their reports from.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Provider {
+    /// Needs an API key. Locations are `latitude,longitude` pairs, or OpenWeatherMap city IDs.
+    OpenWeatherMap,
+    /// Free, with no key. Locations are `latitude,longitude` pairs.
+    OpenMeteo,
+    /// The US National Weather Service, which only covers the United States. Locations are
+    /// `latitude,longitude` pairs.
+    Nws,
+}
+
+impl Provider {
+    pub const ALL: &'static [Provider] = &[Self::OpenWeatherMap, Self::OpenMeteo, Self::Nws];
+
+    /// The human-readable name of the service.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::OpenWeatherMap => "OpenWeatherMap",
+            Self::OpenMeteo => "Open-Meteo",
+            Self::Nws => "National Weather Service",
+        }
+    }
+
+    /// Whether the service needs the user's own API key.
+    pub fn needs_api_key(self) -> bool {
+        matches!(self, Self::OpenWeatherMap)
+    }
+}
+
+impl Default for Provider {
+    /// Weather tiles used OpenWeatherMap before there was a choice.
+    fn default() -> Self {
+        Self::OpenWeatherMap
+    }
+}
+
+impl FromStr for Provider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|p| p.to_string() == s)
+            .ok_or_else(|| format!("unknown weather provider: {}", s))
+    }
+}
+
+impl fmt::Display for Provider {
+    /// Writes the same identifier that is stored in configs.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let id = match self {
+            Self::OpenWeatherMap => "OpenWeatherMap",
+            Self::OpenMeteo => "OpenMeteo",
+            Self::Nws => "Nws",
+        };
+        write!(f, "{}", id)
+    }
+}
+
+/// What a weather tile shows.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// The conditions right now.
+    Current,
+    /// Hourly conditions for the next day, and highs and lows for the next week.
+    Forecast,
+}
+
+impl Mode {
+    pub const ALL: &'static [Mode] = &[Self::Current, Self::Forecast];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Current => "Current conditions",
+            Self::Forecast => "Forecast",
+        }
+    }
+}
+
+impl Default for Mode {
+    /// Weather tiles only showed current conditions before there was a choice.
+    fn default() -> Self {
+        Self::Current
+    }
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Current" => Ok(Self::Current),
+            "Forecast" => Ok(Self::Forecast),
+            _ => Err(format!("unknown weather mode: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// A place found by searching for it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Place {
+    /// Enough of the place's name to tell it apart from others, like "Portland, Oregon, US".
+    pub name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl Place {
+    /// The location ID of the place, which every provider understands.
+    pub fn location_id(&self) -> String {
+        // Four decimal places is about ten meters, which is more than enough for the weather.
+        format!("{:.4},{:.4}", self.latitude, self.longitude)
+    }
+}
+
+/// The current conditions at one location.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WeatherReport {
+    /// The name of the location, as the weather service knows it.
+    pub location_name: String,
+    pub description: String,
+    /// In degrees Celsius.
+    pub temperature: f64,
+    /// An image of the conditions, if the service has one.
+    pub icon_url: Option<String>,
+    /// When the conditions were observed.
+    pub observed_at: DateTime<Utc>,
+    /// The service the report came from, for attribution.
+    pub source_name: String,
+    /// Where to see more about this location on the service's website.
+    pub source_url: Option<String>,
+}
+
+/// The expected conditions at one location over the next several days.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Forecast {
+    pub location_name: String,
+    /// The next 24 hours or so, in order. Some services only forecast every few hours.
+    pub hourly: Vec<HourlyForecast>,
+    /// The next five to seven days, in order, starting with today.
+    pub daily: Vec<DailyForecast>,
+    pub source_name: String,
+    pub source_url: Option<String>,
+}
+
+impl Forecast {
+    /// Drops the hours that have already passed or are more than a day away.
+    pub fn upcoming(mut self, now: DateTime<Utc>) -> Self {
+        let start = now - Duration::hours(1);
+        let end = now + Duration::hours(24);
+        self.hourly.retain(|h| h.time > start && h.time <= end);
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HourlyForecast {
+    pub time: DateTime<Utc>,
+    /// In degrees Celsius.
+    pub temperature: f64,
+    /// The chance of any precipitation, as a percentage, if the service predicts it.
+    pub precipitation_chance: Option<f64>,
+    /// The expected amount of precipitation in millimeters, if the service predicts it.
+    pub precipitation: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DailyForecast {
+    /// The day, in the location's own time zone.
+    pub date: NaiveDate,
+    pub description: String,
+    /// In degrees Celsius.
+    pub high: f64,
+    /// In degrees Celsius.
+    pub low: f64,
+}
@@ -0,0 +1,127 @@
+use chrono::Utc;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use rocket::tokio::fs;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Something that can deliver emails. The backend is chosen by the `mailer.backend` config key.
+#[rocket::async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: Email) -> anyhow::Result<()>;
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Config {
+    pub from: String,
+    #[serde(flatten)]
+    pub backend: Backend,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum Backend {
+    /// Deliver mail through an SMTP relay.
+    Smtp {
+        host: String,
+        port: Option<u16>,
+        username: String,
+        password: String,
+    },
+    /// Write each email to a file in a directory. Useful for local development and tests.
+    File { path: PathBuf },
+    /// Write each email to the server log.
+    Log,
+}
+
+impl Config {
+    pub fn build(self) -> anyhow::Result<Box<dyn Mailer>> {
+        let mailer: Box<dyn Mailer> = match self.backend {
+            Backend::Smtp {
+                host,
+                port,
+                username,
+                password,
+            } => {
+                let mut builder = AsyncSmtpTransport::<Tokio1Executor>::relay(&host)?
+                    .credentials(Credentials::new(username, password));
+                if let Some(port) = port {
+                    builder = builder.port(port);
+                }
+                Box::new(SmtpMailer {
+                    from: self.from,
+                    transport: builder.build(),
+                })
+            }
+            Backend::File { path } => Box::new(FileMailer {
+                from: self.from,
+                path,
+            }),
+            Backend::Log => Box::new(LogMailer { from: self.from }),
+        };
+        Ok(mailer)
+    }
+}
+
+pub struct SmtpMailer {
+    from: String,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+#[rocket::async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: Email) -> anyhow::Result<()> {
+        let message = Message::builder()
+            .from(self.from.parse()?)
+            .to(email.to.parse()?)
+            .subject(email.subject)
+            .body(email.body)?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+pub struct FileMailer {
+    from: String,
+    path: PathBuf,
+}
+
+#[rocket::async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: Email) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.path).await?;
+        let name = format!(
+            "{}-{}.eml",
+            Utc::now().format("%Y%m%dT%H%M%S%.f"),
+            email.to.replace(|c: char| !c.is_ascii_alphanumeric(), "_"),
+        );
+        fs::write(self.path.join(name), format_email(&self.from, &email)).await?;
+        Ok(())
+    }
+}
+
+pub struct LogMailer {
+    from: String,
+}
+
+#[rocket::async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, email: Email) -> anyhow::Result<()> {
+        log::info!("sending email:\n{}", format_email(&self.from, &email));
+        Ok(())
+    }
+}
+
+fn format_email(from: &str, email: &Email) -> String {
+    format!(
+        "From: {}\nTo: {}\nSubject: {}\n\n{}\n",
+        from, email.to, email.subject, email.body
+    )
+}
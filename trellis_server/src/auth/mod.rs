@@ -1,18 +1,256 @@
-use serde::{Deserialize, Serialize};
+use super::models;
+use super::schema;
+use chrono::{Duration, Utc};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use rocket::fairing::AdHoc;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::time::Duration as StdDuration;
 use thiserror::Error;
+use url::Url;
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct User {
-    pub user_id: String,
-}
+pub mod mailer;
+pub mod session;
+pub mod throttle;
+
+pub use super::models::User;
+pub use session::{Session, UserAgent};
+
+/// How long a magic link stays valid after it is sent.
+const TOKEN_LIFETIME_MINUTES: i64 = 15;
+
+/// Number of random bytes in a magic link or share link token.
+const TOKEN_BYTES: usize = 32;
+
+/// How many unexpired magic links one address can have at once. Asking for more is refused, so
+/// nobody can flood someone else's inbox through the login form.
+const MAX_PENDING_TOKENS: i64 = 5;
+
+/// How many magic links one client can ask for in an hour, whichever addresses they're for.
+const LOGINS_PER_CLIENT_PER_HOUR: usize = 20;
 
 #[derive(Error, Debug)]
 pub enum AuthError {
-    #[error("authentication disabled")]
-    Disabled,
+    #[error("invalid or expired token")]
+    InvalidToken,
+    #[error("invalid email address")]
+    InvalidEmail,
+    #[error("missing, expired, or revoked session")]
+    InvalidSession,
+    #[error("too many login links requested")]
+    TooManyRequests,
+    #[error("database unavailable")]
+    DatabaseUnavailable,
+    #[error(transparent)]
+    Database(#[from] diesel::result::Error),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Config {
+    /// The externally-visible URL of the API server, used to build magic links.
+    pub public_url: Url,
 }
 
-pub fn authenticate(_token: &str) -> Result<User, AuthError> {
-    // TODO: Implement real users someday
-    Err(AuthError::Disabled)
+/// Loads the `auth` and `mailer` configuration sections into managed state, along with the login
+/// throttle.
+pub fn fairing() -> AdHoc {
+    AdHoc::try_on_ignite("Authentication", |rocket| async {
+        let figment = rocket.figment();
+        let config = match figment.extract_inner::<Config>("auth") {
+            Ok(config) => config,
+            Err(err) => {
+                log::error!("invalid auth config: {}", err);
+                return Err(rocket);
+            }
+        };
+        let mailer = match figment.extract_inner::<mailer::Config>("mailer") {
+            Ok(config) => config.build(),
+            Err(err) => {
+                log::error!("invalid mailer config: {}", err);
+                return Err(rocket);
+            }
+        };
+        match mailer {
+            Ok(mailer) => Ok(rocket
+                .manage(config)
+                .manage(mailer)
+                .manage(throttle::Throttle::new(
+                    LOGINS_PER_CLIENT_PER_HOUR,
+                    StdDuration::from_secs(3600),
+                ))),
+            Err(err) => {
+                log::error!("could not build mailer: {}", err);
+                Err(rocket)
+            }
+        }
+    })
+}
+
+/// Builds the magic link that exchanges `token` for a session.
+pub fn login_url(config: &Config, token: &str) -> Url {
+    let mut url = config.public_url.clone();
+    url.path_segments_mut()
+        .expect("cannot be base")
+        .pop_if_empty()
+        .extend(&["v1", "authenticate"]);
+    url.query_pairs_mut().append_pair("token", token);
+    url
+}
+
+/// Normalizes an email address enough to use it as an account identifier.
+pub fn normalize_email(email: &str) -> Result<String, AuthError> {
+    let email = email.trim().to_lowercase();
+    match email.split_once('@') {
+        Some((local, domain)) if !local.is_empty() && !domain.is_empty() => Ok(email),
+        _ => Err(AuthError::InvalidEmail),
+    }
+}
+
+/// Mints a single-use login token for `email` and returns the plaintext token. Only a hash of the
+/// token is stored, so a leaked database row cannot be used to log in.
+pub fn create_login_token(conn: &PgConnection, email: &str) -> Result<String, AuthError> {
+    let email = normalize_email(email)?;
+    let pending: i64 = {
+        use schema::login_tokens::dsl;
+        dsl::login_tokens
+            .filter(dsl::email.eq(&email))
+            .filter(dsl::expires_at.gt(diesel::dsl::now))
+            .count()
+            .get_result(conn)?
+    };
+    if pending >= MAX_PENDING_TOKENS {
+        return Err(AuthError::TooManyRequests);
+    }
+
+    let token = new_token();
+    let new_token = models::NewLoginToken {
+        token_hash: hash_token(&token),
+        email,
+        expires_at: (Utc::now() + Duration::minutes(TOKEN_LIFETIME_MINUTES)).naive_utc(),
+    };
+    diesel::insert_into(schema::login_tokens::table)
+        .values(&new_token)
+        .execute(conn)?;
+
+    Ok(token)
+}
+
+/// Consumes a login token, returning the user it was issued for. Each token can be used at most
+/// once, and only until it expires. The first successful login for an email address creates the
+/// user.
+pub fn authenticate(conn: &PgConnection, token: &str) -> Result<User, AuthError> {
+    conn.transaction(|| {
+        let row = {
+            use schema::login_tokens::dsl::*;
+            diesel::update(
+                login_tokens
+                    .filter(token_hash.eq(hash_token(token)))
+                    .filter(used_at.is_null())
+                    .filter(expires_at.gt(diesel::dsl::now)),
+            )
+            .set(used_at.eq(diesel::dsl::now))
+            .get_result::<models::LoginToken>(conn)
+            .optional()?
+        };
+
+        match row {
+            Some(row) => Ok(upsert_user(conn, row.email)?),
+            None => Err(AuthError::InvalidToken),
+        }
+    })
+}
+
+/// Deletes login tokens and sessions that have expired, which can never be used again. Returns how
+/// many rows were deleted.
+pub fn delete_expired(conn: &PgConnection) -> QueryResult<usize> {
+    let tokens = {
+        use schema::login_tokens::dsl::*;
+        diesel::delete(login_tokens.filter(expires_at.lt(diesel::dsl::now))).execute(conn)?
+    };
+    let sessions = {
+        use schema::sessions::dsl::*;
+        diesel::delete(sessions.filter(expires_at.lt(diesel::dsl::now))).execute(conn)?
+    };
+    Ok(tokens + sessions)
+}
+
+/// Finds or creates the user with this email address and records that they just logged in.
+fn upsert_user(conn: &PgConnection, user_email: String) -> QueryResult<User> {
+    use diesel::pg::upsert::excluded;
+    use schema::users::dsl::*;
+
+    let new_user = models::NewUser {
+        email: user_email,
+        last_login_at: Some(Utc::now().naive_utc()),
+    };
+    diesel::insert_into(users)
+        .values(&new_user)
+        .on_conflict(email)
+        .do_update()
+        .set(last_login_at.eq(excluded(last_login_at)))
+        .get_result(conn)
+}
+
+/// Makes a random token that's safe to put in a URL.
+pub fn new_token() -> String {
+    let mut bytes = [0u8; TOKEN_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+/// Hashes a token for storage. Tokens are long and random, so a fast hash is enough.
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_email() {
+        assert_eq!(
+            normalize_email("  Alice@Example.COM ").unwrap(),
+            "alice@example.com"
+        );
+        assert_eq!(
+            normalize_email("bob+boards@example.com").unwrap(),
+            "bob+boards@example.com"
+        );
+        for email in &["", "   ", "alice", "@example.com", "alice@"] {
+            assert!(
+                matches!(normalize_email(email), Err(AuthError::InvalidEmail)),
+                "{:?}",
+                email
+            );
+        }
+    }
+
+    #[test]
+    fn test_login_url() {
+        let token = "abc-_123";
+        for public_url in &[
+            "https://trellis.example/api",
+            "https://trellis.example/api/",
+        ] {
+            let config = Config {
+                public_url: Url::parse(public_url).unwrap(),
+            };
+            assert_eq!(
+                login_url(&config, token).as_str(),
+                "https://trellis.example/api/v1/authenticate?token=abc-_123"
+            );
+        }
+
+        let config = Config {
+            public_url: Url::parse("http://localhost:3000").unwrap(),
+        };
+        assert_eq!(
+            login_url(&config, "a+b/c=").as_str(),
+            "http://localhost:3000/v1/authenticate?token=a%2Bb%2Fc%3D"
+        );
+    }
 }
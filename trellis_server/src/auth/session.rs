@@ -0,0 +1,152 @@
+use super::{AuthError, User};
+use crate::models;
+use crate::schema;
+use crate::DbConn;
+use chrono::{Duration, Utc};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use rocket::http::{Cookie, CookieJar, Status};
+use rocket::outcome::Outcome;
+use rocket::request::{self, FromRequest, Request};
+use uuid::Uuid;
+
+/// Sessions expire after this long without being used.
+const SESSION_LIFETIME_DAYS: i64 = 30;
+
+/// Name of the private cookie holding the session ID.
+const COOKIE_NAME: &str = "session";
+
+/// A request guard for a valid, unexpired session. Using it also marks the session as recently
+/// seen, which pushes back its expiration.
+///
+/// The guard borrows a database connection while it runs, so list it before any `DbConn` guard to
+/// avoid holding two connections at once.
+#[derive(Debug)]
+pub struct Session {
+    pub id: Uuid,
+    pub user: User,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Session {
+    type Error = AuthError;
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let session_id = match req
+            .cookies()
+            .get_private(COOKIE_NAME)
+            .and_then(|cookie| Uuid::parse_str(cookie.value()).ok())
+        {
+            Some(id) => id,
+            None => return Outcome::Failure((Status::Unauthorized, AuthError::InvalidSession)),
+        };
+
+        let db = match req.guard::<DbConn>().await {
+            Outcome::Success(db) => db,
+            _ => {
+                return Outcome::Failure((
+                    Status::ServiceUnavailable,
+                    AuthError::DatabaseUnavailable,
+                ))
+            }
+        };
+
+        match db.run(move |c| touch(c, session_id)).await {
+            Ok(Some(session)) => Outcome::Success(session),
+            Ok(None) => {
+                clear_cookie(req.cookies());
+                Outcome::Failure((Status::Unauthorized, AuthError::InvalidSession))
+            }
+            Err(err) => {
+                log::error!("{}", err);
+                Outcome::Failure((Status::InternalServerError, AuthError::Database(err)))
+            }
+        }
+    }
+}
+
+/// The `User-Agent` header of a request, if there was one.
+pub struct UserAgent(pub Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for UserAgent {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        Outcome::Success(UserAgent(
+            req.headers().get_one("User-Agent").map(String::from),
+        ))
+    }
+}
+
+fn next_expiry() -> chrono::NaiveDateTime {
+    (Utc::now() + Duration::days(SESSION_LIFETIME_DAYS)).naive_utc()
+}
+
+/// Starts a new session for the user.
+pub fn create(
+    conn: &PgConnection,
+    user_id: Uuid,
+    user_agent: Option<String>,
+) -> QueryResult<models::Session> {
+    let new_session = models::NewSession {
+        user_id,
+        user_agent,
+        expires_at: next_expiry(),
+    };
+    diesel::insert_into(schema::sessions::table)
+        .values(&new_session)
+        .get_result(conn)
+}
+
+/// Marks an unexpired session as seen and loads its user.
+fn touch(conn: &PgConnection, session_id: Uuid) -> QueryResult<Option<Session>> {
+    use schema::sessions::dsl::*;
+
+    let row = diesel::update(
+        sessions
+            .find(session_id)
+            .filter(expires_at.gt(diesel::dsl::now)),
+    )
+    .set((
+        last_seen_at.eq(diesel::dsl::now),
+        expires_at.eq(next_expiry()),
+    ))
+    .get_result::<models::Session>(conn)
+    .optional()?;
+
+    match row {
+        None => Ok(None),
+        Some(row) => {
+            let user = schema::users::table.find(row.user_id).first(conn)?;
+            Ok(Some(Session { id: row.id, user }))
+        }
+    }
+}
+
+/// Ends a single session.
+pub fn revoke(conn: &PgConnection, session_id: Uuid) -> QueryResult<usize> {
+    use schema::sessions::dsl::*;
+    diesel::delete(sessions.find(session_id)).execute(conn)
+}
+
+/// Ends every session belonging to the user, on every device.
+pub fn revoke_all(conn: &PgConnection, uid: Uuid) -> QueryResult<usize> {
+    use schema::sessions::dsl::*;
+    diesel::delete(sessions.filter(user_id.eq(uid))).execute(conn)
+}
+
+/// Stores the session ID in the private session cookie.
+pub fn set_cookie(cookies: &CookieJar<'_>, session: &models::Session) {
+    cookies.add_private(
+        Cookie::build(COOKIE_NAME, session.id.to_string())
+            .secure(true)
+            .permanent()
+            .finish(),
+    );
+}
+
+/// Removes the session cookie.
+pub fn clear_cookie(cookies: &CookieJar<'_>) {
+    cookies.remove_private(Cookie::named(COOKIE_NAME));
+}
@@ -0,0 +1,70 @@
+//! Limits how often one client can ask for magic links, so the login form can't be used to send
+//! mail to address after address.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Counts recent requests by client address.
+pub struct Throttle {
+    limit: usize,
+    window: Duration,
+    hits: Mutex<HashMap<IpAddr, VecDeque<Instant>>>,
+}
+
+impl Throttle {
+    /// Allows each client `limit` requests in any `window`.
+    pub fn new(limit: usize, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            hits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records a request from `client`, unless it has already made too many.
+    pub fn allow(&self, client: IpAddr) -> bool {
+        self.allow_at(client, Instant::now())
+    }
+
+    fn allow_at(&self, client: IpAddr, now: Instant) -> bool {
+        let window = self.window;
+        let mut hits = self.hits.lock().expect("login throttle poisoned");
+        // Forget requests that have left the window, and clients with none left.
+        hits.retain(|_, times| {
+            while matches!(times.front(), Some(at) if now.duration_since(*at) >= window) {
+                times.pop_front();
+            }
+            !times.is_empty()
+        });
+        let times = hits.entry(client).or_default();
+        if times.len() >= self.limit {
+            return false;
+        }
+        times.push_back(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throttle_limits_each_client_within_the_window() {
+        let throttle = Throttle::new(2, Duration::from_secs(60));
+        let alice: IpAddr = "203.0.113.1".parse().unwrap();
+        let bob: IpAddr = "203.0.113.2".parse().unwrap();
+        let start = Instant::now();
+
+        assert!(throttle.allow_at(alice, start));
+        assert!(throttle.allow_at(alice, start + Duration::from_secs(10)));
+        assert!(!throttle.allow_at(alice, start + Duration::from_secs(20)));
+        // Other clients have limits of their own.
+        assert!(throttle.allow_at(bob, start + Duration::from_secs(20)));
+        // Once the first request is old enough, there's room for another.
+        assert!(throttle.allow_at(alice, start + Duration::from_secs(60)));
+        assert!(!throttle.allow_at(alice, start + Duration::from_secs(61)));
+    }
+}
@@ -0,0 +1,424 @@
+//! A user's boards. Each one has its own tiles and preferences, saved, versioned, and pushed to
+//! other devices separately from the rest. Boards are found by their slug, which is unique per
+//! user, or by their team key for users that the owner made members.
+
+use super::auth::Session;
+use super::models;
+use super::schema;
+use super::DbConn;
+use diesel::dsl::exists;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use std::collections::HashSet;
+use trellis_core::board::{self, Role};
+use trellis_core::config::Config;
+use trellis_core::{api, migrate};
+use uuid::Uuid;
+
+/// Gives the user their first board, if they don't have any yet. Users who have never saved
+/// anything see the default config.
+fn ensure_default(conn: &PgConnection, uid: Uuid) -> anyhow::Result<()> {
+    use schema::boards::dsl::*;
+
+    let any = diesel::select(exists(boards.filter(user_id.eq(uid)))).get_result::<bool>(conn)?;
+    if any {
+        return Ok(());
+    }
+    diesel::insert_into(boards)
+        .values(&models::NewBoard {
+            data: serde_json::to_value(Config::default())?,
+            user_id: uid,
+            revision: 0,
+            slug: board::DEFAULT_SLUG.to_owned(),
+            name: board::DEFAULT_NAME.to_owned(),
+        })
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+    Ok(())
+}
+
+/// A board, and what the user who found it can do with it.
+pub struct Access {
+    pub board: models::Board,
+    pub role: Role,
+}
+
+/// Finds a board that the user can use: one of their own by its slug, or one they're a member of
+/// by its team key. Owners can find their boards by team key too, so that members can send them
+/// links.
+pub fn find(conn: &PgConnection, uid: Uuid, key: &str) -> anyhow::Result<Option<Access>> {
+    use schema::boards::dsl::*;
+
+    ensure_default(conn, uid)?;
+    if let Some(board_id) = board::parse_team_key(key) {
+        let found = match boards
+            .find(board_id)
+            .first::<models::Board>(conn)
+            .optional()?
+        {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        if found.user_id == uid {
+            return Ok(Some(Access {
+                board: found,
+                role: Role::Owner,
+            }));
+        }
+        let member_role = schema::board_members::table
+            .find((board_id, uid))
+            .select(schema::board_members::role)
+            .first::<String>(conn)
+            .optional()?;
+        return match member_role {
+            Some(r) => Ok(Some(Access {
+                board: found,
+                role: r.parse().map_err(anyhow::Error::msg)?,
+            })),
+            None => Ok(None),
+        };
+    }
+
+    let found = boards
+        .filter(user_id.eq(uid))
+        .filter(slug.eq(key))
+        .first::<models::Board>(conn)
+        .optional()?;
+    Ok(found.map(|found| Access {
+        board: found,
+        role: Role::Owner,
+    }))
+}
+
+/// Why a request can't use a board.
+#[derive(Responder, Debug)]
+pub enum Refusal {
+    #[response(status = 404)]
+    NotFound(&'static str),
+    /// The user's role on the board doesn't allow the request.
+    #[response(status = 403)]
+    Forbidden(&'static str),
+    #[response(status = 500)]
+    Internal(&'static str),
+}
+
+impl From<Refusal> for status::Custom<&'static str> {
+    fn from(refusal: Refusal) -> Self {
+        match refusal {
+            Refusal::NotFound(msg) => status::Custom(Status::NotFound, msg),
+            Refusal::Forbidden(msg) => status::Custom(Status::Forbidden, msg),
+            Refusal::Internal(msg) => status::Custom(Status::InternalServerError, msg),
+        }
+    }
+}
+
+/// Finds a board for a request that needs the user to have at least the `need` role on it.
+pub async fn lookup(db: &DbConn, uid: Uuid, key: String, need: Role) -> Result<Access, Refusal> {
+    match db.run(move |c| find(c, uid, &key)).await {
+        Ok(Some(access)) if access.role >= need => Ok(access),
+        Ok(Some(_)) => Err(Refusal::Forbidden("Forbidden")),
+        Ok(None) => Err(Refusal::NotFound("Not Found")),
+        Err(err) => {
+            log::error!("{}", err);
+            Err(Refusal::Internal("Internal Server Error"))
+        }
+    }
+}
+
+/// Lists the user's own boards, oldest first, followed by the boards they're a member of.
+pub fn list(conn: &PgConnection, uid: Uuid) -> anyhow::Result<api::BoardList> {
+    use schema::boards::dsl::*;
+
+    ensure_default(conn, uid)?;
+    let default_id = schema::users::table
+        .find(uid)
+        .select(schema::users::default_board_id)
+        .first::<Option<Uuid>>(conn)?;
+    let own = boards
+        .filter(user_id.eq(uid))
+        .order((created_at.asc(), id.asc()))
+        .select((id, slug, name))
+        .load::<(Uuid, String, String)>(conn)?;
+    let team = schema::board_members::table
+        // Users also point at boards, through their default board, so this join needs saying how.
+        .inner_join(boards.inner_join(schema::users::table.on(schema::users::id.eq(user_id))))
+        .filter(schema::board_members::user_id.eq(uid))
+        .order((created_at.asc(), id.asc()))
+        .select((id, name, schema::board_members::role, schema::users::email))
+        .load::<(Uuid, String, String, String)>(conn)?;
+
+    let mut rows = Vec::with_capacity(own.len() + team.len());
+    for (board_id, s, n) in own {
+        rows.push((
+            board_id,
+            api::Board {
+                slug: s,
+                name: n,
+                role: Role::Owner,
+                owner: None,
+            },
+        ));
+    }
+    for (board_id, n, r, owner) in team {
+        rows.push((
+            board_id,
+            api::Board {
+                slug: board::team_key(board_id),
+                name: n,
+                role: r.parse().map_err(anyhow::Error::msg)?,
+                owner: Some(owner),
+            },
+        ));
+    }
+
+    // Without a default, or if it was deleted or left, the oldest board is the default.
+    let default = rows
+        .iter()
+        .find(|(board_id, _)| Some(*board_id) == default_id)
+        .or_else(|| rows.first())
+        .map_or_else(|| board::DEFAULT_SLUG.to_owned(), |(_, b)| b.slug.clone());
+    Ok(api::BoardList {
+        boards: rows.into_iter().map(|(_, b)| b).collect(),
+        default,
+    })
+}
+
+/// Picks a slug for a new board that the user doesn't already have, by adding a number to the end
+/// if need be.
+fn unique_slug(conn: &PgConnection, uid: Uuid, board_name: &str) -> QueryResult<String> {
+    use schema::boards::dsl::*;
+
+    let base = board::slugify(board_name);
+    let taken: HashSet<String> = boards
+        .filter(user_id.eq(uid))
+        .filter(slug.like(format!("{}%", base)))
+        .select(slug)
+        .load::<String>(conn)?
+        .into_iter()
+        .collect();
+    Ok(std::iter::once(base.clone())
+        .chain((2..).map(|n| format!("{}-{}", base, n)))
+        .find(|s| !taken.contains(s))
+        .unwrap())
+}
+
+/// Makes a new board with no tiles, using the preferences from the user's default board.
+fn create_board(conn: &PgConnection, uid: Uuid, board_name: String) -> anyhow::Result<api::Board> {
+    use schema::boards::dsl::*;
+
+    conn.transaction(|| {
+        let current = list(conn, uid)?;
+        let preferences = match find(conn, uid, &current.default)? {
+            Some(access) => migrate::from_value(access.board.data)?.preferences,
+            None => Config::default().preferences,
+        };
+        let config = Config {
+            preferences,
+            tiles: Vec::new(),
+            ..Config::default()
+        };
+
+        let new_slug = unique_slug(conn, uid, &board_name)?;
+        diesel::insert_into(boards)
+            .values(&models::NewBoard {
+                data: serde_json::to_value(&config)?,
+                user_id: uid,
+                revision: 0,
+                slug: new_slug.clone(),
+                name: board_name.clone(),
+            })
+            .execute(conn)?;
+        Ok(api::Board {
+            slug: new_slug,
+            name: board_name,
+            role: Role::Owner,
+            owner: None,
+        })
+    })
+}
+
+enum DeleteResult {
+    /// The boards that are left.
+    Deleted(api::BoardList),
+    NotFound,
+    OnlyBoard,
+}
+
+fn delete_board(conn: &PgConnection, uid: Uuid, board_slug: &str) -> anyhow::Result<DeleteResult> {
+    use schema::boards::dsl::*;
+
+    conn.transaction(|| {
+        let current = list(conn, uid)?;
+        let own: Vec<_> = current
+            .boards
+            .iter()
+            .filter(|b| b.role == Role::Owner)
+            .collect();
+        if !own.iter().any(|b| b.slug == board_slug) {
+            return Ok(DeleteResult::NotFound);
+        }
+        if own.len() == 1 {
+            return Ok(DeleteResult::OnlyBoard);
+        }
+        // The user's default falls back to their oldest board if this was it.
+        diesel::delete(boards.filter(user_id.eq(uid)).filter(slug.eq(board_slug))).execute(conn)?;
+        Ok(DeleteResult::Deleted(list(conn, uid)?))
+    })
+}
+
+#[derive(Responder)]
+pub enum BoardError {
+    /// The change would leave the user without any boards.
+    #[response(status = 409)]
+    Conflict(Json<api::Error>),
+    /// The name, of the board or of something on it, is not valid.
+    #[response(status = 422)]
+    Invalid(Json<api::Error>),
+    /// The board doesn't exist, or the user's role on it doesn't allow the change.
+    Refused(Refusal),
+    /// The change sends mail, and too much has been sent lately.
+    #[response(status = 429)]
+    TooManyRequests(Json<api::Error>),
+    #[response(status = 500)]
+    Internal(&'static str),
+}
+
+impl From<Refusal> for BoardError {
+    fn from(refusal: Refusal) -> Self {
+        Self::Refused(refusal)
+    }
+}
+
+impl BoardError {
+    pub fn invalid(err: board::NameError) -> Self {
+        Self::Invalid(Json(api::Error {
+            message: err.to_string(),
+            problems: Vec::new(),
+        }))
+    }
+
+    pub fn too_many_requests(message: &str) -> Self {
+        Self::TooManyRequests(Json(api::Error {
+            message: message.to_owned(),
+            problems: Vec::new(),
+        }))
+    }
+
+    pub fn internal(err: anyhow::Error) -> Self {
+        log::error!("{}", err);
+        Self::Internal("Internal Server Error")
+    }
+}
+
+#[get("/boards")]
+pub async fn index(
+    session: Session,
+    db: DbConn,
+) -> Result<Json<api::BoardList>, status::Custom<&'static str>> {
+    let uid = session.user.id;
+    match db.run(move |c| list(c, uid)).await {
+        Ok(boards) => Ok(Json(boards)),
+        Err(err) => {
+            log::error!("{}", err);
+            Err(status::Custom(
+                Status::InternalServerError,
+                "Internal Server Error",
+            ))
+        }
+    }
+}
+
+/// Adds a board. Its slug is made from its name.
+#[post("/boards", data = "<req>")]
+pub async fn create(
+    session: Session,
+    db: DbConn,
+    req: Json<api::BoardName>,
+) -> Result<Json<api::Board>, BoardError> {
+    let uid = session.user.id;
+    let name = board::normalize_name(&req.name).map_err(BoardError::invalid)?;
+    db.run(move |c| create_board(c, uid, name))
+        .await
+        .map(Json)
+        .map_err(BoardError::internal)
+}
+
+/// Renames a board. Its slug stays the same, so links to it keep working.
+#[put("/boards/<board_slug>", data = "<req>")]
+pub async fn rename(
+    session: Session,
+    db: DbConn,
+    board_slug: String,
+    req: Json<api::BoardName>,
+) -> Result<Option<Json<api::Board>>, BoardError> {
+    let uid = session.user.id;
+    let new_name = board::normalize_name(&req.name).map_err(BoardError::invalid)?;
+    let res = db
+        .run(move |c| {
+            use schema::boards::dsl::*;
+            diesel::update(boards.filter(user_id.eq(uid)).filter(slug.eq(&board_slug)))
+                .set(name.eq(&new_name))
+                .returning((slug, name))
+                .get_result::<(String, String)>(c)
+                .optional()
+        })
+        .await;
+    match res {
+        Ok(board) => Ok(board.map(|(slug, name)| {
+            Json(api::Board {
+                slug,
+                name,
+                role: Role::Owner,
+                owner: None,
+            })
+        })),
+        Err(err) => Err(BoardError::internal(err.into())),
+    }
+}
+
+/// Deletes a board along with its history. Users always keep at least one board. Returns the
+/// boards that are left.
+#[delete("/boards/<board_slug>")]
+pub async fn delete(
+    session: Session,
+    db: DbConn,
+    board_slug: String,
+) -> Result<Option<Json<api::BoardList>>, BoardError> {
+    let uid = session.user.id;
+    match db.run(move |c| delete_board(c, uid, &board_slug)).await {
+        Ok(DeleteResult::Deleted(boards)) => Ok(Some(Json(boards))),
+        Ok(DeleteResult::NotFound) => Ok(None),
+        Ok(DeleteResult::OnlyBoard) => Err(BoardError::Conflict(Json(api::Error {
+            message: "can't delete your only board".to_owned(),
+            problems: Vec::new(),
+        }))),
+        Err(err) => Err(BoardError::internal(err)),
+    }
+}
+
+/// Makes a board the one shown when the user doesn't pick one. Returns the updated list.
+#[post("/boards/<board_slug>/default")]
+pub async fn make_default(
+    session: Session,
+    db: DbConn,
+    board_slug: String,
+) -> Result<Option<Json<api::BoardList>>, BoardError> {
+    let uid = session.user.id;
+    let res = db
+        .run(move |c| {
+            let access = match find(c, uid, &board_slug)? {
+                Some(access) => access,
+                None => return Ok(None),
+            };
+            diesel::update(schema::users::table.find(uid))
+                .set(schema::users::default_board_id.eq(access.board.id))
+                .execute(c)?;
+            Ok::<_, anyhow::Error>(Some(list(c, uid)?))
+        })
+        .await;
+    res.map(|boards| boards.map(Json))
+        .map_err(BoardError::internal)
+}
@@ -0,0 +1,104 @@
+//! Remembers what other sites said for a while, so that several tiles (or several users) asking for
+//! the same thing don't each ask again. Users choose what's asked for, so each cache holds a limited
+//! number of entries and forgets the oldest to make room.
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Values by key, each kept for a while after it's put.
+pub struct Cache<K, V> {
+    keep_for: Duration,
+    max_entries: usize,
+    entries: Mutex<HashMap<K, (Instant, V)>>,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> Cache<K, V> {
+    /// Keeps each value for `keep_for`, and at most `max_entries` of them at once.
+    pub fn new(keep_for: Duration, max_entries: usize) -> Self {
+        Self {
+            keep_for,
+            max_entries,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The value put for `key`, unless it's been forgotten.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.get_at(key, Instant::now())
+    }
+
+    fn get_at<Q>(&self, key: &Q, now: Instant) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let entries = self.entries.lock().expect("cache poisoned");
+        match entries.get(key) {
+            Some((at, value)) if now.duration_since(*at) < self.keep_for => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    /// Remembers `value` for `key`, forgetting the oldest value if the cache is full.
+    pub fn put(&self, key: K, value: V) {
+        self.put_at(key, value, Instant::now())
+    }
+
+    fn put_at(&self, key: K, value: V, now: Instant) {
+        let keep_for = self.keep_for;
+        let mut entries = self.entries.lock().expect("cache poisoned");
+        entries.retain(|_, (at, _)| now.duration_since(*at) < keep_for);
+        if entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, (at, _))| *at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(key, (now, value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_forgets_old_values() {
+        let cache = Cache::new(Duration::from_secs(60), 10);
+        let start = Instant::now();
+
+        cache.put_at("a".to_owned(), 1, start);
+        assert_eq!(cache.get_at("a", start + Duration::from_secs(59)), Some(1));
+        assert_eq!(cache.get_at("a", start + Duration::from_secs(60)), None);
+        assert_eq!(cache.get_at("b", start), None);
+    }
+
+    #[test]
+    fn test_cache_forgets_the_oldest_when_full() {
+        let cache = Cache::new(Duration::from_secs(60), 2);
+        let start = Instant::now();
+
+        cache.put_at("a".to_owned(), 1, start);
+        cache.put_at("b".to_owned(), 2, start + Duration::from_secs(1));
+        // Replacing a value doesn't need room.
+        cache.put_at("a".to_owned(), 3, start + Duration::from_secs(2));
+        assert_eq!(cache.get_at("b", start + Duration::from_secs(2)), Some(2));
+
+        cache.put_at("c".to_owned(), 4, start + Duration::from_secs(3));
+        let now = start + Duration::from_secs(3);
+        assert_eq!(cache.get_at("a", now), Some(3));
+        assert_eq!(cache.get_at("b", now), None);
+        assert_eq!(cache.get_at("c", now), Some(4));
+        assert_eq!(cache.entries.lock().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,151 @@
+//! Sites' icons for link tiles. Browsers fetch them through here rather than from each site, so
+//! that a board doesn't tell every site it links to who is looking at it, and so that icons are
+//! only fetched once in a while no matter how many boards link to the same site.
+
+use super::auth::Session;
+use super::cache::Cache;
+use super::outbound;
+use rocket::fairing::AdHoc;
+use rocket::http::{ContentType, Header};
+use rocket::State;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Config {
+    /// How long to reuse an icon, or the lack of one, before asking the site again.
+    pub cache_ttl_secs: u64,
+    /// The largest icon to pass along. Anything bigger isn't really a favicon.
+    pub max_bytes: usize,
+}
+
+/// How many hosts' icons are remembered at once. The oldest is forgotten to make room.
+const MAX_CACHE_ENTRIES: usize = 1000;
+
+#[derive(Clone)]
+struct Image {
+    content_type: ContentType,
+    bytes: Vec<u8>,
+}
+
+/// Fetches favicons, and caches them by host.
+pub struct Favicons {
+    client: reqwest::Client,
+    ttl: Duration,
+    max_bytes: usize,
+    /// Hosts without a usable icon are cached as `None`, so they aren't asked again right away.
+    entries: Cache<String, Option<Image>>,
+}
+
+impl Favicons {
+    pub fn new(config: Config) -> Self {
+        let ttl = Duration::from_secs(config.cache_ttl_secs);
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(5))
+                .redirect(outbound::redirect_policy())
+                .dns_resolver(Arc::new(outbound::PublicResolver))
+                .build()
+                .expect("could not build HTTP client"),
+            ttl,
+            max_bytes: config.max_bytes,
+            entries: Cache::new(ttl, MAX_CACHE_ENTRIES),
+        }
+    }
+
+    /// Finds a site's icon, from the cache if it was looked for recently.
+    async fn get(&self, host: &str) -> Option<Image> {
+        if let Some(image) = self.entries.get(host) {
+            return image;
+        }
+        let image = match self.fetch(host).await {
+            Ok(image) => image,
+            Err(err) => {
+                log::info!("could not fetch favicon for {}: {}", host, err);
+                None
+            }
+        };
+        self.entries.put(host.to_owned(), image.clone());
+        image
+    }
+
+    /// Asks a site for the icon at `/favicon.ico`, which nearly every site has.
+    async fn fetch(&self, host: &str) -> anyhow::Result<Option<Image>> {
+        let url = Url::parse(&format!("https://{}/favicon.ico", host))?;
+        let mut res = self.client.get(url).send().await?;
+        if !res.status().is_success() {
+            return Ok(None);
+        }
+        let content_type = res
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(ContentType::parse_flexible)
+            .filter(|content_type| content_type.top() == "image");
+        let content_type = match content_type {
+            Some(content_type) => content_type,
+            None => return Ok(None),
+        };
+        if res.content_length().unwrap_or(0) > self.max_bytes as u64 {
+            return Ok(None);
+        }
+
+        // The length may be missing or wrong, so keep counting.
+        let mut bytes = Vec::new();
+        while let Some(chunk) = res.chunk().await? {
+            if bytes.len() + chunk.len() > self.max_bytes {
+                return Ok(None);
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        Ok(Some(Image {
+            content_type,
+            bytes,
+        }))
+    }
+}
+
+/// Loads the `favicons` configuration section into managed state.
+pub fn fairing() -> AdHoc {
+    AdHoc::try_on_ignite("Favicons", |rocket| async {
+        match rocket.figment().extract_inner::<Config>("favicons") {
+            Ok(config) => Ok(rocket.manage(Favicons::new(config))),
+            Err(err) => {
+                log::error!("invalid favicons config: {}", err);
+                Err(rocket)
+            }
+        }
+    })
+}
+
+#[derive(Responder)]
+pub struct Favicon {
+    bytes: Vec<u8>,
+    content_type: ContentType,
+    cache_control: Header<'static>,
+}
+
+/// Finds a site's icon, if it has one that can be passed along.
+pub async fn favicon(favicons: &Favicons, host: &str) -> Option<Favicon> {
+    let host = host.to_lowercase();
+    if !outbound::is_public_host(&host) {
+        return None;
+    }
+    let image = favicons.get(&host).await?;
+    Some(Favicon {
+        bytes: image.bytes,
+        content_type: image.content_type,
+        cache_control: Header::new(
+            "Cache-Control",
+            format!("private, max-age={}", favicons.ttl.as_secs()),
+        ),
+    })
+}
+
+/// Shows a site's icon, for a link tile.
+#[get("/favicons/<host>")]
+pub async fn show(_session: Session, favicons: &State<Favicons>, host: &str) -> Option<Favicon> {
+    favicon(favicons, host).await
+}
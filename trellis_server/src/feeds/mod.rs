@@ -0,0 +1,249 @@
+//! RSS and Atom feeds for feed tiles. Browsers can't fetch most feeds themselves, since few sites
+//! allow it, so feeds are fetched here. Each feed is fetched at most once per `cache_ttl_secs` no
+//! matter how many boards show it, and after that only downloaded again if it has changed.
+
+use super::auth::Session;
+use super::cache::Cache;
+use super::outbound;
+use super::weather::USER_AGENT;
+use rocket::fairing::AdHoc;
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use trellis_core::api;
+use trellis_core::feed::{self, Feed};
+use url::Url;
+
+mod parse;
+
+#[cfg(test)]
+mod tests;
+
+pub use parse::{decode, parse, ParseError};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Config {
+    /// How long to reuse a feed before asking the site whether it has changed.
+    pub cache_ttl_secs: u64,
+    /// The largest feed to read. Feeds that are bigger are usually full of whole articles.
+    pub max_bytes: usize,
+}
+
+/// Feeds that nobody has asked for in this long are forgotten.
+const KEEP_FOR: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How many feeds are remembered at once. The oldest is forgotten to make room.
+const MAX_CACHE_ENTRIES: usize = 1000;
+
+#[derive(Clone)]
+struct Cached {
+    checked_at: Instant,
+    /// What the site said identifies this version of the feed, to ask whether it has changed.
+    etag: Option<String>,
+    last_modified: Option<String>,
+    feed: Feed,
+}
+
+#[derive(Error, Debug)]
+pub enum FetchError {
+    #[error("feed not found")]
+    NotFound,
+    #[error("unexpected status {0}")]
+    Status(reqwest::StatusCode),
+    #[error("feed is larger than {0} bytes")]
+    TooLarge(usize),
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error("request failed: {0}")]
+    Request(#[from] reqwest::Error),
+}
+
+/// Fetches feeds, and caches them by URL.
+pub struct Feeds {
+    client: reqwest::Client,
+    ttl: Duration,
+    max_bytes: usize,
+    entries: Cache<String, Cached>,
+}
+
+impl Feeds {
+    pub fn new(config: Config) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .user_agent(USER_AGENT)
+                .timeout(Duration::from_secs(10))
+                .redirect(outbound::redirect_policy())
+                .dns_resolver(Arc::new(outbound::PublicResolver))
+                .build()
+                .expect("could not build HTTP client"),
+            ttl: Duration::from_secs(config.cache_ttl_secs),
+            max_bytes: config.max_bytes,
+            entries: Cache::new(KEEP_FOR, MAX_CACHE_ENTRIES),
+        }
+    }
+
+    /// Finds the latest version of a feed. A feed that was checked recently comes from the cache.
+    /// Otherwise the site is asked whether it has changed, and if it can't be reached the last
+    /// version it gave is used instead.
+    pub async fn get(&self, url: &Url) -> Result<Feed, FetchError> {
+        let cached = self.entries.get(url.as_str());
+        if let Some(cached) = &cached {
+            if cached.checked_at.elapsed() < self.ttl {
+                return Ok(cached.feed.clone());
+            }
+        }
+        let fresh = match (self.fetch(url, cached.as_ref()).await, cached) {
+            (Ok(fresh), _) => fresh,
+            (Err(err), Some(stale)) => {
+                log::info!("could not fetch feed {}, using the last one: {}", url, err);
+                Cached {
+                    checked_at: Instant::now(),
+                    ..stale
+                }
+            }
+            (Err(err), None) => return Err(err),
+        };
+        self.entries.put(url.as_str().to_owned(), fresh.clone());
+        Ok(fresh.feed)
+    }
+
+    /// Downloads a feed, unless the site says it hasn't changed since it was cached.
+    async fn fetch(&self, url: &Url, cached: Option<&Cached>) -> Result<Cached, FetchError> {
+        use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+        use reqwest::StatusCode;
+
+        let mut req = self.client.get(url.clone());
+        if let Some(cached) = cached {
+            if let Some(etag) = &cached.etag {
+                req = req.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                req = req.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let mut res = req.send().await?;
+        match (res.status(), cached) {
+            (StatusCode::NOT_MODIFIED, Some(cached)) => {
+                return Ok(Cached {
+                    checked_at: Instant::now(),
+                    ..cached.clone()
+                })
+            }
+            (StatusCode::NOT_FOUND, _) | (StatusCode::GONE, _) => return Err(FetchError::NotFound),
+            (status, _) if !status.is_success() => return Err(FetchError::Status(status)),
+            _ => {}
+        }
+
+        let header = |name| {
+            res.headers()
+                .get(name)
+                .and_then(|value: &reqwest::header::HeaderValue| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+        // Links in the feed are relative to wherever it ended up after redirects.
+        let base = res.url().clone();
+        if res.content_length().unwrap_or(0) > self.max_bytes as u64 {
+            return Err(FetchError::TooLarge(self.max_bytes));
+        }
+
+        // The length may be missing or wrong, so keep counting.
+        let mut bytes = Vec::new();
+        while let Some(chunk) = res.chunk().await? {
+            if bytes.len() + chunk.len() > self.max_bytes {
+                return Err(FetchError::TooLarge(self.max_bytes));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        let feed = parse(&decode(&bytes)?, &base)?;
+        Ok(Cached {
+            checked_at: Instant::now(),
+            etag,
+            last_modified,
+            feed,
+        })
+    }
+}
+
+/// Loads the `feeds` configuration section into managed state.
+pub fn fairing() -> AdHoc {
+    AdHoc::try_on_ignite("Feeds", |rocket| async {
+        match rocket.figment().extract_inner::<Config>("feeds") {
+            Ok(config) => Ok(rocket.manage(Feeds::new(config))),
+            Err(err) => {
+                log::error!("invalid feeds config: {}", err);
+                Err(rocket)
+            }
+        }
+    })
+}
+
+#[derive(Responder)]
+pub enum FeedError {
+    #[response(status = 404)]
+    NotFound(Json<api::Error>),
+    /// The feed can't be fetched from its URL, so the tile needs a different one.
+    #[response(status = 422)]
+    Unavailable(Json<api::Error>),
+    #[response(status = 502)]
+    Upstream(Json<api::Error>),
+    #[response(status = 500)]
+    Internal(&'static str),
+}
+
+fn error_body(message: String) -> Json<api::Error> {
+    Json(api::Error {
+        message,
+        problems: Vec::new(),
+    })
+}
+
+/// Checks that a feed's URL is somewhere on the internet. Anything else, like `localhost`, could
+/// be a way to make the server fetch things from its own network.
+fn parse_url(url: &str) -> Result<Url, FeedError> {
+    let unavailable =
+        || FeedError::Unavailable(error_body(format!("Not a feed on the internet: {}", url)));
+    let parsed = Url::parse(url.trim()).map_err(|_| unavailable())?;
+    let public = matches!(parsed.scheme(), "http" | "https")
+        && matches!(parsed.host_str(), Some(host) if outbound::is_public_host(host));
+    if public {
+        Ok(parsed)
+    } else {
+        Err(unavailable())
+    }
+}
+
+/// Finds the latest entries of a feed, at most `max_items` of them.
+pub async fn latest(feeds: &Feeds, url: &str, max_items: Option<u32>) -> Result<Feed, FeedError> {
+    let url = parse_url(url)?;
+    let mut feed = feeds.get(&url).await.map_err(|err| match err {
+        FetchError::NotFound => FeedError::NotFound(error_body(format!("No feed at {}", url))),
+        FetchError::Parse(_) | FetchError::TooLarge(_) => {
+            FeedError::Unavailable(error_body(format!("{}: {}", url, err)))
+        }
+        err => {
+            log::warn!("could not fetch feed {}: {}", url, err);
+            FeedError::Upstream(error_body(err.to_string()))
+        }
+    })?;
+    let max_items = max_items
+        .unwrap_or(feed::DEFAULT_ITEMS)
+        .clamp(1, feed::MAX_ITEMS);
+    feed.entries.truncate(max_items as usize);
+    Ok(feed)
+}
+
+/// Shows the latest entries of a feed, for a feed tile.
+#[get("/feed?<url>&<max_items>")]
+pub async fn show(
+    _session: Session,
+    feeds: &State<Feeds>,
+    url: &str,
+    max_items: Option<u32>,
+) -> Result<Json<Feed>, FeedError> {
+    latest(feeds, url, max_items).await.map(Json)
+}
@@ -0,0 +1,208 @@
+//! Reads RSS 0.9x, 1.0, and 2.0 feeds, and Atom feeds, keeping only what feed tiles show.
+
+use chrono::{DateTime, Utc};
+use encoding_rs::{Encoding, UTF_8};
+use roxmltree::{Document, Node};
+use std::borrow::Cow;
+use thiserror::Error;
+use trellis_core::feed::{Entry, Feed, MAX_ITEMS};
+use url::Url;
+
+const ATOM: &str = "http://www.w3.org/2005/Atom";
+/// Dublin Core, which RSS 1.0 feeds (and some others) use for dates.
+const DC: &str = "http://purl.org/dc/elements/1.1/";
+
+#[derive(Error, Debug)]
+pub enum ParseError {
+    #[error("not XML: {0}")]
+    Xml(#[from] roxmltree::Error),
+    #[error("not an RSS or Atom feed")]
+    NotAFeed,
+    #[error("unknown encoding {0:?}")]
+    UnknownEncoding(String),
+}
+
+/// Reads a feed's bytes as text, in the encoding given by its byte order mark or else by its XML
+/// declaration. Feeds that have neither are UTF-8. Bytes that don't fit the encoding come out as
+/// replacement characters.
+pub fn decode(bytes: &[u8]) -> Result<Cow<'_, str>, ParseError> {
+    let encoding = match declared_encoding(bytes) {
+        Some(label) => Encoding::for_label(label.as_bytes())
+            .ok_or(ParseError::UnknownEncoding(label))?
+            // A declaration that could be read as ASCII can't really be in UTF-16.
+            .output_encoding(),
+        None => UTF_8,
+    };
+    // A byte order mark wins over the declaration, and is left out of the text.
+    let (text, _, _) = encoding.decode(bytes);
+    Ok(text)
+}
+
+/// The encoding named by an XML declaration like `<?xml version="1.0" encoding="ISO-8859-1"?>`.
+fn declared_encoding(bytes: &[u8]) -> Option<String> {
+    let declaration = bytes.strip_prefix(b"<?xml")?;
+    let end = declaration.iter().position(|&b| b == b'>')?;
+    let declaration = std::str::from_utf8(&declaration[..end]).ok()?;
+    let rest = &declaration[declaration.find("encoding")? + "encoding".len()..];
+    let rest = rest.trim_start().strip_prefix('=')?.trim_start();
+    let quote = rest.chars().next().filter(|&c| c == '"' || c == '\'')?;
+    let rest = &rest[1..];
+    Some(rest[..rest.find(quote)?].to_owned())
+}
+
+/// Reads a feed. Relative links are resolved against `base`, which should be the URL the feed was
+/// fetched from. Only the first `MAX_ITEMS` entries are kept.
+pub fn parse(text: &str, base: &Url) -> Result<Feed, ParseError> {
+    let doc = Document::parse(text)?;
+    let root = doc.root_element();
+    match root.tag_name().name() {
+        "rss" | "RDF" => rss(root, base),
+        "feed" if root.tag_name().namespace() == Some(ATOM) => Ok(atom(root, base)),
+        _ => Err(ParseError::NotAFeed),
+    }
+}
+
+/// RSS 2.0 puts items inside the channel, and RSS 1.0 puts them next to it.
+fn rss(root: Node, base: &Url) -> Result<Feed, ParseError> {
+    let channel = root
+        .children()
+        .find(|n| n.tag_name().name() == "channel")
+        .ok_or(ParseError::NotAFeed)?;
+    // RSS 1.0 has a namespace of its own, and the others have none. Extensions like `atom:link`
+    // can have the same names as RSS elements, so they have to be told apart.
+    let ns = channel.tag_name().namespace();
+    let entries = channel
+        .children()
+        .chain(root.children())
+        .filter(|n| n.is_element() && n.tag_name().name() == "item")
+        .take(MAX_ITEMS as usize)
+        .map(|item| {
+            let link = child(item, "link", ns)
+                .and_then(|l| resolve(base, &text(l)))
+                .or_else(|| {
+                    child(item, "guid", ns)
+                        .filter(|g| g.attribute("isPermaLink") != Some("false"))
+                        .and_then(|g| resolve(base, &text(g)))
+                });
+            let published = child(item, "pubDate", ns)
+                .and_then(|d| DateTime::parse_from_rfc2822(&text(d)).ok())
+                .or_else(|| {
+                    child(item, "date", Some(DC))
+                        .and_then(|d| DateTime::parse_from_rfc3339(&text(d)).ok())
+                })
+                .map(|d| d.with_timezone(&Utc));
+            Entry {
+                title: child(item, "title", ns).map(text).unwrap_or_default(),
+                link,
+                published,
+            }
+        })
+        .collect();
+
+    Ok(Feed {
+        title: child(channel, "title", ns).map(text).unwrap_or_default(),
+        link: child(channel, "link", ns).and_then(|l| resolve(base, &text(l))),
+        entries,
+    })
+}
+
+fn atom(feed: Node, base: &Url) -> Feed {
+    let entries = feed
+        .children()
+        .filter(|n| n.has_tag_name((ATOM, "entry")))
+        .take(MAX_ITEMS as usize)
+        .map(|entry| {
+            let published = child(entry, "published", Some(ATOM))
+                .or_else(|| child(entry, "updated", Some(ATOM)))
+                .and_then(|d| DateTime::parse_from_rfc3339(&text(d)).ok())
+                .map(|d| d.with_timezone(&Utc));
+            Entry {
+                title: child(entry, "title", Some(ATOM))
+                    .map(atom_text)
+                    .unwrap_or_default(),
+                link: atom_link(entry, base),
+                published,
+            }
+        })
+        .collect();
+
+    Feed {
+        title: child(feed, "title", Some(ATOM))
+            .map(atom_text)
+            .unwrap_or_default(),
+        link: atom_link(feed, base),
+        entries,
+    }
+}
+
+/// Finds the first child element with the given name.
+fn child<'a, 'input>(
+    node: Node<'a, 'input>,
+    name: &str,
+    ns: Option<&str>,
+) -> Option<Node<'a, 'input>> {
+    node.children()
+        .find(|n| n.is_element() && n.tag_name().name() == name && n.tag_name().namespace() == ns)
+}
+
+/// Reads the text inside an element, with its whitespace collapsed.
+fn text(node: Node) -> String {
+    let text: String = node
+        .descendants()
+        .filter(|n| n.is_text())
+        .filter_map(|n| n.text())
+        .collect();
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Reads an Atom text construct, which may be HTML that was escaped to fit in the XML.
+fn atom_text(node: Node) -> String {
+    let text = text(node);
+    if node.attribute("type") == Some("html") {
+        strip_html(&text)
+    } else {
+        text
+    }
+}
+
+/// Turns a bit of HTML, like a title with some emphasis, into plain text.
+fn strip_html(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Finds the page an Atom feed or entry is about. Other kinds of links, like enclosures, point at
+/// things that aren't pages.
+fn atom_link(node: Node, base: &Url) -> Option<String> {
+    node.children()
+        .filter(|n| n.has_tag_name((ATOM, "link")))
+        .find(|l| matches!(l.attribute("rel"), None | Some("alternate")))
+        .and_then(|l| l.attribute("href"))
+        .and_then(|href| resolve(base, href))
+}
+
+/// Resolves a link, keeping it only if it's a web page. Anything else, like a `javascript:` URL,
+/// shouldn't be clickable on a board.
+fn resolve(base: &Url, link: &str) -> Option<String> {
+    let link = link.trim();
+    if link.is_empty() {
+        return None;
+    }
+    base.join(link)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .map(String::from)
+}
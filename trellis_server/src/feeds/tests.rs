@@ -0,0 +1,207 @@
+use super::*;
+use crate::test_server::stub_server;
+use chrono::{TimeZone, Utc};
+use trellis_core::feed::Entry;
+
+const RSS: &str = "application/rss+xml";
+
+#[test]
+fn test_parse_rss() {
+    let base = Url::parse("https://blog.example.com/feed.xml").unwrap();
+    let feed = parse(include_str!("../../fixtures/feed_rss.xml"), &base).unwrap();
+    assert_eq!(
+        feed,
+        Feed {
+            title: "Example Blog".to_owned(),
+            link: Some("https://blog.example.com/".to_owned()),
+            entries: vec![
+                Entry {
+                    title: "Release notes".to_owned(),
+                    link: Some("https://blog.example.com/2021/09/release".to_owned()),
+                    published: Some(Utc.ymd(2021, 9, 7).and_hms(16, 30, 0)),
+                },
+                Entry {
+                    title: "Tips & tricks".to_owned(),
+                    link: Some("https://blog.example.com/2021/08/tips".to_owned()),
+                    published: Some(Utc.ymd(2021, 8, 30).and_hms(16, 0, 0)),
+                },
+                Entry {
+                    title: "Hello, world".to_owned(),
+                    link: Some("https://blog.example.com/2021/08/hello".to_owned()),
+                    published: None,
+                },
+                Entry {
+                    title: "Not a page".to_owned(),
+                    link: None,
+                    published: None,
+                },
+            ],
+        }
+    );
+}
+
+#[test]
+fn test_parse_atom() {
+    let base = Url::parse("https://news.example.com/atom.xml").unwrap();
+    let feed = parse(include_str!("../../fixtures/feed_atom.xml"), &base).unwrap();
+    assert_eq!(
+        feed,
+        Feed {
+            title: "Example News".to_owned(),
+            link: Some("https://news.example.com/".to_owned()),
+            entries: vec![
+                Entry {
+                    title: "Bridge reopens".to_owned(),
+                    link: Some("https://news.example.com/articles/bridge".to_owned()),
+                    published: Some(Utc.ymd(2021, 9, 7).and_hms(16, 30, 0)),
+                },
+                Entry {
+                    title: "Markets & more".to_owned(),
+                    link: Some("https://news.example.com/articles/markets".to_owned()),
+                    published: Some(Utc.ymd(2021, 9, 6).and_hms(8, 0, 0)),
+                },
+            ],
+        }
+    );
+}
+
+#[test]
+fn test_parse_rss_1() {
+    let base = Url::parse("https://example.org/index.rdf").unwrap();
+    let feed = parse(
+        r#"<?xml version="1.0"?>
+        <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"
+                 xmlns="http://purl.org/rss/1.0/"
+                 xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <channel rdf:about="https://example.org/">
+            <title>Example</title>
+            <link>https://example.org/</link>
+          </channel>
+          <item rdf:about="https://example.org/one">
+            <title>One</title>
+            <link>https://example.org/one</link>
+            <dc:date>2021-09-01T12:00:00Z</dc:date>
+          </item>
+        </rdf:RDF>"#,
+        &base,
+    )
+    .unwrap();
+    assert_eq!(
+        feed.entries,
+        vec![Entry {
+            title: "One".to_owned(),
+            link: Some("https://example.org/one".to_owned()),
+            published: Some(Utc.ymd(2021, 9, 1).and_hms(12, 0, 0)),
+        }]
+    );
+}
+
+#[test]
+fn test_parse_rejects_other_documents() {
+    let base = Url::parse("https://example.com/").unwrap();
+    assert!(matches!(
+        parse("<html><body>Hi</body></html>", &base),
+        Err(ParseError::NotAFeed)
+    ));
+    assert!(matches!(
+        parse("<feed><title>No namespace</title></feed>", &base),
+        Err(ParseError::NotAFeed)
+    ));
+    assert!(matches!(
+        parse(r#"{"title":"JSON"}"#, &base),
+        Err(ParseError::Xml(_))
+    ));
+}
+
+#[test]
+fn test_decode_uses_the_declared_encoding() {
+    let latin1 = b"<?xml version='1.0' encoding='ISO-8859-1'?><rss><title>Caf\xe9</title></rss>";
+    assert_eq!(
+        decode(latin1).unwrap(),
+        "<?xml version='1.0' encoding='ISO-8859-1'?><rss><title>Café</title></rss>"
+    );
+    let utf8 = r#"<?xml version="1.0" encoding="UTF-8"?><rss><title>Café</title></rss>"#;
+    assert_eq!(decode(utf8.as_bytes()).unwrap(), utf8);
+    assert_eq!(decode(b"\xef\xbb\xbf<rss/>").unwrap(), "<rss/>");
+    assert_eq!(decode(b"<rss/>").unwrap(), "<rss/>");
+    assert!(matches!(
+        decode(br#"<?xml version="1.0" encoding="klingon"?><rss/>"#),
+        Err(ParseError::UnknownEncoding(label)) if label == "klingon"
+    ));
+}
+
+#[test]
+fn test_only_fetches_feeds_on_the_internet() {
+    assert!(parse_url("https://blog.example.com/feed.xml").is_ok());
+    for url in &[
+        "http://localhost:8000/feed.xml",
+        "http://127.0.0.1/feed.xml",
+        "http://[::1]/feed.xml",
+        "http://2130706433/feed.xml",
+        "file:///etc/passwd",
+        "blog.example.com/feed.xml",
+    ] {
+        assert!(parse_url(url).is_err(), "{}", url);
+    }
+}
+
+#[rocket::async_test]
+async fn test_fetch_asks_whether_feeds_changed() {
+    let (url, server) = stub_server(
+        RSS,
+        &[
+            (
+                "200 OK",
+                "ETag: \"v1\"\r\nLast-Modified: Tue, 07 Sep 2021 16:30:00 GMT\r\n",
+                include_str!("../../fixtures/feed_rss.xml"),
+            ),
+            ("304 Not Modified", "", ""),
+            ("500 Internal Server Error", "", ""),
+        ],
+    );
+    let url = url.join("feed.xml").unwrap();
+    let feeds = Feeds::new(Config {
+        cache_ttl_secs: 0,
+        max_bytes: 1024 * 1024,
+    });
+
+    let first = feeds.get(&url).await.unwrap();
+    assert_eq!(first.title, "Example Blog");
+    // Unchanged, so the cached feed is used.
+    assert_eq!(feeds.get(&url).await.unwrap(), first);
+    // Unreachable, so the last feed is still used.
+    assert_eq!(feeds.get(&url).await.unwrap(), first);
+
+    let requests = server.join().unwrap();
+    assert!(!requests[0].contains("if-none-match"));
+    assert!(requests[1].contains("if-none-match: \"v1\"\r\n"));
+    assert!(requests[1].contains("if-modified-since: tue, 07 sep 2021 16:30:00 gmt\r\n"));
+}
+
+#[rocket::async_test]
+async fn test_fetch_errors() {
+    let (url, server) = stub_server(
+        RSS,
+        &[
+            ("404 Not Found", "", ""),
+            ("200 OK", "", "<html><body>Not a feed</body></html>"),
+            ("200 OK", "", include_str!("../../fixtures/feed_atom.xml")),
+        ],
+    );
+    let url = url.join("feed.xml").unwrap();
+    let feeds = Feeds::new(Config {
+        cache_ttl_secs: 0,
+        max_bytes: 100,
+    });
+
+    assert!(matches!(feeds.get(&url).await, Err(FetchError::NotFound)));
+    assert!(matches!(
+        feeds.get(&url).await,
+        Err(FetchError::Parse(ParseError::NotAFeed))
+    ));
+    assert!(matches!(
+        feeds.get(&url).await,
+        Err(FetchError::TooLarge(100))
+    ));
+    server.join().unwrap();
+}
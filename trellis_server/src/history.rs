@@ -0,0 +1,215 @@
+use super::auth::Session;
+use super::boards;
+use super::live::Hub;
+use super::models;
+use super::schema;
+use super::DbConn;
+use chrono::{DateTime, Utc};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::Deserialize;
+use serde_json::Value;
+use trellis_core::board::Role;
+use trellis_core::{api, migrate};
+use uuid::Uuid;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Config {
+    /// How many past versions of each board to keep.
+    pub max_snapshots: i64,
+}
+
+impl Config {
+    /// Checks that every board keeps at least the version that was just saved.
+    fn validate(&self) -> Result<(), String> {
+        if self.max_snapshots < 1 {
+            return Err(format!(
+                "max_snapshots must be at least 1, not {}",
+                self.max_snapshots
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Loads the `history` configuration section into managed state.
+pub fn fairing() -> AdHoc {
+    AdHoc::try_on_ignite("Settings history", |rocket| async {
+        let config = rocket
+            .figment()
+            .extract_inner::<Config>("history")
+            .map_err(|err| err.to_string())
+            .and_then(|config| config.validate().map(|()| config));
+        match config {
+            Ok(config) => Ok(rocket.manage(config)),
+            Err(err) => {
+                log::error!("invalid history config: {}", err);
+                Err(rocket)
+            }
+        }
+    })
+}
+
+/// Records a newly-saved revision of a board, then forgets any revisions that are too old to keep.
+pub fn record(
+    conn: &PgConnection,
+    board: Uuid,
+    new_revision: i64,
+    new_data: &Value,
+    config: &Config,
+) -> QueryResult<()> {
+    use schema::settings_history::dsl::*;
+
+    let count = new_data
+        .get("tiles")
+        .and_then(Value::as_array)
+        .map_or(0, |tiles| tiles.len() as i32);
+
+    diesel::insert_into(settings_history)
+        .values(&models::NewSettingsHistory {
+            revision: new_revision,
+            data: new_data.clone(),
+            tile_count: count,
+            board_id: board,
+        })
+        .execute(conn)?;
+
+    diesel::delete(
+        settings_history
+            .filter(board_id.eq(board))
+            .filter(revision.le(new_revision - config.max_snapshots)),
+    )
+    .execute(conn)?;
+
+    Ok(())
+}
+
+fn entries(conn: &PgConnection, board: Uuid) -> QueryResult<Vec<api::HistoryEntry>> {
+    use schema::settings_history::dsl::*;
+
+    let rows = settings_history
+        .filter(board_id.eq(board))
+        .order(revision.desc())
+        .select((id, revision, tile_count, created_at))
+        .load::<(Uuid, i64, i32, chrono::NaiveDateTime)>(conn)?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(entry_id, rev, count, at)| api::HistoryEntry {
+            id: entry_id,
+            revision: rev,
+            tile_count: count,
+            created_at: DateTime::from_utc(at, Utc),
+        })
+        .collect())
+}
+
+/// Saves an old version of a board as the newest revision. Returns `None` if the board has no such
+/// version.
+fn restore_snapshot(
+    conn: &PgConnection,
+    board_id: Uuid,
+    history_id: Uuid,
+    config: &Config,
+) -> anyhow::Result<Option<api::Snapshot>> {
+    conn.transaction(|| {
+        let old = schema::settings_history::table
+            .find(history_id)
+            .filter(schema::settings_history::board_id.eq(board_id))
+            .first::<models::SettingsHistory>(conn)
+            .optional()?;
+        let old = match old {
+            Some(old) => old,
+            None => return Ok(None),
+        };
+
+        // The snapshot could be from an older schema version, so bring it up to date first.
+        let restored = migrate::from_value(old.data)?;
+        let restored_data = serde_json::to_value(&restored)?;
+
+        let new_revision = super::overwrite_settings(conn, board_id, &restored_data, config)?;
+
+        Ok(Some(api::Snapshot {
+            revision: new_revision,
+            config: restored,
+        }))
+    })
+}
+
+/// Lists the saved versions of a board, newest first.
+#[get("/boards/<slug>/history")]
+pub async fn list(
+    session: Session,
+    db: DbConn,
+    slug: String,
+) -> Result<Option<Json<Vec<api::HistoryEntry>>>, status::Custom<&'static str>> {
+    let uid = session.user.id;
+    let res = db
+        .run(move |c| match boards::find(c, uid, &slug)? {
+            Some(access) => Ok(Some(entries(c, access.board.id)?)),
+            None => Ok::<_, anyhow::Error>(None),
+        })
+        .await;
+    match res {
+        Ok(entries) => Ok(entries.map(Json)),
+        Err(err) => {
+            log::error!("{}", err);
+            Err(status::Custom(
+                Status::InternalServerError,
+                "Internal Server Error",
+            ))
+        }
+    }
+}
+
+/// Rolls a board back to a saved version. Only its owner and editors can. The rollback is itself
+/// saved as a new revision, so it can be undone the same way.
+#[post("/boards/<slug>/history/<history_id>/restore")]
+pub async fn restore(
+    session: Session,
+    db: DbConn,
+    config: &State<Config>,
+    hub: &State<Hub>,
+    slug: String,
+    history_id: Uuid,
+) -> Result<Option<Json<api::Snapshot>>, status::Custom<&'static str>> {
+    let board_id = boards::lookup(&db, session.user.id, slug, Role::Editor)
+        .await?
+        .board
+        .id;
+    let config = config.inner().clone();
+    let res = db
+        .run(move |c| restore_snapshot(c, board_id, history_id, &config))
+        .await;
+    match res {
+        Ok(restored) => Ok(restored.map(|snapshot| {
+            hub.publish(board_id, snapshot.clone());
+            Json(snapshot)
+        })),
+        Err(err) => {
+            log::error!("{}", err);
+            Err(status::Custom(
+                Status::InternalServerError,
+                "Internal Server Error",
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_at_least_one_snapshot() {
+        assert!(Config { max_snapshots: 1 }.validate().is_ok());
+        assert!(Config { max_snapshots: 50 }.validate().is_ok());
+        assert!(Config { max_snapshots: 0 }.validate().is_err());
+        assert!(Config { max_snapshots: -3 }.validate().is_err());
+    }
+}
@@ -0,0 +1,253 @@
+//! Pushes saved boards to every device that has them open, so nobody has to reload to see changes
+//! made elsewhere.
+
+use super::auth::Session;
+use super::boards;
+use super::shares;
+use super::DbConn;
+use rocket::fairing::AdHoc;
+use rocket::futures::stream::Stream;
+use rocket::http::Status;
+use rocket::outcome::Outcome;
+use rocket::request::{self, FromRequest, Request};
+use rocket::response::status;
+use rocket::response::stream::{Event, EventStream};
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::{self, error::RecvError};
+use rocket::{Orbit, Rocket, Shutdown, State};
+use trellis_core::{api, board};
+use uuid::Uuid;
+
+/// How many saves can be waiting to go out before slow listeners start missing some. Every save
+/// carries the whole config, so missing one only matters if it was the last.
+const CAPACITY: usize = 64;
+
+#[derive(Clone, Debug)]
+struct Change {
+    board_id: Uuid,
+    snapshot: api::Snapshot,
+}
+
+/// Passes newly saved boards along to whoever is listening for that board's changes.
+pub struct Hub {
+    sender: broadcast::Sender<Change>,
+}
+
+impl Hub {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        Self { sender }
+    }
+
+    /// Tells the board's listeners that it was just saved.
+    pub fn publish(&self, board_id: Uuid, snapshot: api::Snapshot) {
+        // Sending only fails when nobody is listening at all, which is fine.
+        let _ = self.sender.send(Change { board_id, snapshot });
+    }
+
+    /// Starts listening for a board's changes.
+    pub fn subscribe(&self, board_id: Uuid) -> Subscription {
+        Subscription {
+            board_id,
+            receiver: self.sender.subscribe(),
+        }
+    }
+}
+
+impl Default for Hub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Subscription {
+    board_id: Uuid,
+    receiver: broadcast::Receiver<Change>,
+}
+
+impl Subscription {
+    /// Waits for the board to be saved again, returning `None` once the hub is gone.
+    pub async fn next(&mut self) -> Option<api::Snapshot> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(change) if change.board_id == self.board_id => return Some(change.snapshot),
+                Ok(_) => continue,
+                // Later saves are still coming, and each one has the whole config.
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+/// A request guard for the running server, which streams get database connections from as they
+/// need them instead of holding one for as long as they're open.
+pub struct Server<'r>(&'r Rocket<Orbit>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Server<'r> {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        Outcome::Success(Server(req.rocket()))
+    }
+}
+
+/// Who a stream is for, so that they can be checked again before each change is sent to them.
+enum Listener {
+    /// A signed-in user, who may be removed from the board or leave it while they listen.
+    User(Uuid),
+    /// Someone with a share link, which may have been revoked since they started listening.
+    Share(String),
+}
+
+impl Listener {
+    /// Whether the listener can still see the board. Any error counts as no. The connection is only
+    /// held for the check, since streams stay open for a long time and the pool is small.
+    async fn can_see(&self, rocket: &Rocket<Orbit>, board_id: Uuid) -> bool {
+        // Failing to get one is logged already.
+        let db = match DbConn::get_one(rocket).await {
+            Some(db) => db,
+            None => return false,
+        };
+        match self {
+            Self::User(uid) => {
+                let (uid, key) = (*uid, board::team_key(board_id));
+                match db.run(move |c| boards::find(c, uid, &key)).await {
+                    Ok(access) => access.is_some(),
+                    Err(err) => {
+                        log::error!("{}", err);
+                        false
+                    }
+                }
+            }
+            Self::Share(token) => {
+                let token = token.clone();
+                match db.run(move |c| shares::find_board(c, &token)).await {
+                    Ok(board) => matches!(board, Some(board) if board.id == board_id),
+                    Err(err) => {
+                        log::error!("{}", err);
+                        false
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Adds the hub to managed state.
+pub fn fairing() -> AdHoc {
+    AdHoc::on_ignite("Live updates", |rocket| async { rocket.manage(Hub::new()) })
+}
+
+/// Streams a board that the user can see each time it is saved, as `settings` events holding an
+/// `api::Snapshot`. Every save is sent to every stream, including the one belonging to whoever
+/// saved, so clients should skip revisions they already have. The stream ends if the user stops
+/// being able to see the board.
+#[get("/boards/<slug>/events")]
+pub async fn events<'r>(
+    session: Session,
+    db: DbConn,
+    server: Server<'r>,
+    hub: &State<Hub>,
+    shutdown: Shutdown,
+    slug: String,
+) -> Result<Option<EventStream<impl Stream<Item = Event> + 'r>>, status::Custom<&'static str>> {
+    let uid = session.user.id;
+    let access = match db.run(move |c| boards::find(c, uid, &slug)).await {
+        Ok(Some(access)) => access,
+        Ok(None) => return Ok(None),
+        Err(err) => {
+            log::error!("{}", err);
+            return Err(status::Custom(
+                Status::InternalServerError,
+                "Internal Server Error",
+            ));
+        }
+    };
+    let subscription = hub.subscribe(access.board.id);
+    let listener = Listener::User(uid);
+    Ok(Some(stream(server.0, listener, subscription, shutdown)))
+}
+
+/// Streams a shared board each time it is saved, like `events`. Anyone with the share link can
+/// listen, until the link is revoked.
+#[get("/shared/<token>/events")]
+pub async fn shared_events<'r>(
+    db: DbConn,
+    server: Server<'r>,
+    hub: &State<Hub>,
+    shutdown: Shutdown,
+    token: String,
+) -> Result<Option<EventStream<impl Stream<Item = Event> + 'r>>, status::Custom<&'static str>> {
+    let found = {
+        let token = token.clone();
+        db.run(move |c| shares::find_board(c, &token)).await
+    };
+    let board = match found {
+        Ok(Some(board)) => board,
+        Ok(None) => return Ok(None),
+        Err(err) => {
+            log::error!("{}", err);
+            return Err(status::Custom(
+                Status::InternalServerError,
+                "Internal Server Error",
+            ));
+        }
+    };
+    let subscription = hub.subscribe(board.id);
+    let listener = Listener::Share(token);
+    Ok(Some(stream(server.0, listener, subscription, shutdown)))
+}
+
+/// Sends each change as a `settings` event until the server shuts down, or until the listener can
+/// no longer see the board.
+fn stream(
+    rocket: &Rocket<Orbit>,
+    listener: Listener,
+    mut subscription: Subscription,
+    mut shutdown: Shutdown,
+) -> EventStream<impl Stream<Item = Event> + '_> {
+    EventStream! {
+        loop {
+            let snapshot = select! {
+                snapshot = subscription.next() => match snapshot {
+                    Some(snapshot) => snapshot,
+                    None => break,
+                },
+                _ = &mut shutdown => break,
+            };
+            if !listener.can_see(rocket, subscription.board_id).await {
+                break;
+            }
+            yield Event::json(&snapshot).event("settings");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use trellis_core::config::Config;
+
+    fn snapshot(revision: i64) -> api::Snapshot {
+        api::Snapshot {
+            revision,
+            config: Config::default(),
+        }
+    }
+
+    #[rocket::async_test]
+    async fn test_subscription_only_sees_its_board() {
+        let hub = Hub::new();
+        let (work, home) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        let mut subscription = hub.subscribe(work);
+
+        hub.publish(home, snapshot(7));
+        hub.publish(work, snapshot(3));
+        assert_eq!(subscription.next().await.map(|s| s.revision), Some(3));
+
+        drop(hub);
+        assert_eq!(subscription.next().await, None);
+    }
+}
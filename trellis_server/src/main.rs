@@ -6,49 +6,111 @@ extern crate rocket;
 use anyhow;
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
-use rocket::http::{Cookie, CookieJar, Status};
+use rocket::http::{CookieJar, Status};
 use rocket::response::{status, Redirect};
 use rocket::serde::json::Json;
+use rocket::State;
 use rocket_sync_db_pools::database;
+use serde::Deserialize;
 use serde_json::map::Map;
 use serde_json::Value::{self, Object};
-use trellis_core::config;
+use std::net::IpAddr;
+use trellis_core::board::Role;
+use trellis_core::{api, migrate};
+use uuid::Uuid;
 
 mod auth;
+mod boards;
+mod cache;
+mod favicons;
+mod feeds;
+mod history;
+mod live;
+mod members;
 pub mod models;
+mod outbound;
+mod portable;
 pub mod schema;
+mod secrets;
+mod shares;
+#[cfg(test)]
+mod test_server;
+mod weather;
 
 #[database("trellis")]
 struct DbConn(PgConnection);
 
-async fn load_settings(db: DbConn, uid: String) -> anyhow::Result<Option<config::Config>> {
-    use schema::settings::dsl::*;
-    let res = db
-        .run(move |c| {
-            settings
-                .filter(user_id.eq(uid))
-                .first::<models::Settings>(c)
-        })
-        .await
-        .optional()?;
-
-    match res {
-        Some(row) => Ok(Some(serde_json::from_value::<config::Config>(row.data)?)),
-        None => Ok(Some(config::Config::default())),
+/// Reads a board that the user can see, upgrading it to the current schema version.
+fn load_settings(c: &PgConnection, uid: Uuid, slug: &str) -> anyhow::Result<Option<api::Snapshot>> {
+    match boards::find(c, uid, slug)? {
+        Some(access) => Ok(Some(api::Snapshot {
+            revision: access.board.revision,
+            config: migrate::from_value(access.board.data)?,
+        })),
+        None => Ok(None),
     }
 }
 
-#[get("/load")]
+enum SaveResult {
+    Saved(i64),
+    /// The client's revision was not the latest one.
+    Conflict,
+}
+
+/// Saves a board if `snapshot.revision` is still its current revision, bumping it by one. Boards
+/// that have never been saved are at revision zero. Every successful save is also recorded in the
+/// board's history.
+fn save_settings(
+    c: &PgConnection,
+    board_id: Uuid,
+    snapshot: api::Snapshot,
+    history_config: &history::Config,
+) -> anyhow::Result<SaveResult> {
+    use schema::boards::dsl::*;
+
+    let new_data = serde_json::to_value(&snapshot.config)?;
+    c.transaction(|| {
+        let updated = diesel::update(boards.find(board_id).filter(revision.eq(snapshot.revision)))
+            .set((data.eq(&new_data), revision.eq(revision + 1)))
+            .returning(revision)
+            .get_result::<i64>(c)
+            .optional()?;
+        match updated {
+            Some(rev) => {
+                history::record(c, board_id, rev, &new_data, history_config)?;
+                Ok(SaveResult::Saved(rev))
+            }
+            None => Ok(SaveResult::Conflict),
+        }
+    })
+}
+
+/// Saves a board whatever its current revision is, bumping it by one, and records the result in
+/// the board's history. Returns the new revision.
+fn overwrite_settings(
+    c: &PgConnection,
+    board_id: Uuid,
+    new_data: &Value,
+    history_config: &history::Config,
+) -> QueryResult<i64> {
+    use schema::boards::dsl::*;
+
+    let new_revision = diesel::update(boards.find(board_id))
+        .set((data.eq(new_data), revision.eq(revision + 1)))
+        .returning(revision)
+        .get_result::<i64>(c)?;
+    history::record(c, board_id, new_revision, new_data, history_config)?;
+    Ok(new_revision)
+}
+
+#[get("/boards/<slug>/load")]
 async fn load(
+    session: auth::Session,
     db: DbConn,
-    cookies: &CookieJar<'_>,
-) -> Result<Option<Json<config::Config>>, status::Custom<&'static str>> {
-    let user_id = match cookies.get_private("session") {
-        None => return Err(status::Custom(Status::Unauthorized, "Unauthorized")),
-        Some(cookie) => String::from(cookie.value()),
-    };
-
-    match load_settings(db, user_id).await {
+    slug: String,
+) -> Result<Option<Json<api::Snapshot>>, status::Custom<&'static str>> {
+    let uid = session.user.id;
+    match db.run(move |c| load_settings(c, uid, &slug)).await {
         Ok(Some(settings)) => Ok(Some(Json(settings))),
         Ok(None) => Ok(None),
         Err(err) => {
@@ -61,40 +123,87 @@ async fn load(
     }
 }
 
-#[post("/save", data = "<data>")]
-async fn save(
-    db: DbConn,
-    cookies: &CookieJar<'_>,
-    data: Json<Value>,
-) -> Result<Json<Value>, status::Unauthorized<&'static str>> {
-    use schema::settings::dsl;
+#[derive(Responder)]
+enum SaveError {
+    /// The save was based on an old revision. The body is the current server copy.
+    #[response(status = 409)]
+    Conflict(Json<api::Snapshot>),
+    /// The settings could not be parsed, or did not pass validation.
+    #[response(status = 422)]
+    Invalid(Json<api::Error>),
+    /// The board doesn't exist, or the user may only view it.
+    Refused(boards::Refusal),
+    #[response(status = 500)]
+    Internal(&'static str),
+}
 
-    let uid = match cookies.get_private("session") {
-        None => return Err(status::Unauthorized(Some("Unauthorized"))),
-        Some(cookie) => String::from(cookie.value()),
-    };
+impl From<boards::Refusal> for SaveError {
+    fn from(refusal: boards::Refusal) -> Self {
+        Self::Refused(refusal)
+    }
+}
 
-    let new_settings = models::NewSettings {
-        data: data.into_inner(),
-        user_id: uid,
+#[post("/boards/<slug>/save", data = "<data>")]
+async fn save(
+    session: auth::Session,
+    db: DbConn,
+    history_config: &State<history::Config>,
+    hub: &State<live::Hub>,
+    slug: String,
+    data: Result<Json<api::Snapshot>, rocket::serde::json::Error<'_>>,
+) -> Result<Json<api::Saved>, SaveError> {
+    let uid = session.user.id;
+    let mut snapshot = match data {
+        Ok(data) => data.into_inner(),
+        Err(err) => {
+            let message = match err {
+                rocket::serde::json::Error::Io(err) => format!("could not read settings: {}", err),
+                rocket::serde::json::Error::Parse(_, err) => {
+                    format!("could not parse settings: {}", err)
+                }
+            };
+            return Err(SaveError::Invalid(Json(api::Error {
+                message,
+                problems: Vec::new(),
+            })));
+        }
     };
+    // Browsers keep every deleted character of a note until they merge in a compacted copy, so
+    // the limits apply to what's left once the board is compacted.
+    snapshot.config.compact_notes();
+    if let Err(problems) = snapshot.config.validate() {
+        return Err(SaveError::Invalid(Json(api::Error {
+            message: "invalid settings".to_owned(),
+            problems,
+        })));
+    }
 
+    let board_id = boards::lookup(&db, uid, slug.clone(), Role::Editor)
+        .await?
+        .board
+        .id;
+    let history_config = history_config.inner().clone();
+    let config = snapshot.config.clone();
     let res = db
-        .run(move |c| {
-            diesel::insert_into(schema::settings::table)
-                .values(&new_settings)
-                .on_conflict(dsl::user_id)
-                .do_update()
-                .set(dsl::data.eq(&new_settings.data))
-                .execute(c)
-        })
+        .run(move |c| save_settings(c, board_id, snapshot, &history_config))
         .await;
 
     match res {
-        Ok(_) => Ok(Json(Object(Map::new()))),
+        Ok(SaveResult::Saved(revision)) => {
+            hub.publish(board_id, api::Snapshot { revision, config });
+            Ok(Json(api::Saved { revision }))
+        }
+        Ok(SaveResult::Conflict) => match db.run(move |c| load_settings(c, uid, &slug)).await {
+            Ok(Some(current)) => Err(SaveError::Conflict(Json(current))),
+            Ok(None) => Err(SaveError::Refused(boards::Refusal::NotFound("Not Found"))),
+            Err(err) => {
+                log::error!("{}", err);
+                Err(SaveError::Internal("Internal Server Error"))
+            }
+        },
         Err(err) => {
             log::error!("{}", err);
-            return Err(status::Unauthorized(Some("Unauthorized")));
+            Err(SaveError::Internal("Internal Server Error"))
         }
     }
 }
@@ -107,23 +216,200 @@ fn authenticate_head() -> Redirect {
     Redirect::to("/")
 }
 
+#[derive(Deserialize)]
+struct LoginRequest {
+    email: String,
+}
+
+/// Emails a magic link to the given address. The response is the same whether or not the address
+/// has been seen before.
+#[post("/login", data = "<req>")]
+async fn login(
+    db: DbConn,
+    config: &State<auth::Config>,
+    mailer: &State<Box<dyn auth::mailer::Mailer>>,
+    throttle: &State<auth::throttle::Throttle>,
+    client: Option<IpAddr>,
+    req: Json<LoginRequest>,
+) -> Result<status::Accepted<Json<Value>>, status::Custom<&'static str>> {
+    let too_many = status::Custom(
+        Status::TooManyRequests,
+        "Too many login links requested. Try again later.",
+    );
+    let email = match auth::normalize_email(&req.email) {
+        Ok(email) => email,
+        Err(_) => return Err(status::Custom(Status::BadRequest, "Invalid email address")),
+    };
+    if let Some(client) = client {
+        if !throttle.allow(client) {
+            return Err(too_many);
+        }
+    }
+
+    let to = email.clone();
+    let res = db
+        .run(move |c| {
+            // Logging in is as good a time as any to clear out what can't be used any more.
+            auth::delete_expired(c)?;
+            auth::create_login_token(c, &to)
+        })
+        .await;
+    let token = match res {
+        Ok(token) => token,
+        Err(auth::AuthError::TooManyRequests) => return Err(too_many),
+        Err(err) => {
+            log::error!("{}", err);
+            return Err(status::Custom(
+                Status::InternalServerError,
+                "Internal Server Error",
+            ));
+        }
+    };
+
+    let email = auth::mailer::Email {
+        to: email,
+        subject: "Log in to Trellis".to_owned(),
+        body: format!(
+            "Follow this link to log in to Trellis:\n\n{}\n\nThis link expires soon and can only be used once. If you didn't ask for it, you can ignore this email.",
+            auth::login_url(config, &token),
+        ),
+    };
+    if let Err(err) = mailer.send(email).await {
+        log::error!("could not send login email: {}", err);
+        return Err(status::Custom(
+            Status::InternalServerError,
+            "Internal Server Error",
+        ));
+    }
+
+    Ok(status::Accepted(Some(Json(Object(Map::new())))))
+}
+
 #[get("/authenticate?<token>")]
 async fn authenticate(
+    db: DbConn,
     cookies: &CookieJar<'_>,
-    token: Option<&str>,
+    user_agent: auth::UserAgent,
+    token: Option<String>,
 ) -> Result<Redirect, status::Unauthorized<&'static str>> {
     if let Some(t) = token {
-        if let Ok(user) = auth::authenticate(t) {
-            cookies.add_private(Cookie::build("session", user.user_id).secure(true).finish());
-            return Ok(Redirect::to("/"));
+        let res = db
+            .run(move |c| {
+                let user = auth::authenticate(c, &t)?;
+                members::accept_invites(c, &user)?;
+                Ok::<_, auth::AuthError>(auth::session::create(c, user.id, user_agent.0)?)
+            })
+            .await;
+        match res {
+            Ok(session) => {
+                auth::session::set_cookie(cookies, &session);
+                return Ok(Redirect::to("/"));
+            }
+            Err(err) => log::info!("authentication failed: {}", err),
         }
     }
     Err(status::Unauthorized(Some("Unauthorized")))
 }
 
+/// Ends the current session.
+#[post("/logout")]
+async fn logout(
+    session: auth::Session,
+    db: DbConn,
+    cookies: &CookieJar<'_>,
+) -> Result<Json<Value>, status::Custom<&'static str>> {
+    let res = db.run(move |c| auth::session::revoke(c, session.id)).await;
+    auth::session::clear_cookie(cookies);
+
+    match res {
+        Ok(_) => Ok(Json(Object(Map::new()))),
+        Err(err) => {
+            log::error!("{}", err);
+            Err(status::Custom(
+                Status::InternalServerError,
+                "Internal Server Error",
+            ))
+        }
+    }
+}
+
+/// Ends every session for the current user, including sessions on other devices.
+#[post("/logout/everywhere")]
+async fn logout_everywhere(
+    session: auth::Session,
+    db: DbConn,
+    cookies: &CookieJar<'_>,
+) -> Result<Json<Value>, status::Custom<&'static str>> {
+    let res = db
+        .run(move |c| auth::session::revoke_all(c, session.user.id))
+        .await;
+    auth::session::clear_cookie(cookies);
+
+    match res {
+        Ok(_) => Ok(Json(Object(Map::new()))),
+        Err(err) => {
+            log::error!("{}", err);
+            Err(status::Custom(
+                Status::InternalServerError,
+                "Internal Server Error",
+            ))
+        }
+    }
+}
+
 #[launch]
 fn rocket() -> _ {
     rocket::build()
-        .mount("/v1", routes![load, save, authenticate, authenticate_head])
+        .mount(
+            "/v1",
+            routes![
+                boards::index,
+                boards::create,
+                boards::rename,
+                boards::delete,
+                boards::make_default,
+                load,
+                save,
+                login,
+                authenticate,
+                authenticate_head,
+                logout,
+                logout_everywhere,
+                favicons::show,
+                feeds::show,
+                history::list,
+                history::restore,
+                live::events,
+                live::shared_events,
+                members::list,
+                members::create_invite,
+                members::delete_invite,
+                members::update,
+                members::remove,
+                members::leave,
+                portable::export,
+                portable::import,
+                secrets::show,
+                secrets::save,
+                shares::list,
+                shares::create,
+                shares::revoke,
+                shares::show,
+                shares::current_weather,
+                shares::forecast_weather,
+                shares::favicon,
+                shares::feed,
+                weather::current,
+                weather::forecast,
+                weather::locations,
+            ],
+        )
         .attach(DbConn::fairing())
+        .attach(auth::fairing())
+        .attach(favicons::fairing())
+        .attach(feeds::fairing())
+        .attach(history::fairing())
+        .attach(live::fairing())
+        .attach(secrets::fairing())
+        .attach(weather::fairing())
 }
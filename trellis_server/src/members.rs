@@ -0,0 +1,345 @@
+//! Other users who can use a board. Owners invite people by email address, as editors or viewers,
+//! and the invite is sent as a magic link. Whoever logs in with that address next becomes a member.
+
+use super::auth::{self, mailer::Mailer, throttle::Throttle, AuthError, Session};
+use super::boards::{self, BoardError};
+use super::models;
+use super::schema;
+use super::DbConn;
+use chrono::{DateTime, Utc};
+use diesel::pg::upsert::excluded;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::State;
+use std::net::IpAddr;
+use trellis_core::api;
+use trellis_core::board::Role;
+use uuid::Uuid;
+
+fn parse_role(role: &str) -> anyhow::Result<Role> {
+    role.parse().map_err(anyhow::Error::msg)
+}
+
+/// Lists a board's members and the invites that haven't been accepted yet.
+fn members(conn: &PgConnection, board: Uuid) -> anyhow::Result<api::Members> {
+    let rows = schema::board_members::table
+        .inner_join(schema::users::table)
+        .filter(schema::board_members::board_id.eq(board))
+        .order(schema::users::email.asc())
+        .select((
+            schema::users::id,
+            schema::users::email,
+            schema::board_members::role,
+        ))
+        .load::<(Uuid, String, String)>(conn)?;
+    let invites = schema::board_invites::table
+        .filter(schema::board_invites::board_id.eq(board))
+        .order(schema::board_invites::email.asc())
+        .load::<models::BoardInvite>(conn)?;
+
+    Ok(api::Members {
+        members: rows
+            .into_iter()
+            .map(|(user_id, email, role)| {
+                Ok(api::Member {
+                    user_id,
+                    email,
+                    role: parse_role(&role)?,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?,
+        invites: invites
+            .into_iter()
+            .map(|invite| {
+                Ok(api::Invite {
+                    id: invite.id,
+                    email: invite.email,
+                    role: parse_role(&invite.role)?,
+                    created_at: DateTime::from_utc(invite.created_at, Utc),
+                })
+            })
+            .collect::<anyhow::Result<_>>()?,
+    })
+}
+
+/// Invites someone to a board, or changes the role they were invited with. Returns a magic link
+/// token for them.
+fn invite(
+    conn: &PgConnection,
+    board: Uuid,
+    invitee: &str,
+    invite_role: Role,
+) -> Result<String, AuthError> {
+    use schema::board_invites::dsl::*;
+
+    conn.transaction(|| {
+        diesel::insert_into(board_invites)
+            .values(&models::NewBoardInvite {
+                board_id: board,
+                email: invitee.to_owned(),
+                role: invite_role.as_str().to_owned(),
+            })
+            .on_conflict((board_id, email))
+            .do_update()
+            .set(role.eq(excluded(role)))
+            .execute(conn)?;
+        auth::create_login_token(conn, invitee)
+    })
+}
+
+/// Makes the user a member of every board they were invited to. Invites to the user's own boards
+/// are dropped, since owners can already do everything.
+pub fn accept_invites(conn: &PgConnection, user: &models::User) -> QueryResult<usize> {
+    conn.transaction(|| {
+        let invites = diesel::delete(
+            schema::board_invites::table.filter(schema::board_invites::email.eq(&user.email)),
+        )
+        .get_results::<models::BoardInvite>(conn)?;
+        let owned: Vec<Uuid> = schema::boards::table
+            .filter(schema::boards::user_id.eq(user.id))
+            .select(schema::boards::id)
+            .load(conn)?;
+
+        let new_members: Vec<_> = invites
+            .into_iter()
+            .filter(|invite| !owned.contains(&invite.board_id))
+            .map(|invite| models::NewBoardMember {
+                board_id: invite.board_id,
+                user_id: user.id,
+                role: invite.role,
+            })
+            .collect();
+        use schema::board_members::dsl::*;
+        diesel::insert_into(board_members)
+            .values(&new_members)
+            .on_conflict((board_id, user_id))
+            .do_update()
+            .set(role.eq(excluded(role)))
+            .execute(conn)
+    })
+}
+
+/// Checks a role that someone is being given. There's only ever one owner.
+fn member_role(new_role: Role) -> Result<Role, BoardError> {
+    if Role::MEMBER_ROLES.contains(&new_role) {
+        Ok(new_role)
+    } else {
+        Err(BoardError::Invalid(Json(api::Error {
+            message: format!("members can't be made {}s", new_role),
+            problems: Vec::new(),
+        })))
+    }
+}
+
+/// Lists who else can use one of the user's boards. Only the owner can see them.
+#[get("/boards/<slug>/members")]
+pub async fn list(
+    session: Session,
+    db: DbConn,
+    slug: String,
+) -> Result<Json<api::Members>, BoardError> {
+    let board = boards::lookup(&db, session.user.id, slug, Role::Owner)
+        .await?
+        .board
+        .id;
+    db.run(move |c| members(c, board))
+        .await
+        .map(Json)
+        .map_err(BoardError::internal)
+}
+
+/// Invites someone to one of the user's boards by emailing them a magic link. Returns the board's
+/// members and invites. Invites count towards the same limit on mail as logging in.
+#[post("/boards/<slug>/invites", data = "<req>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn create_invite(
+    session: Session,
+    db: DbConn,
+    config: &State<auth::Config>,
+    mailer: &State<Box<dyn Mailer>>,
+    throttle: &State<Throttle>,
+    client: Option<IpAddr>,
+    slug: String,
+    req: Json<api::NewInvite>,
+) -> Result<Json<api::Members>, BoardError> {
+    let inviter = session.user.email.clone();
+    let invitee = auth::normalize_email(&req.email).map_err(|err| {
+        BoardError::Invalid(Json(api::Error {
+            message: err.to_string(),
+            problems: Vec::new(),
+        }))
+    })?;
+    let new_role = member_role(req.role)?;
+    let board = boards::lookup(&db, session.user.id, slug, Role::Owner)
+        .await?
+        .board;
+    let board_id = board.id;
+    if invitee == inviter {
+        return Err(BoardError::Invalid(Json(api::Error {
+            message: "you already own this board".to_owned(),
+            problems: Vec::new(),
+        })));
+    }
+    if let Some(client) = client {
+        if !throttle.allow(client) {
+            return Err(BoardError::too_many_requests(
+                "Too many invites sent. Try again later.",
+            ));
+        }
+    }
+
+    let to = invitee.clone();
+    let token = match db.run(move |c| invite(c, board_id, &to, new_role)).await {
+        Ok(token) => token,
+        Err(AuthError::TooManyRequests) => {
+            return Err(BoardError::too_many_requests(
+                "Too many links sent to that address. Try again later.",
+            ))
+        }
+        Err(err) => return Err(BoardError::internal(err.into())),
+    };
+    let email = auth::mailer::Email {
+        to: invitee,
+        subject: format!("{} shared a Trellis board with you", inviter),
+        body: format!(
+            "{} invited you to the board \"{}\" on Trellis as {} {}. Follow this link to log in and see it:\n\n{}\n\nThis link expires soon and can only be used once. After that, logging in to Trellis with this email address will accept the invite.",
+            inviter,
+            board.name,
+            if new_role == Role::Editor { "an" } else { "a" },
+            new_role,
+            auth::login_url(config, &token),
+        ),
+    };
+    if let Err(err) = mailer.send(email).await {
+        return Err(BoardError::internal(
+            err.context("could not send invite email"),
+        ));
+    }
+
+    db.run(move |c| members(c, board_id))
+        .await
+        .map(Json)
+        .map_err(BoardError::internal)
+}
+
+/// Takes back an invite that hasn't been accepted yet. Returns the board's members and invites.
+#[delete("/boards/<slug>/invites/<invite_id>")]
+pub async fn delete_invite(
+    session: Session,
+    db: DbConn,
+    slug: String,
+    invite_id: Uuid,
+) -> Result<Json<api::Members>, BoardError> {
+    let board = boards::lookup(&db, session.user.id, slug, Role::Owner)
+        .await?
+        .board
+        .id;
+    db.run(move |c| {
+        use schema::board_invites::dsl::*;
+        diesel::delete(board_invites.find(invite_id).filter(board_id.eq(board))).execute(c)?;
+        members(c, board)
+    })
+    .await
+    .map(Json)
+    .map_err(BoardError::internal)
+}
+
+/// Changes what a member can do with one of the user's boards. Returns the board's members and
+/// invites.
+#[put("/boards/<slug>/members/<member_id>", data = "<req>")]
+pub async fn update(
+    session: Session,
+    db: DbConn,
+    slug: String,
+    member_id: Uuid,
+    req: Json<api::MemberRole>,
+) -> Result<Option<Json<api::Members>>, BoardError> {
+    let new_role = member_role(req.role)?;
+    let board = boards::lookup(&db, session.user.id, slug, Role::Owner)
+        .await?
+        .board
+        .id;
+    let res = db
+        .run(move |c| {
+            use schema::board_members::dsl::*;
+            let updated = diesel::update(board_members.find((board, member_id)))
+                .set(role.eq(new_role.as_str()))
+                .execute(c)?;
+            if updated == 0 {
+                return Ok(None);
+            }
+            Ok(Some(members(c, board)?))
+        })
+        .await;
+    res.map(|members| members.map(Json))
+        .map_err(BoardError::internal)
+}
+
+/// Stops a member from using one of the user's boards. Returns the board's members and invites.
+#[delete("/boards/<slug>/members/<member_id>")]
+pub async fn remove(
+    session: Session,
+    db: DbConn,
+    slug: String,
+    member_id: Uuid,
+) -> Result<Json<api::Members>, BoardError> {
+    let board = boards::lookup(&db, session.user.id, slug, Role::Owner)
+        .await?
+        .board
+        .id;
+    db.run(move |c| {
+        use schema::board_members::dsl::*;
+        diesel::delete(board_members.find((board, member_id))).execute(c)?;
+        members(c, board)
+    })
+    .await
+    .map(Json)
+    .map_err(BoardError::internal)
+}
+
+/// Stops using a board that someone else owns. Returns the user's boards.
+#[delete("/boards/<slug>/membership")]
+pub async fn leave(
+    session: Session,
+    db: DbConn,
+    slug: String,
+) -> Result<Json<api::BoardList>, status::Custom<&'static str>> {
+    let uid = session.user.id;
+    let access = boards::lookup(&db, uid, slug, Role::Viewer).await?;
+    if access.role == Role::Owner {
+        return Err(status::Custom(
+            Status::Conflict,
+            "Owners can't leave their own boards",
+        ));
+    }
+    let board = access.board.id;
+    let res = db
+        .run(move |c| {
+            use schema::board_members::dsl::*;
+            diesel::delete(board_members.find((board, uid))).execute(c)?;
+            boards::list(c, uid)
+        })
+        .await;
+    res.map(Json).map_err(|err| {
+        log::error!("{}", err);
+        status::Custom(Status::InternalServerError, "Internal Server Error")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_members_cannot_be_made_owners() {
+        assert_eq!(member_role(Role::Editor).ok(), Some(Role::Editor));
+        assert_eq!(member_role(Role::Viewer).ok(), Some(Role::Viewer));
+        assert!(matches!(
+            member_role(Role::Owner),
+            Err(BoardError::Invalid(_))
+        ));
+    }
+}
@@ -1,20 +1,163 @@
-use super::schema::settings;
+use super::schema::{
+    board_invites, board_members, boards, login_tokens, secrets, sessions, settings_history,
+    share_links, users,
+};
 use chrono::NaiveDateTime;
 use serde_json::Value as Jsonb;
 use uuid::Uuid;
 
 #[derive(Insertable)]
-#[table_name = "settings"]
-pub struct NewSettings {
+#[table_name = "boards"]
+pub struct NewBoard {
     pub data: Jsonb,
-    pub user_id: String,
+    pub user_id: Uuid,
+    pub revision: i64,
+    pub slug: String,
+    pub name: String,
 }
 
 #[derive(Queryable)]
-pub struct Settings {
+pub struct Board {
     pub id: Uuid,
     pub data: Jsonb,
-    pub user_id: String,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub user_id: Uuid,
+    pub revision: i64,
+    pub slug: String,
+    pub name: String,
+}
+
+#[derive(Insertable)]
+#[table_name = "settings_history"]
+pub struct NewSettingsHistory {
+    pub revision: i64,
+    pub data: Jsonb,
+    pub tile_count: i32,
+    pub board_id: Uuid,
+}
+
+#[derive(Queryable)]
+pub struct SettingsHistory {
+    pub id: Uuid,
+    pub revision: i64,
+    pub data: Jsonb,
+    pub tile_count: i32,
+    pub created_at: NaiveDateTime,
+    pub board_id: Uuid,
+}
+
+#[derive(Insertable)]
+#[table_name = "users"]
+pub struct NewUser {
+    pub email: String,
+    pub last_login_at: Option<NaiveDateTime>,
+}
+
+#[derive(Queryable, Debug, Clone)]
+pub struct User {
+    pub id: Uuid,
+    pub email: String,
+    pub display_name: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_login_at: Option<NaiveDateTime>,
+    pub default_board_id: Option<Uuid>,
+}
+
+#[derive(Insertable)]
+#[table_name = "login_tokens"]
+pub struct NewLoginToken {
+    pub token_hash: String,
+    pub email: String,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Queryable)]
+pub struct LoginToken {
+    pub id: Uuid,
+    pub token_hash: String,
+    pub email: String,
+    pub expires_at: NaiveDateTime,
+    pub used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "sessions"]
+pub struct NewSession {
+    pub user_id: Uuid,
+    pub user_agent: Option<String>,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Debug, Clone)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub user_agent: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_seen_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "secrets"]
+pub struct NewSecret {
+    pub user_id: Uuid,
+    pub name: String,
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+#[derive(Queryable)]
+pub struct Secret {
+    pub user_id: Uuid,
+    pub name: String,
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "share_links"]
+pub struct NewShareLink {
+    pub board_id: Uuid,
+    pub token_hash: String,
+    pub label: String,
+}
+
+#[derive(Queryable)]
+pub struct ShareLink {
+    pub id: Uuid,
+    pub board_id: Uuid,
+    pub token_hash: String,
+    pub label: String,
+    pub created_at: NaiveDateTime,
+    pub last_viewed_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[table_name = "board_members"]
+pub struct NewBoardMember {
+    pub board_id: Uuid,
+    pub user_id: Uuid,
+    pub role: String,
+}
+
+#[derive(Insertable)]
+#[table_name = "board_invites"]
+pub struct NewBoardInvite {
+    pub board_id: Uuid,
+    pub email: String,
+    pub role: String,
+}
+
+#[derive(Queryable)]
+pub struct BoardInvite {
+    pub id: Uuid,
+    pub board_id: Uuid,
+    pub email: String,
+    pub role: String,
+    pub created_at: NaiveDateTime,
 }
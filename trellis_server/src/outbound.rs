@@ -0,0 +1,174 @@
+//! Requests to other sites on users' behalf, like favicons and feeds. Users choose the URLs, so
+//! these requests are kept to sites on the internet. Otherwise a board could make the server fetch
+//! things from its own network and pass them along.
+
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::redirect::Policy;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// How many redirects to follow before giving up.
+const MAX_REDIRECTS: usize = 3;
+
+/// Whether a host looks like a site on the internet. Anything else, like `localhost` or an IP
+/// address, could be a way to make the server fetch things from its own network. Names can still
+/// resolve to addresses on that network, which `PublicResolver` catches.
+pub fn is_public_host(host: &str) -> bool {
+    let host = host.trim_end_matches('.');
+    let internal = ["localhost", "local", "internal", "lan", "home.arpa"];
+    host.contains('.')
+        && host.parse::<IpAddr>().is_err()
+        && !host.starts_with('[')
+        && !internal
+            .iter()
+            .any(|name| host == *name || host.ends_with(&format!(".{}", name)))
+}
+
+/// Whether an address is routed on the internet, rather than being private, loopback, link-local,
+/// shared, reserved, or for documentation.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => is_public_ipv6(ip),
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // "This network", carrier-grade NAT, IETF protocol assignments, benchmarking, and the
+        // reserved block above multicast.
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 192 && b == 0 && c == 0)
+        || (a == 198 && (18..20).contains(&b))
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let segments = ip.segments();
+    // IPv4 addresses can be written as IPv6 ones, either mapped or translated by NAT64.
+    if segments[..5] == [0; 5] && segments[5] == 0xffff
+        || segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0]
+    {
+        let [a, b] = segments[6].to_be_bytes();
+        let [c, d] = segments[7].to_be_bytes();
+        return is_public_ipv4(Ipv4Addr::new(a, b, c, d));
+    }
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // Unique local, link-local, and documentation addresses.
+        || (segments[0] & 0xfe00) == 0xfc00
+        || (segments[0] & 0xffc0) == 0xfe80
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8))
+}
+
+/// Follows a few redirects, but only to other sites on the internet.
+pub fn redirect_policy() -> Policy {
+    Policy::custom(|attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            attempt.error("too many redirects")
+        } else if matches!(attempt.url().host_str(), Some(host) if is_public_host(host)) {
+            attempt.follow()
+        } else {
+            attempt.stop()
+        }
+    })
+}
+
+/// Looks up hosts like the system does, but only gives back their public addresses. Checking the
+/// addresses that are actually connected to means a name can't be pointed at the server's own
+/// network after it has been checked.
+pub struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str();
+            let addrs: Vec<SocketAddr> = rocket::tokio::net::lookup_host((host, 0))
+                .await?
+                .filter(|addr| is_public_ip(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public addresses", host).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_fetches_from_public_hosts() {
+        for host in &[
+            "example.com",
+            "www.example.co.uk",
+            "bücher.example",
+            "example.com.",
+        ] {
+            assert!(is_public_host(host), "{}", host);
+        }
+        for host in &[
+            "localhost",
+            "db",
+            "printer.local",
+            "api.localhost",
+            "router.home.arpa",
+            "127.0.0.1",
+            "10.0.0.1",
+            "[::1]",
+            "",
+        ] {
+            assert!(!is_public_host(host), "{}", host);
+        }
+    }
+
+    #[test]
+    fn test_only_connects_to_public_addresses() {
+        for ip in &[
+            "93.184.216.34",
+            "2606:2800:220:1::1",
+            "::ffff:93.184.216.34",
+        ] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in &[
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "198.18.0.1",
+            "203.0.113.5",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "ff02::1",
+            "2001:db8::1",
+            "::ffff:127.0.0.1",
+            "64:ff9b::a00:1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[rocket::async_test]
+    async fn test_resolver_drops_private_addresses() {
+        let name: Name = "localhost".parse().unwrap();
+        assert!(PublicResolver.resolve(name).await.is_err());
+    }
+}
@@ -0,0 +1,255 @@
+use super::auth::Session;
+use super::boards;
+use super::history;
+use super::live::Hub;
+use super::schema;
+use super::secrets::{self, Vault};
+use super::DbConn;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use rocket::data::{Data, ToByteUnit};
+use rocket::http::{ContentType, Header, Status};
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::State;
+use serde_json::Value;
+use trellis_core::board::Role;
+use trellis_core::config::ValidationError;
+use trellis_core::portable::{self, Format};
+use trellis_core::{api, migrate};
+use uuid::Uuid;
+
+/// The largest file that can be imported, in mebibytes.
+const IMPORT_LIMIT_MIB: u64 = 1;
+
+/// Reads the `format` query parameter, which defaults to JSON.
+fn parse_format(format: Option<&str>) -> Result<Format, String> {
+    format.map_or(Ok(Format::Json), str::parse)
+}
+
+/// A board as a file attachment.
+#[derive(Responder)]
+pub struct Download {
+    file: (ContentType, String),
+    disposition: Header<'static>,
+}
+
+/// Downloads a board that the user can see. Secrets are left out unless `secrets` is set, and are
+/// always the user's own.
+#[get("/boards/<slug>/export?<format>&<secrets>")]
+pub async fn export(
+    session: Session,
+    db: DbConn,
+    vault: &State<Vault>,
+    slug: String,
+    format: Option<&str>,
+    secrets: bool,
+) -> Result<Option<Download>, status::Custom<&'static str>> {
+    let uid = session.user.id;
+    let include_secrets = secrets;
+    let format = match parse_format(format) {
+        Ok(format) => format,
+        Err(_) => return Err(status::Custom(Status::BadRequest, "Unknown format")),
+    };
+
+    let vault = vault.inner().clone();
+    let secrets = if include_secrets {
+        db.run(move |c| secrets::load(c, &vault, uid))
+            .await
+            .map(Some)
+    } else {
+        Ok(None)
+    };
+    let board_slug = slug.clone();
+    let snapshot = db
+        .run(move |c| super::load_settings(c, uid, &board_slug))
+        .await;
+    let file = match (snapshot, secrets) {
+        (Ok(Some(snapshot)), Ok(secrets)) => {
+            portable::export(&snapshot.config, secrets.as_ref(), format)
+                .map(Some)
+                .map_err(anyhow::Error::from)
+        }
+        (Ok(None), _) => Ok(None),
+        (Err(err), _) | (_, Err(err)) => Err(err),
+    };
+    let file = match file {
+        Ok(Some(file)) => file,
+        Ok(None) => return Ok(None),
+        Err(err) => {
+            log::error!("{}", err);
+            return Err(status::Custom(
+                Status::InternalServerError,
+                "Internal Server Error",
+            ));
+        }
+    };
+
+    let content_type = match format {
+        Format::Json => ContentType::JSON,
+        Format::Toml => ContentType::new("application", "toml"),
+    };
+    Ok(Some(Download {
+        file: (content_type, file),
+        disposition: Header::new(
+            "Content-Disposition",
+            format!(
+                "attachment; filename=\"trellis-{}.{}\"",
+                slug,
+                format.extension()
+            ),
+        ),
+    }))
+}
+
+/// How an imported board is combined with the current one.
+#[derive(FromFormField, Clone, Copy, Debug)]
+pub enum Mode {
+    /// The imported board takes the place of the current one.
+    Replace,
+    /// Tiles from the imported board are added to the current one. See `portable::merge_into`.
+    Merge,
+}
+
+enum ImportResult {
+    /// What the board is now.
+    Imported(api::Snapshot),
+    /// The merged board would not be valid.
+    Invalid(Vec<ValidationError>),
+}
+
+fn import_settings(
+    c: &PgConnection,
+    uid: Uuid,
+    board_id: Uuid,
+    imported: portable::Imported,
+    mode: Mode,
+    history_config: &history::Config,
+    vault: &Vault,
+) -> anyhow::Result<ImportResult> {
+    c.transaction(|| {
+        let new_config = match mode {
+            Mode::Replace => imported.config,
+            Mode::Merge => {
+                let current = schema::boards::table
+                    .find(board_id)
+                    .select(schema::boards::data)
+                    .for_update()
+                    .first::<Value>(c)?;
+                let current = migrate::from_value(current)?;
+                let merged = portable::merge_into(&current, imported.config);
+                if let Err(problems) = merged.validate() {
+                    return Ok(ImportResult::Invalid(problems));
+                }
+                merged
+            }
+        };
+
+        let new_data = serde_json::to_value(&new_config)?;
+        let revision = super::overwrite_settings(c, board_id, &new_data, history_config)?;
+
+        if let Some(mut new_secrets) = imported.secrets {
+            if let Mode::Merge = mode {
+                // Keep any secrets the user already has.
+                if secrets::stored(c, uid)?.owm_api_key {
+                    new_secrets.owm_api_key = None;
+                }
+            }
+            secrets::update(c, vault, uid, &new_secrets)?;
+        }
+        Ok(ImportResult::Imported(api::Snapshot {
+            revision,
+            config: new_config,
+        }))
+    })
+}
+
+#[derive(Responder)]
+pub enum ImportError {
+    #[response(status = 413)]
+    TooLarge(Json<api::Error>),
+    /// The file could not be read as a board, or the board is not valid.
+    #[response(status = 422)]
+    Invalid(Json<api::Error>),
+    /// The board doesn't exist, or the user may only view it.
+    Refused(boards::Refusal),
+    #[response(status = 500)]
+    Internal(&'static str),
+}
+
+impl From<boards::Refusal> for ImportError {
+    fn from(refusal: boards::Refusal) -> Self {
+        Self::Refused(refusal)
+    }
+}
+
+impl ImportError {
+    fn invalid(message: String, problems: Vec<ValidationError>) -> Self {
+        Self::Invalid(Json(api::Error { message, problems }))
+    }
+}
+
+/// Uploads a board file, replacing a board that the user can edit or merging into it. The import is
+/// saved as a new revision whatever the current revision is, so it can be undone from the history.
+/// Secrets in the file are stored too, but when merging they don't replace secrets the user
+/// already has.
+#[post("/boards/<slug>/import?<format>&<mode>", data = "<file>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn import(
+    session: Session,
+    db: DbConn,
+    history_config: &State<history::Config>,
+    vault: &State<Vault>,
+    hub: &State<Hub>,
+    slug: String,
+    format: Option<&str>,
+    mode: Mode,
+    file: Data<'_>,
+) -> Result<Json<api::Snapshot>, ImportError> {
+    let uid = session.user.id;
+    let format = parse_format(format).map_err(|err| ImportError::invalid(err, Vec::new()))?;
+    let board_id = boards::lookup(&db, uid, slug, Role::Editor).await?.board.id;
+
+    let file = match file.open(IMPORT_LIMIT_MIB.mebibytes()).into_string().await {
+        Ok(file) if file.is_complete() => file.into_inner(),
+        Ok(_) => {
+            return Err(ImportError::TooLarge(Json(api::Error {
+                message: format!("file is larger than {} MiB", IMPORT_LIMIT_MIB),
+                problems: Vec::new(),
+            })))
+        }
+        Err(err) => {
+            return Err(ImportError::invalid(
+                format!("could not read file: {}", err),
+                Vec::new(),
+            ))
+        }
+    };
+    let imported = match portable::import(&file, format) {
+        Ok(imported) => imported,
+        Err(portable::ImportError::Invalid(problems)) => {
+            return Err(ImportError::invalid("invalid board".to_owned(), problems))
+        }
+        Err(err) => return Err(ImportError::invalid(err.to_string(), Vec::new())),
+    };
+
+    let history_config = history_config.inner().clone();
+    let vault = vault.inner().clone();
+    let res = db
+        .run(move |c| import_settings(c, uid, board_id, imported, mode, &history_config, &vault))
+        .await;
+    match res {
+        Ok(ImportResult::Imported(snapshot)) => {
+            hub.publish(board_id, snapshot.clone());
+            Ok(Json(snapshot))
+        }
+        Ok(ImportResult::Invalid(problems)) => Err(ImportError::invalid(
+            "the merged board would be invalid".to_owned(),
+            problems,
+        )),
+        Err(err) => {
+            log::error!("{}", err);
+            Err(ImportError::Internal("Internal Server Error"))
+        }
+    }
+}
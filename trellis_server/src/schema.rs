@@ -1,9 +1,117 @@
 table! {
-    settings (id) {
+    board_invites (id) {
+        id -> Uuid,
+        board_id -> Uuid,
+        email -> Text,
+        role -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    board_members (board_id, user_id) {
+        board_id -> Uuid,
+        user_id -> Uuid,
+        role -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    boards (id) {
         id -> Uuid,
         data -> Jsonb,
-        user_id -> Text,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        user_id -> Uuid,
+        revision -> Int8,
+        slug -> Text,
+        name -> Text,
+    }
+}
+
+table! {
+    login_tokens (id) {
+        id -> Uuid,
+        token_hash -> Text,
+        email -> Text,
+        expires_at -> Timestamptz,
+        used_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    secrets (user_id, name) {
+        user_id -> Uuid,
+        name -> Text,
+        nonce -> Bytea,
+        ciphertext -> Bytea,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+table! {
+    sessions (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        user_agent -> Nullable<Text>,
+        created_at -> Timestamptz,
+        last_seen_at -> Timestamptz,
+        expires_at -> Timestamptz,
+    }
+}
+
+table! {
+    settings_history (id) {
+        id -> Uuid,
+        revision -> Int8,
+        data -> Jsonb,
+        tile_count -> Int4,
+        created_at -> Timestamptz,
+        board_id -> Uuid,
+    }
+}
+
+table! {
+    share_links (id) {
+        id -> Uuid,
+        board_id -> Uuid,
+        token_hash -> Text,
+        label -> Text,
+        created_at -> Timestamptz,
+        last_viewed_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    users (id) {
+        id -> Uuid,
+        email -> Text,
+        display_name -> Nullable<Text>,
+        created_at -> Timestamptz,
+        last_login_at -> Nullable<Timestamptz>,
+        default_board_id -> Nullable<Uuid>,
     }
 }
+
+joinable!(board_invites -> boards (board_id));
+joinable!(board_members -> boards (board_id));
+joinable!(board_members -> users (user_id));
+joinable!(secrets -> users (user_id));
+joinable!(sessions -> users (user_id));
+joinable!(settings_history -> boards (board_id));
+joinable!(share_links -> boards (board_id));
+
+allow_tables_to_appear_in_same_query!(
+    board_invites,
+    board_members,
+    boards,
+    login_tokens,
+    secrets,
+    sessions,
+    settings_history,
+    share_links,
+    users,
+);
@@ -0,0 +1,368 @@
+use super::auth::Session;
+use super::models;
+use super::schema;
+use super::DbConn;
+use aes_gcm::aead::{Aead, NewAead, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{anyhow, bail};
+use diesel::dsl::sql;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::sql_types::Bool;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::Deserialize;
+use serde_json::map::Map;
+use serde_json::Value::{self, Object};
+use std::collections::HashSet;
+use trellis_core::api;
+use trellis_core::config::Secrets;
+use uuid::Uuid;
+
+/// The name the OpenWeatherMap API key is stored under.
+pub const OWM_API_KEY: &str = "owm_api_key";
+
+const KEY_BYTES: usize = 32;
+const NONCE_BYTES: usize = 12;
+
+#[derive(Deserialize)]
+pub struct Config {
+    /// A base64-encoded 256-bit key. Changing it makes every stored secret unreadable.
+    pub encryption_key: String,
+}
+
+/// Encrypts and decrypts secrets with the server's key.
+#[derive(Clone)]
+pub struct Vault {
+    cipher: Aes256Gcm,
+}
+
+impl Vault {
+    pub fn new(config: &Config) -> anyhow::Result<Self> {
+        let key = base64::decode(&config.encryption_key)?;
+        if key.len() != KEY_BYTES {
+            bail!(
+                "encryption key must be {} bytes, not {}",
+                KEY_BYTES,
+                key.len()
+            );
+        }
+        Ok(Self {
+            cipher: Aes256Gcm::new(Key::from_slice(&key)),
+        })
+    }
+
+    /// Binds a ciphertext to its owner and name, so it can't be decrypted if it is copied to
+    /// another row.
+    fn associated_data(user_id: Uuid, name: &str) -> Vec<u8> {
+        let mut data = user_id.as_bytes().to_vec();
+        data.extend_from_slice(name.as_bytes());
+        data
+    }
+
+    /// Encrypts a secret, returning the nonce and the ciphertext.
+    fn seal(&self, user_id: Uuid, name: &str, value: &str) -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
+        let mut nonce = [0u8; NONCE_BYTES];
+        OsRng.fill_bytes(&mut nonce);
+        let payload = Payload {
+            msg: value.as_bytes(),
+            aad: &Self::associated_data(user_id, name),
+        };
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), payload)
+            .map_err(|_| anyhow!("could not encrypt secret {}", name))?;
+        Ok((nonce.to_vec(), ciphertext))
+    }
+
+    fn open(&self, secret: &models::Secret) -> anyhow::Result<String> {
+        if secret.nonce.len() != NONCE_BYTES {
+            bail!("secret {} has an invalid nonce", secret.name);
+        }
+        let payload = Payload {
+            msg: &secret.ciphertext,
+            aad: &Self::associated_data(secret.user_id, &secret.name),
+        };
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(&secret.nonce), payload)
+            .map_err(|_| anyhow!("could not decrypt secret {}", secret.name))?;
+        Ok(String::from_utf8(plaintext)?)
+    }
+}
+
+/// Loads the `secrets` configuration section into managed state, then moves any secrets still
+/// stored the old way into encrypted storage.
+pub fn fairing() -> AdHoc {
+    AdHoc::try_on_ignite("Secrets", |rocket| async {
+        let vault = rocket
+            .figment()
+            .extract_inner::<Config>("secrets")
+            .map_err(anyhow::Error::from)
+            .and_then(|config| Vault::new(&config));
+        let vault = match vault {
+            Ok(vault) => vault,
+            Err(err) => {
+                log::error!("invalid secrets config: {}", err);
+                return Err(rocket);
+            }
+        };
+
+        let db = match DbConn::get_one(&rocket).await {
+            Some(db) => db,
+            None => {
+                log::error!("could not connect to the database to move secrets");
+                return Err(rocket);
+            }
+        };
+        let v = vault.clone();
+        match db.run(move |c| move_plaintext_secrets(c, &v)).await {
+            Ok(0) => {}
+            Ok(n) => log::info!("moved plaintext secrets for {} users", n),
+            Err(err) => {
+                log::error!("could not move plaintext secrets: {}", err);
+                return Err(rocket);
+            }
+        }
+        Ok(rocket.manage(vault))
+    })
+}
+
+/// Before schema version 3, secrets were stored in plaintext inside the settings document. This
+/// encrypts any that are left, then scrubs them from the settings and their history. Returns how
+/// many users had secrets moved.
+fn move_plaintext_secrets(conn: &PgConnection, vault: &Vault) -> anyhow::Result<usize> {
+    conn.transaction(|| {
+        let rows = schema::boards::table
+            .filter(sql::<Bool>("data ? 'secrets'"))
+            .select((schema::boards::user_id, schema::boards::data))
+            .load::<(Uuid, Value)>(conn)?;
+        let mut users = HashSet::new();
+        for (uid, data) in rows.iter() {
+            let legacy: Secrets = serde_json::from_value(data["secrets"].clone())?;
+            if let Some(value) = legacy.owm_api_key {
+                if !stored(conn, *uid)?.owm_api_key {
+                    put(conn, vault, *uid, OWM_API_KEY, &value)?;
+                }
+                users.insert(*uid);
+            }
+        }
+
+        diesel::sql_query("update boards set data = data - 'secrets' where data ? 'secrets'")
+            .execute(conn)?;
+        diesel::sql_query(
+            "update settings_history set data = data - 'secrets' where data ? 'secrets'",
+        )
+        .execute(conn)?;
+        Ok(users.len())
+    })
+}
+
+/// Encrypts and stores a secret, replacing any previous value.
+pub fn put(
+    conn: &PgConnection,
+    vault: &Vault,
+    uid: Uuid,
+    secret_name: &str,
+    value: &str,
+) -> anyhow::Result<()> {
+    use schema::secrets::dsl::*;
+
+    let (new_nonce, new_ciphertext) = vault.seal(uid, secret_name, value)?;
+    diesel::insert_into(secrets)
+        .values(&models::NewSecret {
+            user_id: uid,
+            name: secret_name.to_owned(),
+            nonce: new_nonce.clone(),
+            ciphertext: new_ciphertext.clone(),
+        })
+        .on_conflict((user_id, name))
+        .do_update()
+        .set((nonce.eq(new_nonce), ciphertext.eq(new_ciphertext)))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Forgets a secret.
+pub fn remove(conn: &PgConnection, uid: Uuid, secret_name: &str) -> QueryResult<usize> {
+    use schema::secrets::dsl::*;
+    diesel::delete(secrets.find((uid, secret_name))).execute(conn)
+}
+
+/// Loads and decrypts a secret.
+pub fn get(
+    conn: &PgConnection,
+    vault: &Vault,
+    uid: Uuid,
+    secret_name: &str,
+) -> anyhow::Result<Option<String>> {
+    let secret = schema::secrets::table
+        .find((uid, secret_name))
+        .first::<models::Secret>(conn)
+        .optional()?;
+    secret.map(|s| vault.open(&s)).transpose()
+}
+
+/// Loads and decrypts all of the user's secrets.
+pub fn load(conn: &PgConnection, vault: &Vault, uid: Uuid) -> anyhow::Result<Secrets> {
+    Ok(Secrets {
+        owm_api_key: get(conn, vault, uid, OWM_API_KEY)?,
+    })
+}
+
+/// Reports which secrets the user has stored.
+pub fn stored(conn: &PgConnection, uid: Uuid) -> QueryResult<api::SecretsStatus> {
+    use schema::secrets::dsl::*;
+
+    let names = secrets
+        .filter(user_id.eq(uid))
+        .select(name)
+        .load::<String>(conn)?;
+    Ok(api::SecretsStatus {
+        owm_api_key: names.iter().any(|n| n == OWM_API_KEY),
+    })
+}
+
+/// Applies changes to the user's secrets. Secrets that are `None` are left alone, and secrets set
+/// to an empty string are removed.
+pub fn update(
+    conn: &PgConnection,
+    vault: &Vault,
+    uid: Uuid,
+    changes: &Secrets,
+) -> anyhow::Result<()> {
+    conn.transaction(|| {
+        match changes.owm_api_key.as_deref().map(str::trim) {
+            None => {}
+            Some("") => {
+                remove(conn, uid, OWM_API_KEY)?;
+            }
+            Some(value) => put(conn, vault, uid, OWM_API_KEY, value)?,
+        }
+        Ok(())
+    })
+}
+
+/// Reports which secrets the user has stored, without revealing them.
+#[get("/secrets")]
+pub async fn show(
+    session: Session,
+    db: DbConn,
+) -> Result<Json<api::SecretsStatus>, status::Custom<&'static str>> {
+    let uid = session.user.id;
+    match db.run(move |c| stored(c, uid)).await {
+        Ok(stored) => Ok(Json(stored)),
+        Err(err) => {
+            log::error!("{}", err);
+            Err(status::Custom(
+                Status::InternalServerError,
+                "Internal Server Error",
+            ))
+        }
+    }
+}
+
+/// Sets or removes the user's secrets. See `update` for how the request body is read.
+#[put("/secrets", data = "<changes>")]
+pub async fn save(
+    session: Session,
+    db: DbConn,
+    vault: &State<Vault>,
+    changes: Json<Secrets>,
+) -> Result<Json<Value>, status::Custom<&'static str>> {
+    let uid = session.user.id;
+    let vault = vault.inner().clone();
+    match db.run(move |c| update(c, &vault, uid, &changes)).await {
+        Ok(()) => Ok(Json(Object(Map::new()))),
+        Err(err) => {
+            log::error!("{}", err);
+            Err(status::Custom(
+                Status::InternalServerError,
+                "Internal Server Error",
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn vault(key: &[u8; KEY_BYTES]) -> Vault {
+        Vault::new(&Config {
+            encryption_key: base64::encode(key),
+        })
+        .unwrap()
+    }
+
+    fn sealed(vault: &Vault, user_id: Uuid, name: &str, value: &str) -> models::Secret {
+        let (nonce, ciphertext) = vault.seal(user_id, name, value).unwrap();
+        let now = Utc::now().naive_utc();
+        models::Secret {
+            user_id,
+            name: name.to_owned(),
+            nonce,
+            ciphertext,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn test_vault_round_trip() {
+        let vault = vault(&[7; KEY_BYTES]);
+        let user_id = Uuid::new_v4();
+        let secret = sealed(&vault, user_id, OWM_API_KEY, "abc123");
+
+        assert_ne!(secret.ciphertext, b"abc123".to_vec());
+        assert_eq!(vault.open(&secret).unwrap(), "abc123");
+        // Each seal uses a fresh nonce.
+        assert_ne!(
+            sealed(&vault, user_id, OWM_API_KEY, "abc123").nonce,
+            secret.nonce
+        );
+    }
+
+    #[test]
+    fn test_vault_rejects_the_wrong_key() {
+        let secret = sealed(
+            &vault(&[7; KEY_BYTES]),
+            Uuid::new_v4(),
+            OWM_API_KEY,
+            "abc123",
+        );
+        assert!(vault(&[8; KEY_BYTES]).open(&secret).is_err());
+    }
+
+    #[test]
+    fn test_vault_rejects_tampering() {
+        let vault = vault(&[7; KEY_BYTES]);
+        let user_id = Uuid::new_v4();
+
+        let mut flipped = sealed(&vault, user_id, OWM_API_KEY, "abc123");
+        flipped.ciphertext[0] ^= 1;
+        assert!(vault.open(&flipped).is_err());
+
+        let mut moved = sealed(&vault, user_id, OWM_API_KEY, "abc123");
+        moved.user_id = Uuid::new_v4();
+        assert!(vault.open(&moved).is_err());
+
+        let mut renamed = sealed(&vault, user_id, OWM_API_KEY, "abc123");
+        renamed.name = "other_key".to_owned();
+        assert!(vault.open(&renamed).is_err());
+    }
+
+    #[test]
+    fn test_vault_needs_a_256_bit_key() {
+        let config = Config {
+            encryption_key: base64::encode([7; 16]),
+        };
+        assert!(Vault::new(&config).is_err());
+    }
+}
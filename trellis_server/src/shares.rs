@@ -0,0 +1,409 @@
+//! Links that show a board to anyone who has them, without logging in. Shared boards can't be
+//! changed through the link, and the owner's secrets never leave the server: weather on a shared
+//! board is fetched here, with the owner's API key, and only for places the board shows. Favicons
+//! for link tiles are likewise only fetched for sites the board links to, and feeds for the feeds
+//! it shows.
+
+use super::auth::{self, Session};
+use super::boards::{self, BoardError};
+use super::favicons::{self, Favicon, Favicons};
+use super::feeds::{self, FeedError, Feeds};
+use super::models;
+use super::schema;
+use super::secrets::Vault;
+use super::weather::{self, Weather, WeatherError};
+use super::DbConn;
+use chrono::{DateTime, Utc};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::State;
+use trellis_core::board::{self, Role};
+use trellis_core::config::{Config, Data};
+use trellis_core::feed::Feed;
+use trellis_core::weather::{Forecast, WeatherReport};
+use trellis_core::{api, links, migrate};
+use uuid::Uuid;
+
+fn link_entry(row: models::ShareLink) -> api::ShareLink {
+    api::ShareLink {
+        id: row.id,
+        label: row.label,
+        created_at: DateTime::from_utc(row.created_at, Utc),
+        last_viewed_at: row.last_viewed_at.map(|at| DateTime::from_utc(at, Utc)),
+    }
+}
+
+/// Lists a board's share links, oldest first.
+fn links(conn: &PgConnection, board: Uuid) -> QueryResult<Vec<api::ShareLink>> {
+    use schema::share_links::dsl::*;
+
+    let rows = share_links
+        .filter(board_id.eq(board))
+        .order((created_at.asc(), id.asc()))
+        .load::<models::ShareLink>(conn)?;
+    Ok(rows.into_iter().map(link_entry).collect())
+}
+
+/// Makes a new share link for a board. Only a hash of its token is stored, like a magic link's.
+fn create_link(
+    conn: &PgConnection,
+    board: Uuid,
+    label: String,
+) -> QueryResult<api::CreatedShareLink> {
+    let token = auth::new_token();
+    let row = diesel::insert_into(schema::share_links::table)
+        .values(&models::NewShareLink {
+            board_id: board,
+            token_hash: auth::hash_token(&token),
+            label,
+        })
+        .get_result::<models::ShareLink>(conn)?;
+    Ok(api::CreatedShareLink {
+        link: link_entry(row),
+        token,
+    })
+}
+
+/// Finds the board that a share link is for.
+pub fn find_board(conn: &PgConnection, token: &str) -> QueryResult<Option<models::Board>> {
+    schema::share_links::table
+        .inner_join(schema::boards::table)
+        .filter(schema::share_links::token_hash.eq(auth::hash_token(token)))
+        .select(schema::boards::all_columns)
+        .first::<models::Board>(conn)
+        .optional()
+}
+
+/// Reads the board that a share link is for, and notes that the link was used.
+fn view(conn: &PgConnection, token: &str) -> anyhow::Result<Option<api::SharedBoard>> {
+    use schema::share_links::dsl::*;
+
+    let board = match find_board(conn, token)? {
+        Some(board) => board,
+        None => return Ok(None),
+    };
+    diesel::update(share_links.filter(token_hash.eq(auth::hash_token(token))))
+        .set(last_viewed_at.eq(Utc::now().naive_utc()))
+        .execute(conn)?;
+    // Going through `Config` drops anything in the stored document that it doesn't know about.
+    Ok(Some(api::SharedBoard {
+        name: board.name,
+        snapshot: api::Snapshot {
+            revision: board.revision,
+            config: migrate::from_value(board.data)?,
+        },
+    }))
+}
+
+/// A reminder of what a link is for. Links don't need one, but otherwise it's held to the same
+/// rules as board names.
+fn normalize_label(label: &str) -> Result<String, board::NameError> {
+    match board::normalize_name(label) {
+        Err(board::NameError::Empty) => Ok(String::new()),
+        res => res,
+    }
+}
+
+fn internal_error(err: impl std::fmt::Display) -> status::Custom<&'static str> {
+    log::error!("{}", err);
+    status::Custom(Status::InternalServerError, "Internal Server Error")
+}
+
+/// Lists the links that share one of the user's boards. Only the owner can see them.
+#[get("/boards/<slug>/shares")]
+pub async fn list(
+    session: Session,
+    db: DbConn,
+    slug: String,
+) -> Result<Json<Vec<api::ShareLink>>, status::Custom<&'static str>> {
+    let board_id = boards::lookup(&db, session.user.id, slug, Role::Owner)
+        .await?
+        .board
+        .id;
+    db.run(move |c| links(c, board_id))
+        .await
+        .map(Json)
+        .map_err(internal_error)
+}
+
+/// Shares one of the user's boards with a new link.
+#[post("/boards/<slug>/shares", data = "<req>")]
+pub async fn create(
+    session: Session,
+    db: DbConn,
+    slug: String,
+    req: Json<api::NewShareLink>,
+) -> Result<Json<api::CreatedShareLink>, BoardError> {
+    let label = normalize_label(&req.label).map_err(BoardError::invalid)?;
+    let board_id = boards::lookup(&db, session.user.id, slug, Role::Owner)
+        .await?
+        .board
+        .id;
+    db.run(move |c| create_link(c, board_id, label))
+        .await
+        .map(Json)
+        .map_err(|err| BoardError::internal(err.into()))
+}
+
+/// Stops a link from showing the board. Returns the links that are left.
+#[delete("/boards/<slug>/shares/<link_id>")]
+pub async fn revoke(
+    session: Session,
+    db: DbConn,
+    slug: String,
+    link_id: Uuid,
+) -> Result<Option<Json<Vec<api::ShareLink>>>, status::Custom<&'static str>> {
+    let board = boards::lookup(&db, session.user.id, slug, Role::Owner)
+        .await?
+        .board
+        .id;
+    let res = db
+        .run(move |c| {
+            use schema::share_links::dsl::*;
+
+            let deleted =
+                diesel::delete(share_links.find(link_id).filter(board_id.eq(board))).execute(c)?;
+            if deleted == 0 {
+                return Ok(None);
+            }
+            Ok::<_, anyhow::Error>(Some(links(c, board)?))
+        })
+        .await;
+    res.map(|links| links.map(Json)).map_err(internal_error)
+}
+
+/// Shows the board that a share link is for.
+#[get("/shared/<token>")]
+pub async fn show(
+    db: DbConn,
+    token: String,
+) -> Result<Option<Json<api::SharedBoard>>, status::Custom<&'static str>> {
+    db.run(move |c| view(c, &token))
+        .await
+        .map(|shared| shared.map(Json))
+        .map_err(internal_error)
+}
+
+/// Finds who owns a shared board, if it has a weather tile for the place. Anything else would let
+/// share links fetch weather for any place with the owner's API key.
+async fn weather_owner(
+    db: &DbConn,
+    token: String,
+    provider: &str,
+    location_id: &str,
+) -> Result<Option<Uuid>, WeatherError> {
+    let board = db
+        .run(move |c| find_board(c, &token))
+        .await
+        .map_err(|err| {
+            log::error!("{}", err);
+            WeatherError::Internal("Internal Server Error")
+        })?;
+    let board = match board {
+        Some(board) => board,
+        None => return Ok(None),
+    };
+    let config = migrate::from_value(board.data).map_err(|err| {
+        log::error!("{}", err);
+        WeatherError::Internal("Internal Server Error")
+    })?;
+    Ok(if shows_weather(&config, provider, location_id) {
+        Some(board.user_id)
+    } else {
+        None
+    })
+}
+
+/// Whether the board has a weather tile for the place.
+fn shows_weather(config: &Config, provider: &str, location_id: &str) -> bool {
+    config.tiles.iter().any(|tile| match &tile.data {
+        Data::Weather {
+            provider: p,
+            location_id: l,
+            ..
+        } => p.to_string() == provider && l == location_id,
+        _ => false,
+    })
+}
+
+/// Reports the current weather for a weather tile on a shared board.
+#[get("/shared/<token>/weather?<provider>&<location_id>")]
+pub async fn current_weather(
+    db: DbConn,
+    vault: &State<Vault>,
+    weather: &State<Weather>,
+    token: String,
+    provider: &str,
+    location_id: &str,
+) -> Result<Option<Json<WeatherReport>>, WeatherError> {
+    let owner = match weather_owner(&db, token, provider, location_id).await? {
+        Some(owner) => owner,
+        None => return Ok(None),
+    };
+    let report = weather::report(&db, vault, weather, owner, provider, location_id).await?;
+    Ok(Some(Json(report)))
+}
+
+/// Forecasts the weather for a weather tile on a shared board.
+#[get("/shared/<token>/weather/forecast?<provider>&<location_id>")]
+pub async fn forecast_weather(
+    db: DbConn,
+    vault: &State<Vault>,
+    weather: &State<Weather>,
+    token: String,
+    provider: &str,
+    location_id: &str,
+) -> Result<Option<Json<Forecast>>, WeatherError> {
+    let owner = match weather_owner(&db, token, provider, location_id).await? {
+        Some(owner) => owner,
+        None => return Ok(None),
+    };
+    let forecast = weather::upcoming(&db, vault, weather, owner, provider, location_id).await?;
+    Ok(Some(Json(forecast)))
+}
+
+/// Whether the board has a link to the host that shows its icon.
+fn shows_icon(config: &Config, host: &str) -> bool {
+    config.tiles.iter().any(|tile| match &tile.data {
+        Data::Links {
+            links: tile_links, ..
+        } => tile_links
+            .iter()
+            .any(|link| link.icon && links::host(&link.url).as_deref() == Some(host)),
+        _ => false,
+    })
+}
+
+/// Shows a site's icon for a link on a shared board.
+#[get("/shared/<token>/favicons/<host>")]
+pub async fn favicon(
+    db: DbConn,
+    favicons: &State<Favicons>,
+    token: String,
+    host: &str,
+) -> Result<Option<Favicon>, status::Custom<&'static str>> {
+    let board = db
+        .run(move |c| find_board(c, &token))
+        .await
+        .map_err(internal_error)?;
+    let config = match board {
+        Some(board) => migrate::from_value(board.data).map_err(internal_error)?,
+        None => return Ok(None),
+    };
+    let host = host.to_lowercase();
+    if !shows_icon(&config, &host) {
+        return Ok(None);
+    }
+    Ok(favicons::favicon(favicons, &host).await)
+}
+
+/// Whether the board has a feed tile for the feed.
+fn shows_feed(config: &Config, url: &str) -> bool {
+    config.tiles.iter().any(|tile| match &tile.data {
+        Data::Feed { url: u, .. } => u == url,
+        _ => false,
+    })
+}
+
+/// Shows the latest entries of a feed on a shared board.
+#[get("/shared/<token>/feed?<url>&<max_items>")]
+pub async fn feed(
+    db: DbConn,
+    feeds: &State<Feeds>,
+    token: String,
+    url: &str,
+    max_items: Option<u32>,
+) -> Result<Option<Json<Feed>>, FeedError> {
+    let board = db
+        .run(move |c| find_board(c, &token))
+        .await
+        .map_err(|err| {
+            log::error!("{}", err);
+            FeedError::Internal("Internal Server Error")
+        })?;
+    let board = match board {
+        Some(board) => board,
+        None => return Ok(None),
+    };
+    let config = migrate::from_value(board.data).map_err(|err| {
+        log::error!("{}", err);
+        FeedError::Internal("Internal Server Error")
+    })?;
+    if !shows_feed(&config, url) {
+        return Ok(None);
+    }
+    feeds::latest(feeds, url, max_items)
+        .await
+        .map(|feed| Some(Json(feed)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use trellis_core::links::Link;
+    use trellis_core::weather::Provider;
+
+    #[test]
+    fn test_shows_weather_only_for_tiles_on_the_board() {
+        let mut config = Config::default();
+        for tile in config.tiles.iter_mut() {
+            if let Data::Weather {
+                provider,
+                location_id,
+                ..
+            } = &mut tile.data
+            {
+                *provider = Provider::OpenMeteo;
+                *location_id = "45.52,-122.68".to_owned();
+            }
+        }
+        let open_meteo = Provider::OpenMeteo.to_string();
+        let owm = Provider::OpenWeatherMap.to_string();
+
+        assert!(shows_weather(&config, &open_meteo, "45.52,-122.68"));
+        assert!(!shows_weather(&config, &open_meteo, "51.51,-0.13"));
+        assert!(!shows_weather(&config, &owm, "45.52,-122.68"));
+        assert!(!shows_weather(&config, &open_meteo, ""));
+    }
+
+    #[test]
+    fn test_shows_icons_only_for_links_on_the_board() {
+        let mut config = Config::default();
+        config.tiles[0].data = Data::Links {
+            title: "".to_owned(),
+            links: vec![
+                Link {
+                    label: "".to_owned(),
+                    url: "https://Example.com/page".to_owned(),
+                    icon: true,
+                },
+                Link {
+                    label: "".to_owned(),
+                    url: "https://example.org/".to_owned(),
+                    icon: false,
+                },
+            ],
+            new_tab: false,
+        };
+
+        assert!(shows_icon(&config, "example.com"));
+        assert!(!shows_icon(&config, "example.org"));
+        assert!(!shows_icon(&config, "example.net"));
+    }
+
+    #[test]
+    fn test_shows_feeds_only_for_tiles_on_the_board() {
+        let mut config = Config::default();
+        config.tiles[0].data = Data::Feed {
+            url: "https://blog.example.com/feed.xml".to_owned(),
+            max_items: 5,
+        };
+
+        assert!(shows_feed(&config, "https://blog.example.com/feed.xml"));
+        assert!(!shows_feed(&config, "https://blog.example.com/atom.xml"));
+        assert!(!shows_feed(&config, ""));
+    }
+}
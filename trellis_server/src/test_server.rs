@@ -0,0 +1,51 @@
+//! A stand-in for the other sites that the server fetches things from, for tests.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread;
+use url::Url;
+
+/// Serves canned HTTP responses on a local port, one per connection, in order. Each response is a
+/// status, extra headers, and a body of type `content_type`. Returns the base URL to send requests
+/// to, and a handle that yields each request once every response has been sent: its request line
+/// as it was sent, followed by its headers in lower case.
+pub fn stub_server(
+    content_type: &'static str,
+    responses: &[(&'static str, &'static str, &'static str)],
+) -> (Url, thread::JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+    let responses = responses.to_vec();
+    let handle = thread::spawn(move || {
+        let mut requests = Vec::new();
+        for (status, headers, body) in responses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request = String::new();
+            reader.read_line(&mut request).unwrap();
+            // Requests have no body, so the headers are the rest.
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                request.push_str(&line.to_lowercase());
+            }
+            let mut stream = reader.into_inner();
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\n{}Content-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                headers,
+                content_type,
+                body.len(),
+                body
+            )
+            .unwrap();
+            requests.push(request);
+        }
+        requests
+    });
+    (url, handle)
+}
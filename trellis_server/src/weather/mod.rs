@@ -0,0 +1,420 @@
+use super::auth::Session;
+use super::cache::Cache;
+use super::secrets::{self, Vault};
+use super::DbConn;
+use chrono::Utc;
+use rocket::fairing::AdHoc;
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::time::Duration;
+use thiserror::Error;
+use trellis_core::api;
+use trellis_core::weather::{Forecast, Place, Provider, WeatherReport};
+use url::Url;
+use uuid::Uuid;
+
+mod nws;
+mod open_meteo;
+mod owm;
+
+#[cfg(test)]
+mod tests;
+
+pub use nws::Nws;
+pub use open_meteo::OpenMeteo;
+pub use owm::OpenWeatherMap;
+
+/// Some services (the NWS in particular) refuse requests that don't say who is asking.
+pub const USER_AGENT: &str = concat!(
+    "trellis/",
+    env!("CARGO_PKG_VERSION"),
+    " (https://github.com/metagram-net/trellis)"
+);
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Config {
+    /// The base URL of each service's API. Tests point these at a local stub server.
+    pub owm_url: Url,
+    pub open_meteo_url: Url,
+    /// Open-Meteo's geocoding API, which has its own host.
+    pub open_meteo_geocoding_url: Url,
+    pub nws_url: Url,
+    /// How long to reuse a report before asking upstream again.
+    pub cache_ttl_secs: u64,
+}
+
+/// Something that can report the weather. Each `Provider` has one.
+#[rocket::async_trait]
+pub trait Backend: Send + Sync {
+    /// Fetches the current conditions. `api_key` is only given to providers that need one.
+    async fn current(
+        &self,
+        client: &reqwest::Client,
+        location_id: &str,
+        api_key: Option<&str>,
+    ) -> Result<WeatherReport, FetchError>;
+
+    /// Fetches the hourly and daily forecast. Hours that have already passed may be included.
+    async fn forecast(
+        &self,
+        client: &reqwest::Client,
+        location_id: &str,
+        api_key: Option<&str>,
+    ) -> Result<Forecast, FetchError>;
+}
+
+/// Something that can find places by name.
+#[rocket::async_trait]
+pub trait Geocoder: Send + Sync {
+    /// Finds the places matching a search like "Portland" or "Portland, Oregon, US", best match
+    /// first.
+    async fn search(
+        &self,
+        client: &reqwest::Client,
+        query: &str,
+        api_key: Option<&str>,
+    ) -> Result<Vec<Place>, FetchError>;
+}
+
+/// How many places each cache remembers at once. The oldest is forgotten to make room.
+const MAX_CACHE_ENTRIES: usize = 1000;
+
+/// Fetches weather reports and forecasts, and caches them.
+pub struct Weather {
+    client: reqwest::Client,
+    owm: OpenWeatherMap,
+    open_meteo: OpenMeteo,
+    nws: Nws,
+    /// Recent responses by provider and location, so that several tiles (or several users)
+    /// showing the same place don't each ask upstream.
+    reports: Cache<(Provider, String), WeatherReport>,
+    forecasts: Cache<(Provider, String), Forecast>,
+}
+
+impl Weather {
+    pub fn new(config: Config) -> Self {
+        let ttl = Duration::from_secs(config.cache_ttl_secs);
+        Self {
+            client: reqwest::Client::builder()
+                .user_agent(USER_AGENT)
+                .build()
+                .expect("could not build HTTP client"),
+            owm: OpenWeatherMap {
+                base_url: config.owm_url,
+            },
+            open_meteo: OpenMeteo {
+                base_url: config.open_meteo_url,
+                geocoding_url: config.open_meteo_geocoding_url,
+            },
+            nws: Nws {
+                base_url: config.nws_url,
+            },
+            reports: Cache::new(ttl, MAX_CACHE_ENTRIES),
+            forecasts: Cache::new(ttl, MAX_CACHE_ENTRIES),
+        }
+    }
+
+    fn backend(&self, provider: Provider) -> &dyn Backend {
+        match provider {
+            Provider::OpenWeatherMap => &self.owm,
+            Provider::OpenMeteo => &self.open_meteo,
+            Provider::Nws => &self.nws,
+        }
+    }
+
+    /// OpenWeatherMap searches with the same key as its weather. The others have no search of
+    /// their own, so they use Open-Meteo's, which needs no key.
+    fn geocoder(&self, provider: Provider) -> &dyn Geocoder {
+        match provider {
+            Provider::OpenWeatherMap => &self.owm,
+            Provider::OpenMeteo | Provider::Nws => &self.open_meteo,
+        }
+    }
+}
+
+/// Loads the `weather` configuration section into managed state.
+pub fn fairing() -> AdHoc {
+    AdHoc::try_on_ignite("Weather", |rocket| async {
+        match rocket.figment().extract_inner::<Config>("weather") {
+            Ok(config) => Ok(rocket.manage(Weather::new(config))),
+            Err(err) => {
+                log::error!("invalid weather config: {}", err);
+                Err(rocket)
+            }
+        }
+    })
+}
+
+#[derive(Error, Debug)]
+pub enum FetchError {
+    #[error("unknown location")]
+    NotFound,
+    #[error("invalid location: {0}")]
+    Location(String),
+    #[error("missing API key")]
+    MissingApiKey,
+    #[error("weather service responded with {0}")]
+    Status(reqwest::StatusCode),
+    #[error("unexpected response from weather service: {0}")]
+    Response(String),
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+}
+
+/// Builds the URL of an API endpoint under a service's base URL.
+fn endpoint(base_url: &Url, segments: &[&str]) -> Url {
+    let mut url = base_url.clone();
+    url.path_segments_mut()
+        .expect("cannot be base")
+        .pop_if_empty()
+        .extend(segments);
+    url
+}
+
+/// Sends a GET request and reads the JSON response.
+async fn get_json<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    url: Url,
+) -> Result<T, FetchError> {
+    // URLs can have API keys in them, so keep them out of any errors.
+    let res = client
+        .get(url)
+        .send()
+        .await
+        .map_err(reqwest::Error::without_url)?;
+    match res.status() {
+        reqwest::StatusCode::NOT_FOUND => return Err(FetchError::NotFound),
+        s if !s.is_success() => return Err(FetchError::Status(s)),
+        _ => {}
+    }
+    Ok(res.json::<T>().await.map_err(reqwest::Error::without_url)?)
+}
+
+/// Reads a location written as `latitude,longitude`.
+fn coordinates(location_id: &str) -> Result<(f64, f64), FetchError> {
+    let invalid = || FetchError::Location(location_id.to_owned());
+    let (lat, lon) = location_id.split_once(',').ok_or_else(invalid)?;
+    let lat: f64 = lat.trim().parse().map_err(|_| invalid())?;
+    let lon: f64 = lon.trim().parse().map_err(|_| invalid())?;
+    if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+        return Err(invalid());
+    }
+    Ok((lat, lon))
+}
+
+/// Names a place by joining the non-empty parts of its name, like its city and country. Parts
+/// that repeat the one before, like the city-state of Berlin in Berlin, are left out.
+fn place_name(parts: &[Option<&str>]) -> String {
+    let mut name: Vec<&str> = Vec::new();
+    for part in parts.iter().flatten() {
+        let part = part.trim();
+        if !part.is_empty() && name.last() != Some(&part) {
+            name.push(part);
+        }
+    }
+    name.join(", ")
+}
+
+#[derive(Responder)]
+pub enum WeatherError {
+    #[response(status = 404)]
+    NotFound(Json<api::Error>),
+    /// Weather can't be fetched until the user changes something, like adding an API key.
+    #[response(status = 422)]
+    Unavailable(Json<api::Error>),
+    #[response(status = 502)]
+    Upstream(Json<api::Error>),
+    #[response(status = 500)]
+    Internal(&'static str),
+}
+
+fn error_body(message: String) -> Json<api::Error> {
+    Json(api::Error {
+        message,
+        problems: Vec::new(),
+    })
+}
+
+fn parse_provider(provider: &str) -> Result<Provider, WeatherError> {
+    provider
+        .parse()
+        .map_err(|err| WeatherError::Unavailable(error_body(err)))
+}
+
+/// Checks the query parameters shared by the weather endpoints.
+fn parse_query(provider: &str, location_id: &str) -> Result<Provider, WeatherError> {
+    let provider = parse_provider(provider)?;
+    if location_id.is_empty() {
+        return Err(WeatherError::Unavailable(error_body(
+            "Missing location ID".to_owned(),
+        )));
+    }
+    Ok(provider)
+}
+
+/// Loads the user's API key for the provider, if it needs one. A user without a key gets an error
+/// here, before the cache is checked, so they can't see reports fetched with someone else's key.
+async fn api_key(
+    db: &DbConn,
+    vault: &Vault,
+    uid: Uuid,
+    provider: Provider,
+) -> Result<Option<String>, WeatherError> {
+    if !provider.needs_api_key() {
+        return Ok(None);
+    }
+    let vault = vault.clone();
+    let key = db
+        .run(move |c| secrets::get(c, &vault, uid, secrets::OWM_API_KEY))
+        .await
+        .map_err(|err| {
+            log::error!("{}", err);
+            WeatherError::Internal("Internal Server Error")
+        })?;
+    match key {
+        Some(key) => Ok(Some(key)),
+        None => Err(WeatherError::Unavailable(error_body(format!(
+            "Missing {} API key",
+            provider.name()
+        )))),
+    }
+}
+
+fn fetch_error(provider: Provider, location_id: &str, err: FetchError) -> WeatherError {
+    match err {
+        FetchError::NotFound => {
+            WeatherError::NotFound(error_body(format!("Unknown location: {}", location_id)))
+        }
+        FetchError::MissingApiKey => {
+            WeatherError::Unavailable(error_body(format!("Missing {} API key", provider.name())))
+        }
+        FetchError::Location(_) => WeatherError::Unavailable(error_body(err.to_string())),
+        err => {
+            log::warn!("could not fetch weather: {}", err);
+            WeatherError::Upstream(error_body(err.to_string()))
+        }
+    }
+}
+
+/// Reports the current weather at a location, from the cache if it's recent. OpenWeatherMap is
+/// called with the API key of the user `uid`.
+pub async fn report(
+    db: &DbConn,
+    vault: &Vault,
+    weather: &Weather,
+    uid: Uuid,
+    provider: &str,
+    location_id: &str,
+) -> Result<WeatherReport, WeatherError> {
+    let provider = parse_query(provider, location_id)?;
+    let api_key = api_key(db, vault, uid, provider).await?;
+    if let Some(report) = weather.reports.get(&(provider, location_id.to_owned())) {
+        return Ok(report);
+    }
+
+    let report = weather
+        .backend(provider)
+        .current(&weather.client, location_id, api_key.as_deref())
+        .await
+        .map_err(|err| fetch_error(provider, location_id, err))?;
+    weather
+        .reports
+        .put((provider, location_id.to_owned()), report.clone());
+    Ok(report)
+}
+
+/// Forecasts the weather at a location, like `report`.
+pub async fn upcoming(
+    db: &DbConn,
+    vault: &Vault,
+    weather: &Weather,
+    uid: Uuid,
+    provider: &str,
+    location_id: &str,
+) -> Result<Forecast, WeatherError> {
+    let provider = parse_query(provider, location_id)?;
+    let api_key = api_key(db, vault, uid, provider).await?;
+    let forecast = match weather.forecasts.get(&(provider, location_id.to_owned())) {
+        Some(forecast) => forecast,
+        None => {
+            let forecast = weather
+                .backend(provider)
+                .forecast(&weather.client, location_id, api_key.as_deref())
+                .await
+                .map_err(|err| fetch_error(provider, location_id, err))?;
+            weather
+                .forecasts
+                .put((provider, location_id.to_owned()), forecast.clone());
+            forecast
+        }
+    };
+    // The cached forecast may be a few minutes old, so trim it now rather than before caching.
+    Ok(forecast.upcoming(Utc::now()))
+}
+
+/// Reports the current weather at a location. OpenWeatherMap is called with the user's own API
+/// key.
+#[get("/weather?<provider>&<location_id>")]
+pub async fn current(
+    session: Session,
+    db: DbConn,
+    vault: &State<Vault>,
+    weather: &State<Weather>,
+    provider: &str,
+    location_id: &str,
+) -> Result<Json<WeatherReport>, WeatherError> {
+    report(&db, vault, weather, session.user.id, provider, location_id)
+        .await
+        .map(Json)
+}
+
+/// Forecasts the weather at a location for the next day by the hour, and for the next week by the
+/// day.
+#[get("/weather/forecast?<provider>&<location_id>")]
+pub async fn forecast(
+    session: Session,
+    db: DbConn,
+    vault: &State<Vault>,
+    weather: &State<Weather>,
+    provider: &str,
+    location_id: &str,
+) -> Result<Json<Forecast>, WeatherError> {
+    upcoming(&db, vault, weather, session.user.id, provider, location_id)
+        .await
+        .map(Json)
+}
+
+/// Searches for places to report the weather for. Searching for `latitude,longitude` finds exactly
+/// that place, without asking any service.
+#[get("/weather/locations?<provider>&<query>")]
+pub async fn locations(
+    session: Session,
+    db: DbConn,
+    vault: &State<Vault>,
+    weather: &State<Weather>,
+    provider: &str,
+    query: &str,
+) -> Result<Json<Vec<Place>>, WeatherError> {
+    let provider = parse_provider(provider)?;
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok(Json(Vec::new()));
+    }
+    if let Ok((latitude, longitude)) = coordinates(query) {
+        return Ok(Json(vec![Place {
+            name: format!("{:.4}, {:.4}", latitude, longitude),
+            latitude,
+            longitude,
+        }]));
+    }
+
+    let api_key = api_key(&db, vault, session.user.id, provider).await?;
+    let places = weather
+        .geocoder(provider)
+        .search(&weather.client, query, api_key.as_deref())
+        .await
+        .map_err(|err| fetch_error(provider, query, err))?;
+    Ok(Json(places))
+}
@@ -0,0 +1,289 @@
+use super::{coordinates, endpoint, get_json, Backend, FetchError};
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::Deserialize;
+use trellis_core::weather::{DailyForecast, Forecast, HourlyForecast, WeatherReport};
+use url::Url;
+
+/// The US National Weather Service API, which needs no key. Locations are `latitude,longitude`
+/// pairs inside the United States.
+///
+/// Current conditions take three requests: the forecast grid point for the location, the
+/// observation stations near that point, and then the latest observation from the nearest one.
+/// Forecasts take two: the grid point, and then its forecast.
+pub struct Nws {
+    pub base_url: Url,
+}
+
+/// The API speaks GeoJSON, so everything interesting is in `properties`.
+#[derive(Deserialize)]
+struct Feature<P> {
+    properties: P,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Point {
+    grid_id: String,
+    grid_x: u32,
+    grid_y: u32,
+    relative_location: Feature<RelativeLocation>,
+}
+
+#[derive(Deserialize)]
+struct RelativeLocation {
+    city: String,
+    state: String,
+}
+
+#[derive(Deserialize)]
+struct Stations {
+    /// Sorted nearest first.
+    features: Vec<Feature<Station>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Station {
+    station_identifier: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Observation {
+    timestamp: String,
+    text_description: String,
+    icon: Option<String>,
+    temperature: Measurement,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Measurement {
+    /// Missing when the station's sensor didn't report.
+    value: Option<f64>,
+    unit_code: String,
+}
+
+#[derive(Deserialize)]
+struct Periods {
+    periods: Vec<Period>,
+}
+
+/// A period of an hourly forecast, or half a day of a daily forecast.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Period {
+    start_time: String,
+    is_daytime: bool,
+    temperature: f64,
+    /// "C" or "F".
+    temperature_unit: String,
+    probability_of_precipitation: Option<Measurement>,
+    short_forecast: String,
+}
+
+impl Period {
+    fn start_time(&self) -> Result<DateTime<FixedOffset>, FetchError> {
+        parse_time(&self.start_time)
+    }
+
+    fn celsius(&self) -> Result<f64, FetchError> {
+        match self.temperature_unit.as_str() {
+            "C" => Ok(self.temperature),
+            "F" => Ok((self.temperature - 32.0) * 5.0 / 9.0),
+            unit => Err(FetchError::Response(format!(
+                "unexpected temperature unit: {}",
+                unit
+            ))),
+        }
+    }
+}
+
+fn parse_time(time: &str) -> Result<DateTime<FixedOffset>, FetchError> {
+    DateTime::parse_from_rfc3339(time)
+        .map_err(|_| FetchError::Response(format!("invalid time: {}", time)))
+}
+
+fn location_name(place: &RelativeLocation) -> String {
+    format!("{}, {}", place.city, place.state)
+}
+
+fn source_url(lat: f64, lon: f64) -> String {
+    format!(
+        "https://forecast.weather.gov/MapClick.php?lat={:.4}&lon={:.4}",
+        lat, lon
+    )
+}
+
+fn normalize(
+    lat: f64,
+    lon: f64,
+    place: RelativeLocation,
+    obs: Observation,
+) -> Result<WeatherReport, FetchError> {
+    if obs.temperature.unit_code != "wmoUnit:degC" {
+        return Err(FetchError::Response(format!(
+            "unexpected temperature unit: {}",
+            obs.temperature.unit_code
+        )));
+    }
+    let temperature = obs
+        .temperature
+        .value
+        .ok_or_else(|| FetchError::Response("no temperature".to_owned()))?;
+    let observed_at = parse_time(&obs.timestamp)?;
+    Ok(WeatherReport {
+        location_name: location_name(&place),
+        description: obs.text_description.to_lowercase(),
+        temperature,
+        icon_url: obs.icon,
+        observed_at: observed_at.with_timezone(&Utc),
+        source_name: "National Weather Service".to_owned(),
+        source_url: Some(source_url(lat, lon)),
+    })
+}
+
+fn normalize_forecast(
+    lat: f64,
+    lon: f64,
+    place: RelativeLocation,
+    hours: Periods,
+    half_days: Periods,
+) -> Result<Forecast, FetchError> {
+    let hourly = hours
+        .periods
+        .iter()
+        .map(|p| {
+            Ok(HourlyForecast {
+                time: p.start_time()?.with_timezone(&Utc),
+                temperature: p.celsius()?,
+                precipitation_chance: p
+                    .probability_of_precipitation
+                    .as_ref()
+                    .and_then(|m| m.value),
+                precipitation: None,
+            })
+        })
+        .collect::<Result<_, FetchError>>()?;
+
+    // Each day is split into a daytime period with the high and a nighttime period with the low.
+    // A day that is missing either half, like when the forecast starts in the evening, is skipped.
+    let mut daily = Vec::new();
+    let mut daytime: Option<&Period> = None;
+    for period in half_days.periods.iter() {
+        if period.is_daytime {
+            daytime = Some(period);
+            continue;
+        }
+        if let Some(day) = daytime.take() {
+            let date = day.start_time()?.naive_local().date();
+            if date == period.start_time()?.naive_local().date() {
+                daily.push(DailyForecast {
+                    date,
+                    description: day.short_forecast.to_lowercase(),
+                    high: day.celsius()?,
+                    low: period.celsius()?,
+                });
+            }
+        }
+    }
+
+    Ok(Forecast {
+        location_name: location_name(&place),
+        hourly,
+        daily,
+        source_name: "National Weather Service".to_owned(),
+        source_url: Some(source_url(lat, lon)),
+    })
+}
+
+impl Nws {
+    async fn point(
+        &self,
+        client: &reqwest::Client,
+        lat: f64,
+        lon: f64,
+    ) -> Result<Point, FetchError> {
+        // The API redirects anything more precise than four decimal places.
+        let point = format!("{:.4},{:.4}", lat, lon);
+        let url = endpoint(&self.base_url, &["points", &point]);
+        Ok(get_json::<Feature<Point>>(client, url).await?.properties)
+    }
+
+    /// Builds the URL of an endpoint under a forecast grid point.
+    fn grid_endpoint(&self, point: &Point, segments: &[&str]) -> Url {
+        let grid_xy = format!("{},{}", point.grid_x, point.grid_y);
+        let mut path: Vec<&str> = vec!["gridpoints", &point.grid_id, &grid_xy];
+        path.extend_from_slice(segments);
+        endpoint(&self.base_url, &path)
+    }
+
+    /// Fetches a forecast for a grid point, in metric units.
+    async fn periods(
+        &self,
+        client: &reqwest::Client,
+        point: &Point,
+        segments: &[&str],
+    ) -> Result<Periods, FetchError> {
+        let mut url = self.grid_endpoint(point, segments);
+        url.query_pairs_mut().append_pair("units", "si");
+        Ok(get_json::<Feature<Periods>>(client, url).await?.properties)
+    }
+}
+
+#[rocket::async_trait]
+impl Backend for Nws {
+    async fn current(
+        &self,
+        client: &reqwest::Client,
+        location_id: &str,
+        _api_key: Option<&str>,
+    ) -> Result<WeatherReport, FetchError> {
+        let (lat, lon) = coordinates(location_id)?;
+        let point = self.point(client, lat, lon).await?;
+
+        let url = self.grid_endpoint(&point, &["stations"]);
+        let stations = get_json::<Stations>(client, url).await?;
+        let station = stations
+            .features
+            .into_iter()
+            .next()
+            .ok_or_else(|| FetchError::Response("no observation stations".to_owned()))?
+            .properties;
+
+        let url = endpoint(
+            &self.base_url,
+            &[
+                "stations",
+                &station.station_identifier,
+                "observations",
+                "latest",
+            ],
+        );
+        let obs = get_json::<Feature<Observation>>(client, url)
+            .await?
+            .properties;
+        normalize(lat, lon, point.relative_location.properties, obs)
+    }
+
+    async fn forecast(
+        &self,
+        client: &reqwest::Client,
+        location_id: &str,
+        _api_key: Option<&str>,
+    ) -> Result<Forecast, FetchError> {
+        let (lat, lon) = coordinates(location_id)?;
+        let point = self.point(client, lat, lon).await?;
+        let hours = self
+            .periods(client, &point, &["forecast", "hourly"])
+            .await?;
+        let half_days = self.periods(client, &point, &["forecast"]).await?;
+        normalize_forecast(
+            lat,
+            lon,
+            point.relative_location.properties,
+            hours,
+            half_days,
+        )
+    }
+}
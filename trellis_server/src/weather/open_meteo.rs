@@ -0,0 +1,267 @@
+use super::{coordinates, endpoint, get_json, place_name, Backend, FetchError, Geocoder};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, Utc};
+use serde::Deserialize;
+use trellis_core::weather::{DailyForecast, Forecast, HourlyForecast, Place, WeatherReport};
+use url::Url;
+
+/// Open-Meteo's forecast and geocoding APIs, which need no key. Locations are
+/// `latitude,longitude` pairs.
+pub struct OpenMeteo {
+    pub base_url: Url,
+    pub geocoding_url: Url,
+}
+
+#[derive(Deserialize)]
+struct ForecastResponse {
+    current_weather: CurrentWeather,
+}
+
+#[derive(Deserialize)]
+struct CurrentWeather {
+    temperature: f64,
+    weathercode: u32,
+    /// An ISO 8601 time in UTC, without the seconds or the offset.
+    time: String,
+}
+
+/// Open-Meteo returns each series as a separate array, all the same length as `time`.
+#[derive(Deserialize)]
+struct HourlyForecastResponse {
+    utc_offset_seconds: i64,
+    hourly: HourlySeries,
+    daily: DailySeries,
+}
+
+#[derive(Deserialize)]
+struct HourlySeries {
+    /// Local times, in the same format as `CurrentWeather::time`.
+    time: Vec<String>,
+    temperature_2m: Vec<f64>,
+    /// In millimeters.
+    precipitation: Vec<f64>,
+}
+
+#[derive(Deserialize)]
+struct DailySeries {
+    time: Vec<NaiveDate>,
+    weathercode: Vec<u32>,
+    temperature_2m_max: Vec<f64>,
+    temperature_2m_min: Vec<f64>,
+}
+
+#[derive(Deserialize)]
+struct GeocodingResponse {
+    /// Missing entirely when nothing matched.
+    #[serde(default)]
+    results: Vec<GeocodingResult>,
+}
+
+#[derive(Deserialize)]
+struct GeocodingResult {
+    name: String,
+    latitude: f64,
+    longitude: f64,
+    /// The state, province, or similar.
+    admin1: Option<String>,
+    country: Option<String>,
+    country_code: Option<String>,
+}
+
+impl GeocodingResult {
+    /// Whether every part of a search after the place's name, like the "Oregon" and "US" of
+    /// "Portland, Oregon, US", names the region or country the place is in.
+    fn is_in(&self, regions: &[&str]) -> bool {
+        let known = [&self.admin1, &self.country, &self.country_code];
+        regions.iter().all(|region| {
+            known
+                .iter()
+                .filter_map(|k| k.as_deref())
+                .any(|k| k.eq_ignore_ascii_case(region))
+        })
+    }
+}
+
+const SOURCE_URL: &str = "https://open-meteo.com/";
+
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M";
+
+fn parse_time(time: &str) -> Result<NaiveDateTime, FetchError> {
+    NaiveDateTime::parse_from_str(time, TIME_FORMAT)
+        .map_err(|_| FetchError::Response(format!("invalid time: {}", time)))
+}
+
+fn location_name(lat: f64, lon: f64) -> String {
+    // Open-Meteo doesn't name places, so this is the best there is.
+    format!("{:.2}, {:.2}", lat, lon)
+}
+
+/// Describes a WMO weather interpretation code, as used by Open-Meteo.
+fn describe(code: u32) -> &'static str {
+    match code {
+        0 => "clear sky",
+        1 => "mainly clear",
+        2 => "partly cloudy",
+        3 => "overcast",
+        45 | 48 => "fog",
+        51 => "light drizzle",
+        53 => "drizzle",
+        55 => "dense drizzle",
+        56 | 57 => "freezing drizzle",
+        61 => "light rain",
+        63 => "rain",
+        65 => "heavy rain",
+        66 | 67 => "freezing rain",
+        71 => "light snow",
+        73 => "snow",
+        75 => "heavy snow",
+        77 => "snow grains",
+        80 => "light rain showers",
+        81 => "rain showers",
+        82 => "violent rain showers",
+        85 => "light snow showers",
+        86 => "heavy snow showers",
+        95 => "thunderstorm",
+        96 | 99 => "thunderstorm with hail",
+        _ => "unknown",
+    }
+}
+
+fn normalize(lat: f64, lon: f64, res: ForecastResponse) -> Result<WeatherReport, FetchError> {
+    let current = res.current_weather;
+    let observed_at = parse_time(&current.time)?;
+    Ok(WeatherReport {
+        location_name: location_name(lat, lon),
+        description: describe(current.weathercode).to_owned(),
+        temperature: current.temperature,
+        icon_url: None,
+        observed_at: DateTime::from_utc(observed_at, Utc),
+        source_name: "Open-Meteo".to_owned(),
+        source_url: Some(SOURCE_URL.to_owned()),
+    })
+}
+
+fn normalize_forecast(
+    lat: f64,
+    lon: f64,
+    res: HourlyForecastResponse,
+) -> Result<Forecast, FetchError> {
+    let offset = Duration::seconds(res.utc_offset_seconds);
+    let hours = res.hourly;
+    if hours.temperature_2m.len() != hours.time.len()
+        || hours.precipitation.len() != hours.time.len()
+    {
+        return Err(FetchError::Response("mismatched hourly series".to_owned()));
+    }
+    let hourly = hours
+        .time
+        .iter()
+        .zip(hours.temperature_2m.iter().zip(hours.precipitation.iter()))
+        .map(|(time, (temperature, precipitation))| {
+            Ok(HourlyForecast {
+                time: DateTime::from_utc(parse_time(time)? - offset, Utc),
+                temperature: *temperature,
+                precipitation_chance: None,
+                precipitation: Some(*precipitation),
+            })
+        })
+        .collect::<Result<_, FetchError>>()?;
+
+    let days = res.daily;
+    if days.weathercode.len() != days.time.len()
+        || days.temperature_2m_max.len() != days.time.len()
+        || days.temperature_2m_min.len() != days.time.len()
+    {
+        return Err(FetchError::Response("mismatched daily series".to_owned()));
+    }
+    let daily = (0..days.time.len())
+        .map(|i| DailyForecast {
+            date: days.time[i],
+            description: describe(days.weathercode[i]).to_owned(),
+            high: days.temperature_2m_max[i],
+            low: days.temperature_2m_min[i],
+        })
+        .collect();
+
+    Ok(Forecast {
+        location_name: location_name(lat, lon),
+        hourly,
+        daily,
+        source_name: "Open-Meteo".to_owned(),
+        source_url: Some(SOURCE_URL.to_owned()),
+    })
+}
+
+#[rocket::async_trait]
+impl Backend for OpenMeteo {
+    async fn current(
+        &self,
+        client: &reqwest::Client,
+        location_id: &str,
+        _api_key: Option<&str>,
+    ) -> Result<WeatherReport, FetchError> {
+        let (lat, lon) = coordinates(location_id)?;
+        let mut url = endpoint(&self.base_url, &["v1", "forecast"]);
+        url.query_pairs_mut()
+            .append_pair("latitude", &lat.to_string())
+            .append_pair("longitude", &lon.to_string())
+            .append_pair("current_weather", "true")
+            .append_pair("timezone", "UTC");
+        let body = get_json::<ForecastResponse>(client, url).await?;
+        normalize(lat, lon, body)
+    }
+
+    async fn forecast(
+        &self,
+        client: &reqwest::Client,
+        location_id: &str,
+        _api_key: Option<&str>,
+    ) -> Result<Forecast, FetchError> {
+        let (lat, lon) = coordinates(location_id)?;
+        let mut url = endpoint(&self.base_url, &["v1", "forecast"]);
+        url.query_pairs_mut()
+            .append_pair("latitude", &lat.to_string())
+            .append_pair("longitude", &lon.to_string())
+            .append_pair("hourly", "temperature_2m,precipitation")
+            .append_pair("daily", "weathercode,temperature_2m_max,temperature_2m_min")
+            // Days should start at midnight where the location is, not in UTC.
+            .append_pair("timezone", "auto");
+        let body = get_json::<HourlyForecastResponse>(client, url).await?;
+        normalize_forecast(lat, lon, body)
+    }
+}
+
+#[rocket::async_trait]
+impl Geocoder for OpenMeteo {
+    async fn search(
+        &self,
+        client: &reqwest::Client,
+        query: &str,
+        _api_key: Option<&str>,
+    ) -> Result<Vec<Place>, FetchError> {
+        // The API only searches names, so narrow down the results by region here instead.
+        let mut parts = query.split(',').map(str::trim);
+        let name = parts.next().unwrap_or_default();
+        let regions: Vec<&str> = parts.filter(|p| !p.is_empty()).collect();
+
+        let mut url = endpoint(&self.geocoding_url, &["v1", "search"]);
+        url.query_pairs_mut()
+            .append_pair("name", name)
+            .append_pair("count", "10")
+            .append_pair("format", "json");
+        let body = get_json::<GeocodingResponse>(client, url).await?;
+        Ok(body
+            .results
+            .into_iter()
+            .filter(|r| r.is_in(&regions))
+            .map(|r| Place {
+                name: place_name(&[
+                    Some(r.name.as_str()),
+                    r.admin1.as_deref(),
+                    r.country_code.as_deref(),
+                ]),
+                latitude: r.latitude,
+                longitude: r.longitude,
+            })
+            .collect())
+    }
+}
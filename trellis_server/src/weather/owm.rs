@@ -0,0 +1,247 @@
+use super::{coordinates, endpoint, get_json, place_name, Backend, FetchError, Geocoder};
+use chrono::{NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc};
+use serde::Deserialize;
+use trellis_core::weather::{DailyForecast, Forecast, HourlyForecast, Place, WeatherReport};
+use url::Url;
+
+/// OpenWeatherMap's current weather, 5 day / 3 hour forecast, and geocoding APIs. Locations are
+/// `latitude,longitude` pairs, or city IDs.
+pub struct OpenWeatherMap {
+    pub base_url: Url,
+}
+
+#[derive(Deserialize)]
+struct OwmResponse {
+    id: u64,
+    name: String,
+    /// Unix time of the observation.
+    dt: i64,
+    weather: Vec<OwmCondition>,
+    main: OwmMain,
+}
+
+#[derive(Deserialize)]
+struct OwmCondition {
+    description: String,
+    icon: String,
+}
+
+#[derive(Deserialize)]
+struct OwmMain {
+    temp: f64,
+}
+
+#[derive(Deserialize)]
+struct OwmForecastResponse {
+    list: Vec<OwmForecastEntry>,
+    city: OwmCity,
+}
+
+#[derive(Deserialize)]
+struct OwmCity {
+    id: u64,
+    name: String,
+    /// The offset from UTC, in seconds.
+    timezone: i64,
+}
+
+/// The forecast for one three-hour period.
+#[derive(Deserialize)]
+struct OwmForecastEntry {
+    dt: i64,
+    main: OwmForecastMain,
+    weather: Vec<OwmCondition>,
+    /// The probability of precipitation, from 0 to 1.
+    pop: Option<f64>,
+    rain: Option<OwmVolume>,
+    snow: Option<OwmVolume>,
+}
+
+#[derive(Deserialize)]
+struct OwmForecastMain {
+    temp: f64,
+    temp_min: f64,
+    temp_max: f64,
+}
+
+#[derive(Deserialize)]
+struct OwmVolume {
+    /// In millimeters.
+    #[serde(rename = "3h")]
+    three_hours: f64,
+}
+
+/// A place found by the geocoding API.
+#[derive(Deserialize)]
+struct OwmPlace {
+    name: String,
+    lat: f64,
+    lon: f64,
+    /// A two-letter country code.
+    country: String,
+    /// Only for some countries, like the US.
+    state: Option<String>,
+}
+
+fn source_url(city_id: u64) -> String {
+    format!("https://openweathermap.org/city/{}", city_id)
+}
+
+fn normalize(res: OwmResponse) -> Result<WeatherReport, FetchError> {
+    // There can be more than one condition at once, but the first one is the main one.
+    let condition = res
+        .weather
+        .into_iter()
+        .next()
+        .ok_or_else(|| FetchError::Response("no weather conditions".to_owned()))?;
+    Ok(WeatherReport {
+        location_name: res.name,
+        description: condition.description,
+        temperature: res.main.temp,
+        icon_url: Some(format!(
+            "https://openweathermap.org/img/w/{}.png",
+            condition.icon
+        )),
+        observed_at: Utc.timestamp(res.dt, 0),
+        source_name: "OpenWeatherMap".to_owned(),
+        source_url: Some(source_url(res.id)),
+    })
+}
+
+fn normalize_forecast(res: OwmForecastResponse) -> Forecast {
+    let hourly = res
+        .list
+        .iter()
+        .map(|entry| {
+            // Rain and snow are reported separately, but they're both precipitation.
+            let precipitation = [&entry.rain, &entry.snow]
+                .iter()
+                .filter_map(|v| v.as_ref())
+                .map(|v| v.three_hours)
+                .sum();
+            HourlyForecast {
+                time: Utc.timestamp(entry.dt, 0),
+                temperature: entry.main.temp,
+                precipitation_chance: entry.pop.map(|p| p * 100.0),
+                precipitation: Some(precipitation),
+            }
+        })
+        .collect();
+
+    // There's no daily forecast, so build one from the three-hour periods of each local day.
+    let mut days: Vec<(NaiveDate, Vec<(NaiveDateTime, &OwmForecastEntry)>)> = Vec::new();
+    for entry in res.list.iter() {
+        let local = NaiveDateTime::from_timestamp(entry.dt + res.city.timezone, 0);
+        match days.last_mut() {
+            Some((date, entries)) if *date == local.date() => entries.push((local, entry)),
+            _ => days.push((local.date(), vec![(local, entry)])),
+        }
+    }
+    let daily = days
+        .into_iter()
+        .map(|(date, entries)| {
+            // Describe the day by the period closest to noon.
+            let description = entries
+                .iter()
+                .min_by_key(|(local, _)| (local.hour() as i32 - 12).abs())
+                .and_then(|(_, entry)| entry.weather.first())
+                .map_or_else(String::new, |c| c.description.clone());
+            DailyForecast {
+                date,
+                description,
+                high: entries
+                    .iter()
+                    .map(|(_, e)| e.main.temp_max)
+                    .fold(f64::NEG_INFINITY, f64::max),
+                low: entries
+                    .iter()
+                    .map(|(_, e)| e.main.temp_min)
+                    .fold(f64::INFINITY, f64::min),
+            }
+        })
+        .collect();
+
+    Forecast {
+        location_name: res.city.name,
+        hourly,
+        daily,
+        source_name: "OpenWeatherMap".to_owned(),
+        source_url: Some(source_url(res.city.id)),
+    }
+}
+
+/// Builds the URL of an OpenWeatherMap endpoint for a location.
+fn location_endpoint(base_url: &Url, name: &str, location_id: &str, api_key: &str) -> Url {
+    let mut url = endpoint(base_url, &["data", "2.5", name]);
+    {
+        let mut query = url.query_pairs_mut();
+        // Locations found by searching are coordinates, but older tiles have city IDs.
+        match coordinates(location_id) {
+            Ok((lat, lon)) => query
+                .append_pair("lat", &lat.to_string())
+                .append_pair("lon", &lon.to_string()),
+            Err(_) => query.append_pair("id", location_id),
+        };
+        query
+            .append_pair("appid", api_key)
+            .append_pair("units", "metric");
+    }
+    url
+}
+
+#[rocket::async_trait]
+impl Backend for OpenWeatherMap {
+    async fn current(
+        &self,
+        client: &reqwest::Client,
+        location_id: &str,
+        api_key: Option<&str>,
+    ) -> Result<WeatherReport, FetchError> {
+        let api_key = api_key.ok_or(FetchError::MissingApiKey)?;
+        let url = location_endpoint(&self.base_url, "weather", location_id, api_key);
+        let body = get_json::<OwmResponse>(client, url).await?;
+        normalize(body)
+    }
+
+    async fn forecast(
+        &self,
+        client: &reqwest::Client,
+        location_id: &str,
+        api_key: Option<&str>,
+    ) -> Result<Forecast, FetchError> {
+        let api_key = api_key.ok_or(FetchError::MissingApiKey)?;
+        let url = location_endpoint(&self.base_url, "forecast", location_id, api_key);
+        let body = get_json::<OwmForecastResponse>(client, url).await?;
+        Ok(normalize_forecast(body))
+    }
+}
+
+#[rocket::async_trait]
+impl Geocoder for OpenWeatherMap {
+    async fn search(
+        &self,
+        client: &reqwest::Client,
+        query: &str,
+        api_key: Option<&str>,
+    ) -> Result<Vec<Place>, FetchError> {
+        let api_key = api_key.ok_or(FetchError::MissingApiKey)?;
+        let mut url = endpoint(&self.base_url, &["geo", "1.0", "direct"]);
+        url.query_pairs_mut()
+            .append_pair("q", query)
+            .append_pair("limit", "5")
+            .append_pair("appid", api_key);
+        let places = get_json::<Vec<OwmPlace>>(client, url).await?;
+        Ok(places
+            .into_iter()
+            .map(|p| Place {
+                name: place_name(&[
+                    Some(p.name.as_str()),
+                    p.state.as_deref(),
+                    Some(p.country.as_str()),
+                ]),
+                latitude: p.lat,
+                longitude: p.lon,
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,523 @@
+use super::*;
+use crate::test_server::stub_server;
+use chrono::{NaiveDate, TimeZone, Utc};
+use trellis_core::weather::{DailyForecast, HourlyForecast, Place};
+
+const JSON: &str = "application/json";
+
+#[rocket::async_test]
+async fn test_fetch_owm() {
+    let (url, server) = stub_server(
+        JSON,
+        &[(
+            "200 OK",
+            "",
+            include_str!("../../fixtures/owm_weather.json"),
+        )],
+    );
+    let report = OpenWeatherMap { base_url: url }
+        .current(&reqwest::Client::new(), "5391959", Some("TEST_KEY"))
+        .await
+        .unwrap();
+
+    let request_lines = server.join().unwrap();
+    assert!(request_lines[0]
+        .starts_with("GET /data/2.5/weather?id=5391959&appid=TEST_KEY&units=metric "));
+    assert_eq!(
+        report,
+        WeatherReport {
+            location_name: "San Francisco".to_owned(),
+            description: "scattered clouds".to_owned(),
+            temperature: 17.5,
+            icon_url: Some("https://openweathermap.org/img/w/03d.png".to_owned()),
+            observed_at: Utc.ymd(2021, 8, 26).and_hms(17, 46, 40),
+            source_name: "OpenWeatherMap".to_owned(),
+            source_url: Some("https://openweathermap.org/city/5391959".to_owned()),
+        }
+    );
+}
+
+#[rocket::async_test]
+async fn test_fetch_owm_errors() {
+    let client = reqwest::Client::new();
+
+    let (url, server) = stub_server(
+        JSON,
+        &[(
+            "404 Not Found",
+            "",
+            r#"{"cod":"404","message":"city not found"}"#,
+        )],
+    );
+    let owm = OpenWeatherMap { base_url: url };
+    let res = owm.current(&client, "0", Some("TEST_KEY")).await;
+    server.join().unwrap();
+    assert!(matches!(res, Err(FetchError::NotFound)));
+
+    let res = owm.current(&client, "5391959", None).await;
+    assert!(matches!(res, Err(FetchError::MissingApiKey)));
+
+    let (url, server) = stub_server(
+        JSON,
+        &[(
+            "401 Unauthorized",
+            "",
+            r#"{"cod":401,"message":"Invalid API key"}"#,
+        )],
+    );
+    let owm = OpenWeatherMap { base_url: url };
+    let res = owm.current(&client, "5391959", Some("TEST_KEY")).await;
+    server.join().unwrap();
+    assert!(matches!(
+        res,
+        Err(FetchError::Status(reqwest::StatusCode::UNAUTHORIZED))
+    ));
+
+    let (url, server) = stub_server(JSON, &[("200 OK", "", r#"{"unexpected": true}"#)]);
+    let owm = OpenWeatherMap { base_url: url };
+    let res = owm.current(&client, "5391959", Some("TEST_KEY")).await;
+    server.join().unwrap();
+    let err = res.unwrap_err();
+    assert!(!err.to_string().contains("TEST_KEY"), "{}", err);
+}
+
+#[rocket::async_test]
+async fn test_fetch_owm_forecast() {
+    let (url, server) = stub_server(
+        JSON,
+        &[(
+            "200 OK",
+            "",
+            include_str!("../../fixtures/owm_forecast.json"),
+        )],
+    );
+    let forecast = OpenWeatherMap { base_url: url }
+        .forecast(&reqwest::Client::new(), "5391959", Some("TEST_KEY"))
+        .await
+        .unwrap();
+
+    let request_lines = server.join().unwrap();
+    assert!(request_lines[0]
+        .starts_with("GET /data/2.5/forecast?id=5391959&appid=TEST_KEY&units=metric "));
+    assert_eq!(forecast.location_name, "San Francisco");
+    assert_eq!(forecast.hourly.len(), 16);
+    assert_eq!(
+        forecast.hourly[0].time,
+        Utc.ymd(2021, 8, 26).and_hms(21, 0, 0)
+    );
+    assert_eq!(forecast.hourly[0].temperature, 19.2);
+    assert_eq!(forecast.hourly[12].precipitation, Some(0.35));
+    assert!((forecast.hourly[12].precipitation_chance.unwrap() - 56.0).abs() < 1e-9);
+
+    // Three-hour periods are grouped by the day in San Francisco, not in UTC.
+    assert_eq!(
+        forecast.daily,
+        vec![
+            DailyForecast {
+                date: NaiveDate::from_ymd(2021, 8, 26),
+                description: "scattered clouds".to_owned(),
+                high: 19.5,
+                low: 13.6,
+            },
+            DailyForecast {
+                date: NaiveDate::from_ymd(2021, 8, 27),
+                description: "scattered clouds".to_owned(),
+                high: 20.6,
+                low: 12.5,
+            },
+            DailyForecast {
+                date: NaiveDate::from_ymd(2021, 8, 28),
+                description: "few clouds".to_owned(),
+                high: 18.9,
+                low: 13.2,
+            },
+        ]
+    );
+}
+
+#[rocket::async_test]
+async fn test_fetch_open_meteo() {
+    let (url, server) = stub_server(
+        JSON,
+        &[(
+            "200 OK",
+            "",
+            include_str!("../../fixtures/open_meteo_forecast.json"),
+        )],
+    );
+    let report = OpenMeteo {
+        base_url: url.clone(),
+        geocoding_url: url,
+    }
+    .current(&reqwest::Client::new(), "37.7749, -122.4194", None)
+    .await
+    .unwrap();
+
+    let request_lines = server.join().unwrap();
+    assert!(request_lines[0].starts_with(
+        "GET /v1/forecast?latitude=37.7749&longitude=-122.4194&current_weather=true&timezone=UTC "
+    ));
+    assert_eq!(
+        report,
+        WeatherReport {
+            location_name: "37.77, -122.42".to_owned(),
+            description: "partly cloudy".to_owned(),
+            temperature: 17.5,
+            icon_url: None,
+            observed_at: Utc.ymd(2021, 8, 26).and_hms(17, 0, 0),
+            source_name: "Open-Meteo".to_owned(),
+            source_url: Some("https://open-meteo.com/".to_owned()),
+        }
+    );
+}
+
+#[rocket::async_test]
+async fn test_fetch_open_meteo_forecast() {
+    let (url, server) = stub_server(
+        JSON,
+        &[(
+            "200 OK",
+            "",
+            include_str!("../../fixtures/open_meteo_hourly_daily.json"),
+        )],
+    );
+    let forecast = OpenMeteo {
+        base_url: url.clone(),
+        geocoding_url: url,
+    }
+    .forecast(&reqwest::Client::new(), "37.7749,-122.4194", None)
+    .await
+    .unwrap();
+
+    let request_lines = server.join().unwrap();
+    assert!(request_lines[0].starts_with("GET /v1/forecast?latitude=37.7749&longitude=-122.4194&"));
+    assert!(request_lines[0].contains("&timezone=auto "));
+    assert_eq!(forecast.location_name, "37.77, -122.42");
+    assert_eq!(forecast.hourly.len(), 48);
+    // Midnight in San Francisco.
+    assert_eq!(
+        forecast.hourly[0].time,
+        Utc.ymd(2021, 8, 26).and_hms(7, 0, 0)
+    );
+    assert_eq!(forecast.hourly[0].temperature, 12.2);
+    assert_eq!(forecast.hourly[31].precipitation, Some(0.6));
+    assert_eq!(forecast.daily.len(), 7);
+    assert_eq!(
+        forecast.daily[2],
+        DailyForecast {
+            date: NaiveDate::from_ymd(2021, 8, 28),
+            description: "light rain".to_owned(),
+            high: 16.2,
+            low: 12.1,
+        }
+    );
+}
+
+#[rocket::async_test]
+async fn test_fetch_nws() {
+    let (url, server) = stub_server(
+        JSON,
+        &[
+            ("200 OK", "", include_str!("../../fixtures/nws_points.json")),
+            (
+                "200 OK",
+                "",
+                include_str!("../../fixtures/nws_stations.json"),
+            ),
+            (
+                "200 OK",
+                "",
+                include_str!("../../fixtures/nws_observation.json"),
+            ),
+        ],
+    );
+    let report = Nws { base_url: url }
+        .current(&reqwest::Client::new(), "37.7749,-122.4194", None)
+        .await
+        .unwrap();
+
+    let request_lines = server.join().unwrap();
+    assert!(request_lines[0].starts_with("GET /points/37.7749,-122.4194 "));
+    assert!(request_lines[1].starts_with("GET /gridpoints/MTR/85,105/stations "));
+    assert!(request_lines[2].starts_with("GET /stations/KSFO/observations/latest "));
+    assert_eq!(
+        report,
+        WeatherReport {
+            location_name: "San Francisco, CA".to_owned(),
+            description: "partly cloudy".to_owned(),
+            temperature: 17.8,
+            icon_url: Some("https://api.weather.gov/icons/land/day/sct?size=medium".to_owned()),
+            observed_at: Utc.ymd(2021, 8, 26).and_hms(17, 56, 0),
+            source_name: "National Weather Service".to_owned(),
+            source_url: Some(
+                "https://forecast.weather.gov/MapClick.php?lat=37.7749&lon=-122.4194".to_owned()
+            ),
+        }
+    );
+}
+
+#[rocket::async_test]
+async fn test_fetch_nws_forecast() {
+    let (url, server) = stub_server(
+        JSON,
+        &[
+            ("200 OK", "", include_str!("../../fixtures/nws_points.json")),
+            (
+                "200 OK",
+                "",
+                include_str!("../../fixtures/nws_forecast_hourly.json"),
+            ),
+            (
+                "200 OK",
+                "",
+                include_str!("../../fixtures/nws_forecast.json"),
+            ),
+        ],
+    );
+    let forecast = Nws { base_url: url }
+        .forecast(&reqwest::Client::new(), "37.7749,-122.4194", None)
+        .await
+        .unwrap();
+
+    let request_lines = server.join().unwrap();
+    assert!(request_lines[1].starts_with("GET /gridpoints/MTR/85,105/forecast/hourly?units=si "));
+    assert!(request_lines[2].starts_with("GET /gridpoints/MTR/85,105/forecast?units=si "));
+    assert_eq!(forecast.location_name, "San Francisco, CA");
+    assert_eq!(forecast.hourly.len(), 30);
+    assert_eq!(
+        forecast.hourly[0],
+        HourlyForecast {
+            time: Utc.ymd(2021, 8, 26).and_hms(18, 0, 0),
+            temperature: 17.0,
+            precipitation_chance: Some(0.0),
+            precipitation: None,
+        }
+    );
+    assert_eq!(forecast.daily.len(), 7);
+    assert_eq!(
+        forecast.daily[0],
+        DailyForecast {
+            date: NaiveDate::from_ymd(2021, 8, 26),
+            description: "mostly sunny".to_owned(),
+            high: 21.0,
+            low: 13.0,
+        }
+    );
+    assert_eq!(forecast.daily[6].date, NaiveDate::from_ymd(2021, 9, 1));
+}
+
+#[rocket::async_test]
+async fn test_fetch_nws_outside_us() {
+    let (url, server) = stub_server(
+        JSON,
+        &[(
+            "404 Not Found",
+            "",
+            r#"{"title":"Data Unavailable For Requested Point","status":404}"#,
+        )],
+    );
+    let res = Nws { base_url: url }
+        .current(&reqwest::Client::new(), "51.5074,-0.1278", None)
+        .await;
+    server.join().unwrap();
+    assert!(matches!(res, Err(FetchError::NotFound)));
+}
+
+#[rocket::async_test]
+async fn test_fetch_owm_by_coordinates() {
+    let (url, server) = stub_server(
+        JSON,
+        &[(
+            "200 OK",
+            "",
+            include_str!("../../fixtures/owm_weather.json"),
+        )],
+    );
+    OpenWeatherMap { base_url: url }
+        .current(
+            &reqwest::Client::new(),
+            "45.5152,-122.6784",
+            Some("TEST_KEY"),
+        )
+        .await
+        .unwrap();
+
+    let request_lines = server.join().unwrap();
+    assert!(request_lines[0].starts_with(
+        "GET /data/2.5/weather?lat=45.5152&lon=-122.6784&appid=TEST_KEY&units=metric "
+    ));
+}
+
+#[rocket::async_test]
+async fn test_search_owm() {
+    let (url, server) = stub_server(
+        JSON,
+        &[(
+            "200 OK",
+            "",
+            include_str!("../../fixtures/owm_geocoding.json"),
+        )],
+    );
+    let owm = OpenWeatherMap { base_url: url };
+    let places = owm
+        .search(&reqwest::Client::new(), "Portland, US", Some("TEST_KEY"))
+        .await
+        .unwrap();
+
+    let request_lines = server.join().unwrap();
+    assert!(request_lines[0]
+        .starts_with("GET /geo/1.0/direct?q=Portland%2C+US&limit=5&appid=TEST_KEY "));
+    let names: Vec<&str> = places.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec![
+            "Portland, Oregon, US",
+            "Portland, Maine, US",
+            "Portland, Victoria, AU"
+        ]
+    );
+    assert_eq!(places[0].location_id(), "45.5202,-122.6742");
+
+    let res = owm.search(&reqwest::Client::new(), "Portland", None).await;
+    assert!(matches!(res, Err(FetchError::MissingApiKey)));
+}
+
+#[rocket::async_test]
+async fn test_search_open_meteo() {
+    let client = reqwest::Client::new();
+    let body = include_str!("../../fixtures/open_meteo_geocoding.json");
+    let (url, server) = stub_server(
+        JSON,
+        &[
+            ("200 OK", "", body),
+            ("200 OK", "", body),
+            ("200 OK", "", "{}"),
+        ],
+    );
+    let open_meteo = OpenMeteo {
+        base_url: Url::parse("http://localhost/").unwrap(),
+        geocoding_url: url,
+    };
+
+    let places = open_meteo.search(&client, "Portland", None).await.unwrap();
+    assert_eq!(places.len(), 3);
+    assert_eq!(
+        places[0],
+        Place {
+            name: "Portland, Oregon, US".to_owned(),
+            latitude: 45.52345,
+            longitude: -122.67621,
+        }
+    );
+
+    // Everything after the name has to match the place's region or country.
+    let places = open_meteo
+        .search(&client, "Portland, maine, United States", None)
+        .await
+        .unwrap();
+    let names: Vec<&str> = places.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, vec!["Portland, Maine, US"]);
+
+    let places = open_meteo.search(&client, "Nowhere", None).await.unwrap();
+    assert_eq!(places, vec![]);
+
+    let request_lines = server.join().unwrap();
+    assert!(request_lines[0].starts_with("GET /v1/search?name=Portland&count=10&format=json "));
+    assert!(request_lines[1].starts_with("GET /v1/search?name=Portland&count=10&format=json "));
+}
+
+#[test]
+fn test_place_name() {
+    assert_eq!(
+        place_name(&[Some("Berlin"), Some("Berlin"), Some("DE")]),
+        "Berlin, DE"
+    );
+    assert_eq!(
+        place_name(&[Some("Monaco"), None, Some(" "), Some("MC")]),
+        "Monaco, MC"
+    );
+}
+
+#[test]
+fn test_coordinates() {
+    assert_eq!(
+        coordinates("37.7749,-122.4194").unwrap(),
+        (37.7749, -122.4194)
+    );
+    assert_eq!(coordinates(" 1.5 , 2 ").unwrap(), (1.5, 2.0));
+    for bad in &["5391959", "north,west", "91,0", "0,181", "1,2,3"] {
+        assert!(
+            matches!(coordinates(bad), Err(FetchError::Location(_))),
+            "{}",
+            bad
+        );
+    }
+}
+
+fn test_config(cache_ttl_secs: u64) -> Config {
+    let url = Url::parse("http://localhost/").unwrap();
+    Config {
+        owm_url: url.clone(),
+        open_meteo_url: url.clone(),
+        open_meteo_geocoding_url: url.clone(),
+        nws_url: url,
+        cache_ttl_secs,
+    }
+}
+
+#[test]
+fn test_cache_expires() {
+    let weather = Weather::new(test_config(0));
+    let report = WeatherReport {
+        location_name: "Somewhere".to_owned(),
+        description: "clear sky".to_owned(),
+        temperature: 20.0,
+        icon_url: None,
+        observed_at: Utc.timestamp(0, 0),
+        source_name: "Test".to_owned(),
+        source_url: None,
+    };
+    let key = |provider, location_id: &str| (provider, location_id.to_owned());
+    weather
+        .reports
+        .put(key(Provider::OpenMeteo, "1,2"), report.clone());
+    assert_eq!(weather.reports.get(&key(Provider::OpenMeteo, "1,2")), None);
+
+    let weather = Weather::new(test_config(60));
+    weather
+        .reports
+        .put(key(Provider::OpenMeteo, "1,2"), report.clone());
+    assert_eq!(
+        weather.reports.get(&key(Provider::OpenMeteo, "1,2")),
+        Some(report)
+    );
+    assert_eq!(weather.reports.get(&key(Provider::OpenMeteo, "3,4")), None);
+    assert_eq!(weather.reports.get(&key(Provider::Nws, "1,2")), None);
+    assert_eq!(
+        weather.forecasts.get(&key(Provider::OpenMeteo, "1,2")),
+        None
+    );
+}
+
+#[test]
+fn test_cache_forgets_the_oldest_when_full() {
+    let weather = Weather::new(test_config(60));
+    let report = WeatherReport {
+        location_name: "Somewhere".to_owned(),
+        description: "clear sky".to_owned(),
+        temperature: 20.0,
+        icon_url: None,
+        observed_at: Utc.timestamp(0, 0),
+        source_name: "Test".to_owned(),
+        source_url: None,
+    };
+    let key = |i| (Provider::OpenMeteo, format!("{},0", i));
+    for i in 0..=MAX_CACHE_ENTRIES {
+        weather.reports.put(key(i), report.clone());
+    }
+    let kept = (0..=MAX_CACHE_ENTRIES)
+        .filter(|&i| weather.reports.get(&key(i)).is_some())
+        .count();
+    assert_eq!(kept, MAX_CACHE_ENTRIES);
+    assert_eq!(weather.reports.get(&key(MAX_CACHE_ENTRIES)), Some(report));
+}
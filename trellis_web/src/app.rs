@@ -1,26 +1,59 @@
+use trellis_core::api;
 use yew::prelude::*;
 use yew_router::prelude::*;
 
 mod about;
+mod account_form;
 mod add_tile_form;
 mod board;
+mod boards;
+mod boards_form;
 mod clock;
 mod config_form;
+mod feed;
 mod grid;
+mod history_list;
+mod import_export_form;
+mod links;
+mod locale;
+mod markdown;
+mod login_page;
+mod members_form;
 mod note;
+mod preferences_form;
 mod secrets_form;
 mod settings;
 mod settings_page;
+mod share_links_form;
+mod shared_board;
+mod sync_status;
 mod weather;
 
-pub struct App {}
+pub struct App {
+    /// Kept open so that the nav shows the boards as they change.
+    _boards_service: Box<dyn Bridge<boards::Boards>>,
+    boards: Option<api::BoardList>,
+}
+
+pub enum Msg {
+    Boards(api::BoardList),
+    Noop,
+}
 
 #[derive(Switch, Debug, Clone)]
 pub enum AppRoute {
     #[to = "/about"]
     About,
+    #[to = "/login"]
+    Login,
     #[to = "/settings"]
     Settings,
+    #[to = "/b/{}/settings"]
+    BoardSettings(String),
+    #[to = "/b/{}"]
+    NamedBoard(String),
+    #[to = "/s/{}"]
+    Shared(String),
     #[to = "/"]
     Board,
 }
@@ -28,15 +61,31 @@ pub enum AppRoute {
 type Anchor = RouterAnchor<AppRoute>;
 
 impl Component for App {
-    type Message = ();
+    type Message = Msg;
     type Properties = ();
 
-    fn create(_props: Self::Properties, _link: ComponentLink<Self>) -> Self {
-        Self {}
+    fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let mut boards_service =
+            boards::Boards::bridge(link.callback(|output: boards::Output| match output {
+                boards::Output::List(list) => Msg::Boards(list),
+                // The settings page shows these.
+                boards::Output::Failed(_) => Msg::Noop,
+            }));
+        boards_service.send(boards::Request::Load);
+        Self {
+            _boards_service: boards_service,
+            boards: None,
+        }
     }
 
-    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
-        false
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::Boards(list) => {
+                self.boards = Some(list);
+                true
+            }
+            Msg::Noop => false,
+        }
     }
 
     fn change(&mut self, _props: Self::Properties) -> ShouldRender {
@@ -44,21 +93,76 @@ impl Component for App {
     }
 
     fn view(&self) -> Html {
+        let boards = self.boards.clone();
         html! {
             <div class="min-h-screen text-black bg-white dark:text-white dark:bg-black flex flex-col">
                 <Router<AppRoute, ()> render=Router::render(move |route: AppRoute| {
+                    // Shared boards are for people who may not have an account, so there's no
+                    // nav to confuse them.
+                    if let AppRoute::Shared(token) = route {
+                        return html! { <main class="m-1"><shared_board::SharedBoard token=token /></main> };
+                    }
+                    let boards = match &boards {
+                        Some(boards) => boards,
+                        None => return html! { <p class="text-xl">{"Loading..."}</p> },
+                    };
+                    // The board that's shown, or whose settings are.
+                    let current = match &route {
+                        AppRoute::NamedBoard(slug) | AppRoute::BoardSettings(slug) => slug.clone(),
+                        _ => boards.default.clone(),
+                    };
+                    let role = boards
+                        .boards
+                        .iter()
+                        .find(|b| b.slug == current)
+                        .map(|b| b.role)
+                        .unwrap_or_default();
                     let main = match route {
                         AppRoute::About => html! { <about::About /> },
-                        AppRoute::Board => html! { <board::Board /> },
-                        AppRoute::Settings => html! { <settings_page::SettingsPage /> },
+                        AppRoute::Board | AppRoute::NamedBoard(_) => {
+                            html! { <board::Board board=current.clone() role=role /> }
+                        }
+                        AppRoute::Login => html! { <login_page::LoginPage /> },
+                        AppRoute::Settings | AppRoute::BoardSettings(_) => {
+                            html! { <settings_page::SettingsPage board=current.clone() role=role /> }
+                        }
+                        AppRoute::Shared(_) => unreachable!(),
+                    };
+                    let settings_route = if current == boards.default {
+                        AppRoute::Settings
+                    } else {
+                        AppRoute::BoardSettings(current.clone())
+                    };
+                    let switcher = if boards.boards.len() > 1 {
+                        boards.boards.iter().map(|b| {
+                            let mut class = if b.slug == current { "font-bold" } else { "" }.to_owned();
+                            // Boards that someone else owns come last, and look different.
+                            let name = match &b.owner {
+                                Some(owner) => {
+                                    class.push_str(" italic");
+                                    html! { <span title=format!("Shared by {}", owner)>{&b.name}</span> }
+                                }
+                                None => html! { <>{&b.name}</> },
+                            };
+                            html! {
+                                <Anchor route=AppRoute::NamedBoard(b.slug.clone()) classes=class>
+                                    {name}
+                                </Anchor>
+                            }
+                        }).collect::<Html>()
+                    } else {
+                        html! {}
                     };
                     html! { <>
                         <nav class="px-3 py-1 flex justify-between bg-gray-200 dark:bg-gray-900">
-                            <Anchor route=AppRoute::Board>{"Trellis"}</Anchor>
+                            <div class="space-x-4">
+                                <Anchor route=AppRoute::Board>{"Trellis"}</Anchor>
+                                {switcher}
+                            </div>
                             <div class="space-x-4">
                                 <Anchor route=AppRoute::About>{"About"}</Anchor>
-                                <Anchor route=AppRoute::Settings>{"Settings"}</Anchor>
-                                <a href="/login">{"Log in"}</a>
+                                <Anchor route=settings_route>{"Settings"}</Anchor>
+                                <Anchor route=AppRoute::Login>{"Log in"}</Anchor>
                             </div>
                         </nav>
                         <div class="flex-grow flex flex-col justify-between">
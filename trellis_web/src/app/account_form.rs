@@ -0,0 +1,102 @@
+use yew::format::Nothing;
+use yew::prelude::*;
+use yew::services::console::ConsoleService;
+use yew::services::fetch::{FetchService, FetchTask, Request, Response};
+
+pub struct AccountForm {
+    link: ComponentLink<Self>,
+    fetch_task: Option<FetchTask>,
+    error: Option<String>,
+}
+
+pub enum Msg {
+    LogOut,
+    LogOutEverywhere,
+    LoggedOut,
+    Failed,
+}
+
+impl Component for AccountForm {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self {
+            link,
+            fetch_task: None,
+            error: None,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::LogOut => {
+                self.post("/api/v1/logout");
+                true
+            }
+            Msg::LogOutEverywhere => {
+                self.post("/api/v1/logout/everywhere");
+                true
+            }
+            Msg::LoggedOut => {
+                self.fetch_task = None;
+                // Reload from the root so nothing from the old session stays on screen.
+                if let Err(err) = yew::utils::window().location().set_href("/") {
+                    ConsoleService::error(&format!("could not redirect: {:?}", err));
+                }
+                false
+            }
+            Msg::Failed => {
+                self.fetch_task = None;
+                self.error = Some("Could not log out. Try again?".to_owned());
+                true
+            }
+        }
+    }
+
+    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
+        false
+    }
+
+    fn view(&self) -> Html {
+        let busy = self.fetch_task.is_some();
+        let error = match &self.error {
+            None => html! {},
+            Some(msg) => html! { <p class="text-red-500">{msg}</p> },
+        };
+        html! {
+            <div class="flex flex-col items-center space-y-1">
+                <span class="font-bold">{"Account"}</span>
+                {error}
+                <div class="flex space-x-4">
+                    <button type="button" disabled=busy onclick=self.link.callback(|_| Msg::LogOut)>
+                        {"Log out"}
+                    </button>
+                    <button type="button" disabled=busy onclick=self.link.callback(|_| Msg::LogOutEverywhere)>
+                        {"Log out everywhere"}
+                    </button>
+                </div>
+            </div>
+        }
+    }
+}
+
+impl AccountForm {
+    fn post(&mut self, url: &str) {
+        let req = Request::post(url)
+            .body(Nothing)
+            .expect("could not build request");
+
+        let cb = self.link.callback(|res: Response<anyhow::Result<String>>| {
+            if res.status().is_success() {
+                Msg::LoggedOut
+            } else {
+                ConsoleService::error("could not log out");
+                Msg::Failed
+            }
+        });
+
+        let task = FetchService::fetch(req, cb).expect("could not start request");
+        self.fetch_task = Some(task);
+    }
+}
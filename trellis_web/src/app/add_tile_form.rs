@@ -1,4 +1,8 @@
+use trellis_core::clock::Style;
 use trellis_core::config;
+use trellis_core::feed;
+use trellis_core::note::Text;
+use trellis_core::weather::{Mode, Provider};
 use yew::prelude::*;
 
 #[derive(Properties, Clone, Debug)]
@@ -43,12 +47,28 @@ impl Component for AddTileForm {
             Msg::Submit(e) => {
                 e.prevent_default();
                 let res = match self.value.as_str() {
-                    "clock" => Some(config::Data::Clock),
+                    "clock" => Some(config::Data::Clock {
+                        time_zone: None,
+                        extra_zones: Vec::new(),
+                        style: Style::default(),
+                    }),
+                    "feed" => Some(config::Data::Feed {
+                        url: "".to_owned(),
+                        max_items: feed::DEFAULT_ITEMS,
+                    }),
+                    "links" => Some(config::Data::Links {
+                        title: "".to_owned(),
+                        links: Vec::new(),
+                        new_tab: false,
+                    }),
                     "note" => Some(config::Data::Note {
-                        text: "".to_owned(),
+                        text: Text::default(),
                     }),
                     "weather" => Some(config::Data::Weather {
+                        provider: Provider::default(),
                         location_id: "".to_owned(),
+                        location_name: "".to_owned(),
+                        mode: Mode::default(),
                     }),
                     _ => None,
                 };
@@ -78,6 +98,8 @@ impl Component for AddTileForm {
                             <option value="clock">{"Clock"}</option>
                             <option value="weather">{"Weather"}</option>
                             <option value="note">{"Note"}</option>
+                            <option value="links">{"Links"}</option>
+                            <option value="feed">{"Feed"}</option>
                         </select>
                     </label>
                 </div>
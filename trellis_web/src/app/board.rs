@@ -1,31 +1,53 @@
 use super::grid::Grid;
 use super::settings;
-use super::{clock, note, weather};
+use super::sync_status;
+use super::{clock, feed, links, note, weather};
+use trellis_core::board::Role;
 use trellis_core::config;
+use trellis_core::outbox::Status;
+use trellis_core::preferences::Preferences;
 use uuid::Uuid;
 use yew::prelude::*;
 
+#[derive(Properties, Clone, Debug)]
+pub struct Props {
+    /// The slug of the board to show.
+    pub board: String,
+    /// What the user can do with the board. Viewers can't change tiles.
+    #[prop_or_default]
+    pub role: Role,
+}
+
 pub struct Board {
     link: ComponentLink<Self>,
+    props: Props,
     settings: Option<config::Config>,
+    status: Status,
     settings_service: Box<dyn Bridge<settings::Settings>>,
 }
 
 pub enum Msg {
     Load(config::Config),
+    Status(Status),
     Change { id: Uuid, data: config::Data },
 }
 
 impl Component for Board {
     type Message = Msg;
-    type Properties = ();
+    type Properties = Props;
 
-    fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
-        let mut settings_service = settings::Settings::bridge(link.callback(Msg::Load));
-        settings_service.send(settings::Request::Load);
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let mut settings_service =
+            settings::Settings::bridge(link.callback(|output: settings::Output| match output {
+                settings::Output::Config(cfg) => Msg::Load(cfg),
+                settings::Output::Status(status) => Msg::Status(status),
+            }));
+        settings_service.send(settings::Request::Load(props.board.clone()));
         Self {
             link,
+            props,
             settings: None,
+            status: Status::Saved,
             settings_service,
         }
     }
@@ -36,6 +58,10 @@ impl Component for Board {
                 self.settings = Some(cfg);
                 true
             }
+            Msg::Status(status) => {
+                self.status = status;
+                true
+            }
             Msg::Change { id, data } => {
                 self.settings_service
                     .send(settings::Request::SaveSingle { id, data });
@@ -44,8 +70,17 @@ impl Component for Board {
         }
     }
 
-    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
-        false
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        if props.board == self.props.board {
+            let changed = props.role != self.props.role;
+            self.props = props;
+            return changed;
+        }
+        self.props = props;
+        self.settings = None;
+        self.settings_service
+            .send(settings::Request::Load(self.props.board.clone()));
+        true
     }
 
     fn view(&self) -> Html {
@@ -53,49 +88,109 @@ impl Component for Board {
             None => html! { <p class="text-xl">{"Loading..."}</p> },
             Some(cfg) => {
                 let tiles = cfg.tiles.clone();
-                let secrets = cfg.secrets.clone();
+                let onchange = self.link.callback(|(id, data)| Msg::Change { id, data });
+                let (onchange, status) = if self.props.role.can_edit() {
+                    (Some(&onchange), sync_status::view(self.status))
+                } else {
+                    (
+                        None,
+                        html! { <span class="text-gray-400">{"View only"}</span> },
+                    )
+                };
                 html! {
-                    <Grid>
-                        { tiles.iter().map(|t| self.render_tile(t.clone(), secrets.clone())).collect::<Html>() }
-                    </Grid>
+                    <>
+                        <div class="flex justify-end px-2">{status}</div>
+                        <Grid>
+                            { tiles.iter().map(|t| view_tile(&cfg.preferences, t.clone(), None, onchange)).collect::<Html>() }
+                        </Grid>
+                    </>
                 }
             }
         }
     }
 }
 
-impl Board {
-    // TODO: Tile component
-    fn render_tile(&self, tile: config::Tile, secrets: config::Secrets) -> Html {
-        let id = tile.id.clone();
-        let inner = match &tile.data {
-            config::Data::Clock => html! { <clock::Clock /> },
-            config::Data::Weather { location_id } => {
-                html! {
-                    <weather::Weather
-                        location_id=location_id.clone()
-                        owm_api_key=secrets.owm_api_key.clone().unwrap_or("".to_owned())
-                    />
-                }
-            }
-            config::Data::Note { text } => html! {
-                <note::Note
-                    text=text.clone()
-                    onchange=self.link.callback(move |text| {
-                        Msg::Change{id, data: config::Data::Note{ text }}
-                    })
-                />
-            },
-        };
+// TODO: Tile component
+/// Shows a tile. Tiles seen through a share link fetch their weather through the link. `onchange`
+/// is called with tiles that change themselves, like notes; without it, tiles can't be changed.
+pub fn view_tile(
+    preferences: &Preferences,
+    tile: config::Tile,
+    share: Option<&str>,
+    onchange: Option<&Callback<(Uuid, config::Data)>>,
+) -> Html {
+    let id = tile.id;
+    let height = tile.height.unwrap_or(1);
+    let width = tile.width.unwrap_or(1);
+    let prefs = preferences.with_overrides(&tile.overrides);
+    let inner = match &tile.data {
+        config::Data::Clock {
+            time_zone,
+            extra_zones,
+            style,
+        } => html! {
+            <clock::Clock
+                time_zone=time_zone.clone()
+                extra_zones=extra_zones.clone()
+                style=*style
+                prefs=prefs
+            />
+        },
+        config::Data::Weather {
+            provider,
+            location_id,
+            location_name,
+            mode,
+        } => html! {
+            <weather::Weather
+                provider=*provider
+                location_id=location_id.clone()
+                location_name=location_name.clone()
+                mode=*mode
+                width=width
+                height=height
+                prefs=prefs
+                share=share.map(str::to_owned)
+            />
+        },
+        config::Data::Note { text } => html! {
+            <note::Note
+                id=id
+                text=text.clone()
+                onchange=onchange.map_or_else(Callback::noop, |cb| {
+                    cb.reform(move |text| (id, config::Data::Note { text }))
+                })
+                readonly=onchange.is_none()
+            />
+        },
+        config::Data::Links {
+            title,
+            links: tile_links,
+            new_tab,
+        } => html! {
+            <links::Links
+                title=title.clone()
+                links=tile_links.clone()
+                new_tab=*new_tab
+                share=share.map(str::to_owned)
+            />
+        },
+        config::Data::Feed { url, max_items } => html! {
+            <feed::Feed
+                url=url.clone()
+                max_items=*max_items
+                prefs=prefs
+                share=share.map(str::to_owned)
+            />
+        },
+    };
 
-        let height = tile.height.unwrap_or(1);
-        let width = tile.width.unwrap_or(1);
-        let style = format!(
-            "grid-row: auto / span {}; grid-column: auto / span {}",
-            height, width
-        );
-        html! {
-            <div style=style>{inner}</div>
-        }
+    let style = format!(
+        "grid-row: auto / span {}; grid-column: auto / span {}",
+        height, width
+    );
+    // Tiles are keyed so that when they move, each one keeps its own component and state.
+    html! {
+        <div key=id.to_string() style=style>{inner}</div>
     }
 }
@@ -0,0 +1,223 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use trellis_core::{api, board};
+use yew::format::{Json, Nothing, Text};
+use yew::services::console::ConsoleService;
+use yew::services::fetch;
+use yew::services::storage::{Area, StorageService};
+use yew::worker::*;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum Request {
+    Load,
+    Create {
+        name: String,
+    },
+    Rename {
+        slug: String,
+        name: String,
+    },
+    Delete {
+        slug: String,
+    },
+    MakeDefault {
+        slug: String,
+    },
+    /// Stops using a board that someone else owns.
+    Leave {
+        slug: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum Output {
+    List(api::BoardList),
+    /// A change to the boards didn't go through. The message is meant for the user.
+    Failed(String),
+}
+
+/// Keeps track of which boards the user has.
+pub struct Boards {
+    link: AgentLink<Self>,
+    subscribers: HashSet<HandlerId>,
+    local: StorageService,
+    /// The boards, as last loaded. Kept in LocalStorage so that boards can be shown offline.
+    list: api::BoardList,
+    load_req: Option<fetch::FetchTask>,
+    change_req: Option<fetch::FetchTask>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum Msg {
+    Loaded(api::BoardList),
+    /// A board was created or renamed, so the list needs loading again.
+    Changed,
+    Failed(String),
+    LoadFailed,
+}
+
+impl Boards {
+    const KEY: &'static str = "trellis.boards";
+
+    fn broadcast(&self, output: Output) {
+        for sub in self.subscribers.iter() {
+            self.link.respond(*sub, output.clone());
+        }
+    }
+
+    /// Reads the boards this device last saw. Without any, there's only the board every user
+    /// starts with.
+    fn restore(local: &StorageService) -> api::BoardList {
+        local
+            .restore::<Json<anyhow::Result<api::BoardList>>>(Self::KEY)
+            .0
+            .unwrap_or_else(|_| api::BoardList {
+                boards: vec![api::Board {
+                    slug: board::DEFAULT_SLUG.to_owned(),
+                    name: board::DEFAULT_NAME.to_owned(),
+                    role: board::Role::Owner,
+                    owner: None,
+                }],
+                default: board::DEFAULT_SLUG.to_owned(),
+            })
+    }
+
+    fn fetch(&mut self) {
+        let req = fetch::Request::get("/api/v1/boards")
+            .body(Nothing)
+            .expect("could not build request");
+        let cb = self.link.callback(
+            |res: fetch::Response<Json<anyhow::Result<api::BoardList>>>| {
+                let Json(data) = res.into_body();
+                match data {
+                    Ok(list) => Msg::Loaded(list),
+                    Err(err) => {
+                        ConsoleService::error(&format!("could not load boards: {}", err));
+                        Msg::LoadFailed
+                    }
+                }
+            },
+        );
+        let task = fetch::FetchService::fetch(req, cb).expect("could not start request");
+        self.load_req = Some(task);
+    }
+
+    /// Sends a change to the boards. Responses with a list of boards replace the current one, and
+    /// anything else successful means the list should be loaded again.
+    fn send(&mut self, req: fetch::Request<Text>, action: &'static str) {
+        let cb = self
+            .link
+            .callback(move |res: fetch::Response<anyhow::Result<String>>| {
+                let (meta, body) = res.into_parts();
+                if meta.status.is_success() {
+                    return match body.and_then(|b| Ok(serde_json::from_str(&b)?)) {
+                        Ok(list) => Msg::Loaded(list),
+                        Err(_) => Msg::Changed,
+                    };
+                }
+                let reason = body
+                    .and_then(|b| Ok(serde_json::from_str::<api::Error>(&b)?))
+                    .map(|err| err.message)
+                    .unwrap_or_else(|_| meta.status.to_string());
+                Msg::Failed(format!("Could not {} board: {}", action, reason))
+            });
+        let task = fetch::FetchService::fetch(req, cb).expect("could not start request");
+        self.change_req = Some(task);
+    }
+}
+
+impl Agent for Boards {
+    type Reach = Context<Self>;
+    type Message = Msg;
+    type Input = Request;
+    type Output = Output;
+
+    fn create(link: AgentLink<Self>) -> Self {
+        let local = StorageService::new(Area::Local).expect("Could not connect to LocalStorage");
+        let list = Self::restore(&local);
+        Self {
+            link,
+            subscribers: HashSet::new(),
+            local,
+            list,
+            load_req: None,
+            change_req: None,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) {
+        match msg {
+            // The boards from last time will do.
+            Msg::LoadFailed => self.load_req = None,
+            Msg::Loaded(list) => {
+                self.load_req = None;
+                self.change_req = None;
+                self.local.store(Self::KEY, Json(&list));
+                if list != self.list {
+                    self.list = list;
+                    self.broadcast(Output::List(self.list.clone()));
+                }
+            }
+            Msg::Changed => {
+                self.change_req = None;
+                self.fetch();
+            }
+            Msg::Failed(message) => {
+                self.change_req = None;
+                ConsoleService::error(&message);
+                self.broadcast(Output::Failed(message));
+            }
+        }
+    }
+
+    fn handle_input(&mut self, msg: Self::Input, caller: HandlerId) {
+        let (method, url, name, action) = match msg {
+            Request::Load => {
+                self.link.respond(caller, Output::List(self.list.clone()));
+                self.fetch();
+                return;
+            }
+            Request::Create { name } => ("POST", "/api/v1/boards".to_owned(), Some(name), "create"),
+            Request::Rename { slug, name } => (
+                "PUT",
+                format!("/api/v1/boards/{}", slug),
+                Some(name),
+                "rename",
+            ),
+            Request::Delete { slug } => {
+                ("DELETE", format!("/api/v1/boards/{}", slug), None, "delete")
+            }
+            Request::MakeDefault { slug } => (
+                "POST",
+                format!("/api/v1/boards/{}/default", slug),
+                None,
+                "change the default",
+            ),
+            Request::Leave { slug } => (
+                "DELETE",
+                format!("/api/v1/boards/{}/membership", slug),
+                None,
+                "leave",
+            ),
+        };
+        let body: Text = match name {
+            Some(name) => Json(&api::BoardName { name }).into(),
+            None => Nothing.into(),
+        };
+        let req = fetch::Request::builder()
+            .method(method)
+            .uri(url)
+            .header("Content-Type", "application/json")
+            .body(body)
+            .expect("could not build request");
+        self.send(req, action);
+    }
+
+    fn connected(&mut self, id: HandlerId) {
+        self.subscribers.insert(id);
+    }
+
+    fn disconnected(&mut self, id: HandlerId) {
+        self.subscribers.remove(&id);
+    }
+}
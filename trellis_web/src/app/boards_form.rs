@@ -0,0 +1,258 @@
+use super::boards;
+use super::AppRoute;
+use trellis_core::api;
+use trellis_core::board::Role;
+use yew::prelude::*;
+use yew_router::agent::{RouteAgentDispatcher, RouteRequest};
+use yew_router::route::Route;
+
+/// Renames, deletes, or makes default the board being edited, and adds new boards. Boards that
+/// someone else owns can only be made default or left.
+pub struct BoardsForm {
+    link: ComponentLink<Self>,
+    props: Props,
+    boards_service: Box<dyn Bridge<boards::Boards>>,
+    router: RouteAgentDispatcher<()>,
+    list: Option<api::BoardList>,
+    /// What's typed in as the board's new name.
+    name: String,
+    /// What's typed in as the name of a board to add.
+    new_name: String,
+    /// Whether this board is being deleted or left, so the default board's settings should be
+    /// shown once it's gone.
+    deleting: bool,
+    error: Option<String>,
+}
+
+#[derive(Properties, Clone, Debug)]
+pub struct Props {
+    /// The slug of the board being edited.
+    pub board: String,
+    /// What the user can do with the board being edited.
+    #[prop_or_default]
+    pub role: Role,
+}
+
+pub enum Msg {
+    Loaded(api::BoardList),
+    Failed(String),
+    Name(InputData),
+    NewName(InputData),
+    Rename(FocusEvent),
+    Create(FocusEvent),
+    MakeDefault,
+    Delete,
+    Leave,
+}
+
+impl Component for BoardsForm {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let mut boards_service =
+            boards::Boards::bridge(link.callback(|output: boards::Output| match output {
+                boards::Output::List(list) => Msg::Loaded(list),
+                boards::Output::Failed(message) => Msg::Failed(message),
+            }));
+        boards_service.send(boards::Request::Load);
+        Self {
+            link,
+            props,
+            boards_service,
+            router: RouteAgentDispatcher::new(),
+            list: None,
+            name: String::new(),
+            new_name: String::new(),
+            deleting: false,
+            error: None,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::Loaded(list) => {
+                let deleted = !list.boards.iter().any(|b| b.slug == self.props.board);
+                if self.deleting && deleted {
+                    self.deleting = false;
+                    self.router
+                        .send(RouteRequest::ChangeRoute(Route::from(AppRoute::Settings)));
+                }
+                self.list = Some(list);
+                self.name = self.current().map_or_else(String::new, |b| b.name.clone());
+                self.error = None;
+                true
+            }
+            Msg::Failed(message) => {
+                self.deleting = false;
+                self.error = Some(message);
+                true
+            }
+            Msg::Name(data) => {
+                self.name = data.value;
+                false
+            }
+            Msg::NewName(data) => {
+                self.new_name = data.value;
+                false
+            }
+            Msg::Rename(e) => {
+                e.prevent_default();
+                self.boards_service.send(boards::Request::Rename {
+                    slug: self.props.board.clone(),
+                    name: self.name.clone(),
+                });
+                false
+            }
+            Msg::Create(e) => {
+                e.prevent_default();
+                let name = std::mem::take(&mut self.new_name);
+                self.boards_service.send(boards::Request::Create { name });
+                true
+            }
+            Msg::MakeDefault => {
+                self.boards_service.send(boards::Request::MakeDefault {
+                    slug: self.props.board.clone(),
+                });
+                false
+            }
+            Msg::Delete => {
+                let confirmed = yew::utils::window()
+                    .confirm_with_message("Delete this board and its history?")
+                    .unwrap_or(false);
+                if !confirmed {
+                    return false;
+                }
+                self.deleting = true;
+                self.boards_service.send(boards::Request::Delete {
+                    slug: self.props.board.clone(),
+                });
+                false
+            }
+            Msg::Leave => {
+                let confirmed = yew::utils::window()
+                    .confirm_with_message(
+                        "Leave this board? Its owner will have to invite you again.",
+                    )
+                    .unwrap_or(false);
+                if !confirmed {
+                    return false;
+                }
+                self.deleting = true;
+                self.boards_service.send(boards::Request::Leave {
+                    slug: self.props.board.clone(),
+                });
+                false
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        self.name = self.current().map_or_else(String::new, |b| b.name.clone());
+        true
+    }
+
+    fn view(&self) -> Html {
+        let list = match &self.list {
+            Some(list) => list,
+            None => return html! {},
+        };
+        let is_default = list.default == self.props.board;
+        let owner = self.props.role == Role::Owner;
+        let only_board = list.boards.iter().filter(|b| b.role == Role::Owner).count() <= 1;
+        let delete_title = if only_board {
+            "You need at least one board."
+        } else {
+            ""
+        };
+        let error = match &self.error {
+            None => html! {},
+            Some(msg) => html! { <p class="text-red-500">{msg}</p> },
+        };
+
+        html! {
+            <div class="flex flex-col items-center space-y-1">
+                <span class="font-bold">{"Boards"}</span>
+                {error}
+                {
+                    if owner {
+                        html! {
+                            <form class="flex space-x-2" onsubmit=self.link.callback(Msg::Rename)>
+                                <label for="board_name" class="sr-only">{"Board name"}</label>
+                                <input
+                                    id="board_name"
+                                    type="text"
+                                    value=self.name.clone()
+                                    oninput=self.link.callback(Msg::Name)
+                                />
+                                <button type="submit">{"Rename"}</button>
+                            </form>
+                        }
+                    } else {
+                        let owner = self.current().and_then(|b| b.owner.clone()).unwrap_or_default();
+                        html! {
+                            <span>
+                                {format!("{}, shared by {} ({})", self.name, owner, self.props.role.label())}
+                            </span>
+                        }
+                    }
+                }
+                <div class="flex space-x-4">
+                    {
+                        if is_default {
+                            html! { <span class="text-gray-400">{"Shown by default"}</span> }
+                        } else {
+                            html! {
+                                <button type="button" onclick=self.link.callback(|_| Msg::MakeDefault)>
+                                    {"Show by default"}
+                                </button>
+                            }
+                        }
+                    }
+                    {
+                        if owner {
+                            html! {
+                                <button
+                                    type="button"
+                                    disabled=only_board
+                                    title=delete_title
+                                    onclick=self.link.callback(|_| Msg::Delete)
+                                >
+                                    {"Delete board"}
+                                </button>
+                            }
+                        } else {
+                            html! {
+                                <button type="button" onclick=self.link.callback(|_| Msg::Leave)>
+                                    {"Leave board"}
+                                </button>
+                            }
+                        }
+                    }
+                </div>
+                <form class="flex space-x-2" onsubmit=self.link.callback(Msg::Create)>
+                    <label for="new_board_name" class="sr-only">{"New board name"}</label>
+                    <input
+                        id="new_board_name"
+                        type="text"
+                        placeholder="New board"
+                        value=self.new_name.clone()
+                        oninput=self.link.callback(Msg::NewName)
+                    />
+                    <button type="submit">{"Add board"}</button>
+                </form>
+            </div>
+        }
+    }
+}
+
+impl BoardsForm {
+    fn current(&self) -> Option<&api::Board> {
+        self.list
+            .as_ref()?
+            .boards
+            .iter()
+            .find(|b| b.slug == self.props.board)
+    }
+}
@@ -1,44 +1,343 @@
-use chrono::{offset, DateTime, Local};
+use super::locale::locale;
+use chrono::{DateTime, Local, TimeZone, Timelike, Utc};
+use chrono_tz::{Tz, TZ_VARIANTS};
+use std::fmt;
 use std::time::Duration;
+use trellis_core::clock::Style;
+use trellis_core::config;
+use trellis_core::preferences::Preferences;
 use yew::prelude::*;
 use yew::services::interval::{IntervalService, IntervalTask};
 
 pub struct Clock {
-    time: DateTime<Local>,
+    props: Props,
+    time: DateTime<Utc>,
     #[allow(dead_code)]
     ticker: IntervalTask,
 }
 
+#[derive(Properties, Clone, Debug)]
+pub struct Props {
+    /// Without one, the clock shows the browser's time zone.
+    #[prop_or_default]
+    pub time_zone: Option<String>,
+    #[prop_or_default]
+    pub extra_zones: Vec<String>,
+    #[prop_or_default]
+    pub style: Style,
+    pub prefs: Preferences,
+}
+
 impl Component for Clock {
     type Message = ();
-    type Properties = ();
+    type Properties = Props;
 
-    fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
         Self {
-            time: Local::now(),
+            props,
+            time: Utc::now(),
             ticker: IntervalService::spawn(Duration::from_millis(1000), link.callback(|_| ())),
         }
     }
 
     fn update(&mut self, _msg: Self::Message) -> ShouldRender {
-        self.time = Local::now();
+        self.time = Utc::now();
         true
     }
 
-    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
-        false
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
     }
 
     fn view(&self) -> Html {
+        let main = match &self.props.time_zone {
+            None => self.view_main(self.time.with_timezone(&Local)),
+            Some(name) => match name.parse::<Tz>() {
+                Ok(tz) => self.view_main(self.time.with_timezone(&tz)),
+                Err(_) => html! {
+                    <>
+                        {unknown_zone(name)}
+                        {self.view_main(self.time.with_timezone(&Local))}
+                    </>
+                },
+            },
+        };
+
         html! {
             <div class="flex flex-col items-center justify-around w-full h-full">
-                <div class="text-3xl">{self.time.format("%A, %B %-d, %Y")}</div>
-                <div class="text-7xl">{self.time.format("%H:%M:%S")}</div>
+                {main}
+                {self.view_extra_zones()}
                 <div class="flex items-center justify-around self-stretch text-gray-400">
-                    <div data-clock-target="iso">{self.time.with_timezone(&offset::Utc).format("%Y-%m-%dT%H:%M:%S")}</div>
+                    <div data-clock-target="iso">{self.time.format("%Y-%m-%dT%H:%M:%S")}</div>
                     <div data-clock-target="unix">{self.time.format("%s")}</div>
                 </div>
             </div>
         }
     }
 }
+
+impl Clock {
+    fn view_main<Z: TimeZone>(&self, time: DateTime<Z>) -> Html
+    where
+        Z::Offset: fmt::Display,
+    {
+        let prefs = &self.props.prefs;
+        let locale = locale(prefs);
+        let face = match self.props.style {
+            Style::Digital => html! {
+                <div class="text-7xl">
+                    {time.format_localized(prefs.time_format.pattern(true), locale)}
+                </div>
+            },
+            Style::Analog => view_analog(time.hour(), time.minute(), time.second()),
+        };
+        // Say which zone this is, unless it's the viewer's own.
+        let zone = match &self.props.time_zone {
+            None => html! {},
+            Some(name) => html! {
+                <div class="text-gray-400">
+                    {format!("{} (UTC{})", zone_name(name), time.format("%:z"))}
+                </div>
+            },
+        };
+        html! {
+            <>
+                <div class="text-3xl">
+                    {time.format_localized(prefs.long_date_pattern(), locale)}
+                </div>
+                {face}
+                {zone}
+            </>
+        }
+    }
+
+    fn view_extra_zones(&self) -> Html {
+        if self.props.extra_zones.is_empty() {
+            return html! {};
+        }
+        let prefs = &self.props.prefs;
+        let locale = locale(prefs);
+        let rows = self
+            .props
+            .extra_zones
+            .iter()
+            .map(|name| match name.parse::<Tz>() {
+                Err(_) => html! { <li>{unknown_zone(name)}</li> },
+                Ok(tz) => {
+                    let time = self.time.with_timezone(&tz);
+                    html! {
+                        <li class="flex justify-between">
+                            <span>{zone_name(name)}</span>
+                            <span>
+                                {time.format_localized(prefs.time_format.pattern(false), locale)}
+                                <span class="text-gray-400">
+                                    {" "}
+                                    {time.format_localized("%a", locale)}
+                                    {format!(" UTC{}", time.format("%:z"))}
+                                </span>
+                            </span>
+                        </li>
+                    }
+                }
+            })
+            .collect::<Html>();
+        html! {
+            <ul class="self-stretch px-4 text-xl">{rows}</ul>
+        }
+    }
+}
+
+fn view_analog(hour: u32, minute: u32, second: u32) -> Html {
+    // Each hand points at its angle in degrees clockwise from 12, on a face 100 units across.
+    let hand = |angle: f64, length: u32, width: u32, class: &'static str| {
+        html! {
+            <line
+                x1="50"
+                y1="50"
+                x2="50"
+                y2=(50 - length).to_string()
+                stroke-width=width.to_string()
+                stroke-linecap="round"
+                class=class
+                transform=format!("rotate({} 50 50)", angle)
+            />
+        }
+    };
+    let ticks = (0..12)
+        .map(|i| {
+            // Longer marks at 12, 3, 6, and 9
+            let inner = if i % 3 == 0 { "12" } else { "8" };
+            html! {
+                <line
+                    x1="50"
+                    y1="4"
+                    x2="50"
+                    y2=inner
+                    stroke-width="2"
+                    transform=format!("rotate({} 50 50)", i * 30)
+                />
+            }
+        })
+        .collect::<Html>();
+    let second = second as f64;
+    let minute = minute as f64 + second / 60.0;
+    let hour = (hour % 12) as f64 + minute / 60.0;
+    html! {
+        <svg viewBox="0 0 100 100" class="h-48 w-48 stroke-current">
+            <circle cx="50" cy="50" r="48" fill="none" stroke-width="2" />
+            {ticks}
+            {hand(hour * 30.0, 24, 4, "")}
+            {hand(minute * 6.0, 36, 3, "")}
+            {hand(second * 6.0, 40, 1, "text-red-500")}
+        </svg>
+    }
+}
+
+/// Names a time zone by its city, like "New York" for `America/New_York`.
+fn zone_name(name: &str) -> String {
+    name.rsplit('/').next().unwrap_or(name).replace('_', " ")
+}
+
+fn unknown_zone(name: &str) -> Html {
+    html! { <div class="text-red-500">{format!("Unknown time zone: {}", name)}</div> }
+}
+
+pub struct ConfigForm {
+    props: ConfigFormProps,
+    link: ComponentLink<Self>,
+}
+
+#[derive(Properties, Clone, Debug)]
+pub struct ConfigFormProps {
+    pub time_zone: Option<String>,
+    pub extra_zones: Vec<String>,
+    pub style: Style,
+    /// Called with the tile's new `config::Data::Clock`.
+    pub onchange: Callback<config::Data>,
+}
+
+pub enum ConfigFormMsg {
+    ChooseStyle(ChangeData),
+    ChooseTimeZone(ChangeData),
+    AddZone(ChangeData),
+    RemoveZone(usize),
+}
+
+impl Component for ConfigForm {
+    type Message = ConfigFormMsg;
+    type Properties = ConfigFormProps;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self { props, link }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        let mut time_zone = self.props.time_zone.clone();
+        let mut extra_zones = self.props.extra_zones.clone();
+        let mut style = self.props.style;
+        match msg {
+            ConfigFormMsg::ChooseStyle(ChangeData::Select(elt)) => match elt.value().parse() {
+                Ok(s) => style = s,
+                Err(_) => return false,
+            },
+            ConfigFormMsg::ChooseTimeZone(ChangeData::Select(elt)) => {
+                let value = elt.value();
+                time_zone = if value.is_empty() { None } else { Some(value) };
+            }
+            ConfigFormMsg::AddZone(ChangeData::Select(elt)) => {
+                let value = elt.value();
+                // Go back to the prompt, ready to add another one.
+                elt.set_value("");
+                if value.is_empty() || extra_zones.contains(&value) {
+                    return false;
+                }
+                extra_zones.push(value);
+            }
+            ConfigFormMsg::RemoveZone(idx) => {
+                if idx >= extra_zones.len() {
+                    return false;
+                }
+                extra_zones.remove(idx);
+            }
+            _ => return false,
+        }
+        self.props.onchange.emit(config::Data::Clock {
+            time_zone,
+            extra_zones,
+            style,
+        });
+        true
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let style_options = Style::ALL
+            .iter()
+            .map(|s| {
+                html! {
+                    <option value=s.to_string() selected=*s == self.props.style>
+                        {s.name()}
+                    </option>
+                }
+            })
+            .collect::<Html>();
+        let selected = self.props.time_zone.as_deref();
+        let extra_zones = self
+            .props
+            .extra_zones
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let onclick = self.link.callback(move |_| ConfigFormMsg::RemoveZone(i));
+                html! {
+                    <li class="flex justify-between">
+                        <span>{name}</span>
+                        <button type="button" onclick=onclick>{"Remove"}</button>
+                    </li>
+                }
+            })
+            .collect::<Html>();
+
+        html! {
+            <div class="flex flex-col">
+                <label>
+                    {"Style"}
+                    <select onchange=self.link.callback(ConfigFormMsg::ChooseStyle)>
+                        {style_options}
+                    </select>
+                </label>
+                <label>
+                    {"Time zone"}
+                    <select onchange=self.link.callback(ConfigFormMsg::ChooseTimeZone)>
+                        <option value="" selected=selected.is_none()>{"This device's"}</option>
+                        {zone_options(selected)}
+                    </select>
+                </label>
+                <ul>{extra_zones}</ul>
+                <select onchange=self.link.callback(ConfigFormMsg::AddZone)>
+                    <option value="" selected=true>{"Add another time zone..."}</option>
+                    {zone_options(None)}
+                </select>
+            </div>
+        }
+    }
+}
+
+/// Lists every time zone in the bundled database.
+fn zone_options(selected: Option<&str>) -> Html {
+    TZ_VARIANTS
+        .iter()
+        .map(|tz| {
+            html! {
+                <option value=tz.name() selected=selected == Some(tz.name())>
+                    {tz.name()}
+                </option>
+            }
+        })
+        .collect::<Html>()
+}
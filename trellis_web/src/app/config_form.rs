@@ -1,8 +1,10 @@
 use super::add_tile_form::AddTileForm;
 use super::grid;
+use super::preferences_form::PreferencesForm;
 use super::secrets_form::SecretsForm;
-use super::weather;
+use super::{clock, feed, links, weather};
 use trellis_core::config;
+use trellis_core::preferences::Overrides;
 use uuid::Uuid;
 use yew::prelude::*;
 
@@ -27,7 +29,8 @@ pub enum Msg {
     SetHeight(Uuid, u32),
     SetWidth(Uuid, u32),
     ChangeSingle { id: Uuid, data: config::Data },
-    ChangeSecrets(config::Secrets),
+    SetPreferences(Overrides),
+    SetOverrides(Uuid, Overrides),
     Save,
 }
 
@@ -51,6 +54,7 @@ impl Component for ConfigForm {
                     id: Uuid::new_v4(),
                     width: None,
                     height: None,
+                    overrides: Overrides::default(),
                     data,
                 };
                 self.staged.tiles.push(tile);
@@ -76,6 +80,7 @@ impl Component for ConfigForm {
                             id: tile.id,
                             width: tile.width,
                             height: tile.height,
+                            overrides: tile.overrides.clone(),
                             data: data.clone(),
                         })
                     } else {
@@ -85,8 +90,17 @@ impl Component for ConfigForm {
                 self.staged.tiles = new_tiles;
                 true
             }
-            Msg::ChangeSecrets(secrets) => {
-                self.staged.secrets = secrets;
+            Msg::SetPreferences(values) => {
+                self.staged.preferences = self.staged.preferences.with_overrides(&values);
+                true
+            }
+            Msg::SetOverrides(id, overrides) => {
+                // TODO: Make this a method on Config
+                for tile in self.staged.tiles.iter_mut() {
+                    if tile.id == id {
+                        tile.overrides = overrides.clone();
+                    }
+                }
                 true
             }
             Msg::Save => {
@@ -122,7 +136,7 @@ impl Component for ConfigForm {
                 }
             }
             Msg::SetHeight(id, height) => {
-                if height < 1 {
+                if !(1..=config::MAX_TILE_SPAN).contains(&height) {
                     return false;
                 }
                 // TODO: Make this a method on Config
@@ -134,7 +148,7 @@ impl Component for ConfigForm {
                 true
             }
             Msg::SetWidth(id, width) => {
-                if width < 1 {
+                if !(1..=config::MAX_TILE_SPAN).contains(&width) {
                     return false;
                 }
                 // TODO: Make this a method on Config
@@ -148,8 +162,14 @@ impl Component for ConfigForm {
         }
     }
 
-    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
-        false
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        // Start over from the new config if it changed underneath us, such as after a restore.
+        let changed = props.config != self.props.config;
+        if changed {
+            self.staged = props.config.clone();
+        }
+        self.props = props;
+        changed
     }
 
     fn view(&self) -> Html {
@@ -163,12 +183,12 @@ impl Component for ConfigForm {
             },
         };
 
-        let onchange = self.link.callback(Self::Message::ChangeSecrets);
         let onsubmit = self.link.callback(|e: FocusEvent| {
             e.prevent_default();
             Self::Message::Save
         });
         let add_tile = self.link.callback(Self::Message::AddTile);
+        let set_preferences = self.link.callback(Self::Message::SetPreferences);
 
         html! {
             <>
@@ -180,7 +200,15 @@ impl Component for ConfigForm {
                     </div>
                 </grid::Grid>
                 <div class="w-4/5 mx-auto my-2 border-t border-gray-200 dark:border-gray-700"></div>
-                <SecretsForm secrets=self.staged.secrets.clone() onchange=onchange />
+                <div class="flex flex-col items-center">
+                    <span class="font-bold">{"Preferences"}</span>
+                    <PreferencesForm
+                        preferences=self.staged.preferences.clone()
+                        onchange=set_preferences
+                    />
+                </div>
+                <div class="w-4/5 mx-auto my-2 border-t border-gray-200 dark:border-gray-700"></div>
+                <SecretsForm />
                 <div class="w-4/5 mx-auto my-2 border-t border-gray-200 dark:border-gray-700"></div>
                 {errors}
                 <form class="w-full text-center" onsubmit=onsubmit>
@@ -196,23 +224,93 @@ impl ConfigForm {
     fn render_tile(&self, tile: config::Tile) -> Html {
         let id = tile.id.clone();
         let delete_tile = self.link.callback(move |_| Msg::DeleteTile(id));
+        // Notes and links don't show anything that preferences would change.
+        let overrides = match &tile.data {
+            config::Data::Note { .. } | config::Data::Links { .. } => html! {},
+            config::Data::Clock { .. }
+            | config::Data::Weather { .. }
+            | config::Data::Feed { .. } => html! {
+                <PreferencesForm
+                    preferences=self.staged.preferences.clone()
+                    overrides=Some(tile.overrides.clone())
+                    onchange=self.link.callback(move |o| Msg::SetOverrides(id, o))
+                />
+            },
+        };
         let inner = match &tile.data {
-            config::Data::Clock => html! { <p>{"(not configurable)"}</p> },
+            config::Data::Clock {
+                time_zone,
+                extra_zones,
+                style,
+            } => {
+                let onchange = self
+                    .link
+                    .callback(move |data| Msg::ChangeSingle { id, data });
+                html! {
+                    <clock::ConfigForm
+                        time_zone=time_zone.clone()
+                        extra_zones=extra_zones.clone()
+                        style=*style
+                        onchange=onchange
+                    />
+                }
+            }
             config::Data::Note { text: _ } => html! { <p>{"(not configurable)"}</p> },
-            config::Data::Weather { location_id } => {
-                let onchange = self.link.callback(move |lid: String| Msg::ChangeSingle {
-                    id,
-                    data: config::Data::Weather { location_id: lid },
-                });
+            config::Data::Links {
+                title,
+                links: tile_links,
+                new_tab,
+            } => {
+                let onchange = self
+                    .link
+                    .callback(move |data| Msg::ChangeSingle { id, data });
+                html! {
+                    <links::ConfigForm
+                        title=title.clone()
+                        links=tile_links.clone()
+                        new_tab=*new_tab
+                        onchange=onchange
+                    />
+                }
+            }
+            config::Data::Weather {
+                provider,
+                location_id,
+                location_name,
+                mode,
+            } => {
+                let onchange = self
+                    .link
+                    .callback(move |data| Msg::ChangeSingle { id, data });
+                html! {
+                    <weather::ConfigForm
+                        provider=*provider
+                        location_id=location_id.clone()
+                        location_name=location_name.clone()
+                        mode=*mode
+                        onchange=onchange
+                    />
+                }
+            }
+            config::Data::Feed { url, max_items } => {
+                let onchange = self
+                    .link
+                    .callback(move |data| Msg::ChangeSingle { id, data });
                 html! {
-                    <weather::ConfigForm location_id=location_id.clone() onchange=onchange />
+                    <feed::ConfigForm
+                        url=url.clone()
+                        max_items=*max_items
+                        onchange=onchange
+                    />
                 }
             }
         };
         let title = match &tile.data {
-            config::Data::Clock => "Clock",
+            config::Data::Clock { .. } => "Clock",
             config::Data::Note { text: _ } => "Note",
-            config::Data::Weather { location_id: _ } => "Weather",
+            config::Data::Weather { .. } => "Weather",
+            config::Data::Links { .. } => "Links",
+            config::Data::Feed { .. } => "Feed",
         };
 
         let height = tile.height.unwrap_or(1);
@@ -249,6 +347,7 @@ impl ConfigForm {
                     </div>
                 </div>
                 {inner}
+                {overrides}
                 <button onclick=delete_tile>{"Delete Tile"}</button>
             </div>
         }
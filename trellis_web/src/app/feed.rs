@@ -0,0 +1,280 @@
+use super::locale::locale;
+use super::weather::fetch;
+use chrono::{DateTime, Local, Utc};
+use std::time::Duration;
+use trellis_core::config;
+use trellis_core::feed::{self, Entry};
+use trellis_core::preferences::Preferences;
+use url::form_urlencoded;
+use yew::prelude::*;
+use yew::services::fetch::FetchTask;
+use yew::services::interval::{IntervalService, IntervalTask};
+
+/// Shows the latest entries of an RSS or Atom feed.
+pub struct Feed {
+    props: Props,
+    link: ComponentLink<Self>,
+    feed: Option<feed::Feed>,
+    #[allow(dead_code)]
+    ticker: IntervalTask,
+    fetch_task: Option<FetchTask>,
+    error: Option<anyhow::Error>,
+}
+
+#[derive(Properties, Clone, Debug, PartialEq)]
+pub struct Props {
+    pub url: String,
+    pub max_items: u32,
+    pub prefs: Preferences,
+    /// The token of the share link the tile is seen through, if it is. The feed is then fetched
+    /// through the link.
+    #[prop_or_default]
+    pub share: Option<String>,
+}
+
+pub enum Msg {
+    Fetch,
+    Receive(Result<feed::Feed, anyhow::Error>),
+}
+
+impl Component for Feed {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        link.send_message(Msg::Fetch);
+
+        Self {
+            props,
+            link: link.clone(),
+            feed: None,
+            // The server only asks the site every so often anyway.
+            ticker: IntervalService::spawn(Duration::from_secs(600), link.callback(|_| Msg::Fetch)),
+            fetch_task: None,
+            error: None,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::Fetch => {
+                if self.props.url.is_empty() {
+                    return false;
+                }
+
+                // The server fetches the feed, since most sites don't let browsers do it.
+                let query = form_urlencoded::Serializer::new(String::new())
+                    .append_pair("url", &self.props.url)
+                    .append_pair("max_items", &self.props.max_items.to_string())
+                    .finish();
+                let url = match &self.props.share {
+                    Some(token) => format!("/api/v1/shared/{}/feed?{}", token, query),
+                    None => format!("/api/v1/feed?{}", query),
+                };
+                self.fetch_task = Some(fetch(&self.link, url, Msg::Receive));
+                false
+            }
+            Msg::Receive(res) => {
+                self.fetch_task = None;
+                match res {
+                    Ok(feed) => {
+                        self.feed = Some(feed);
+                        self.error = None;
+                    }
+                    Err(error) => self.error = Some(error),
+                }
+                true
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        if self.props == props {
+            return false;
+        }
+        let refetch = self.props.url != props.url || self.props.max_items != props.max_items;
+        self.props = props;
+        if refetch {
+            self.feed = None;
+            self.error = None;
+            self.link.send_message(Msg::Fetch);
+        }
+        true
+    }
+
+    fn view(&self) -> Html {
+        let error = match &self.error {
+            Some(error) => html! { <p class="text-red-500">{error.to_string()}</p> },
+            None => html! {},
+        };
+        let body = match (&self.feed, self.props.url.is_empty()) {
+            (_, true) => html! { <p class="text-gray-400">{"No feed yet."}</p> },
+            (None, false) if self.error.is_none() => html! { <p>{"Loading..."}</p> },
+            (None, false) => html! {},
+            (Some(feed), false) => self.view_feed(feed),
+        };
+        html! {
+            <div class="w-full h-full overflow-auto p-2">
+                {body}
+                {error}
+            </div>
+        }
+    }
+}
+
+impl Feed {
+    fn view_feed(&self, feed: &feed::Feed) -> Html {
+        let title = if feed.title.is_empty() {
+            self.props.url.clone()
+        } else {
+            feed.title.clone()
+        };
+        let title = match &feed.link {
+            Some(link) => html! {
+                <a href=link.clone() target="_blank" rel="noopener noreferrer">{title}</a>
+            },
+            None => html! { {title} },
+        };
+        let entries = if feed.entries.is_empty() {
+            html! { <li class="text-gray-400">{"No entries."}</li> }
+        } else {
+            feed.entries
+                .iter()
+                .map(|entry| self.view_entry(entry))
+                .collect::<Html>()
+        };
+        html! {
+            <>
+                <h2 class="font-bold">{title}</h2>
+                <ul class="space-y-1">{entries}</ul>
+            </>
+        }
+    }
+
+    fn view_entry(&self, entry: &Entry) -> Html {
+        let title = if entry.title.is_empty() {
+            "(untitled)".to_owned()
+        } else {
+            entry.title.clone()
+        };
+        let title = match &entry.link {
+            Some(link) => html! {
+                <a href=link.clone() target="_blank" rel="noopener noreferrer">{title}</a>
+            },
+            None => html! { {title} },
+        };
+        let published = match entry.published {
+            Some(published) => html! {
+                <span class="ml-1 text-gray-400">{self.format_date(published)}</span>
+            },
+            None => html! {},
+        };
+        html! { <li>{title}{published}</li> }
+    }
+
+    /// Shows the time for today's entries, and the day for older ones.
+    fn format_date(&self, published: DateTime<Utc>) -> String {
+        let prefs = &self.props.prefs;
+        let published = published.with_timezone(&Local);
+        let pattern = if published.date() == Local::today() {
+            prefs.time_format.pattern(false)
+        } else {
+            prefs.short_date_pattern()
+        };
+        published
+            .format_localized(pattern, locale(prefs))
+            .to_string()
+    }
+}
+
+/// Chooses a feed tile's feed and how many of its entries to show.
+pub struct ConfigForm {
+    props: ConfigFormProps,
+    link: ComponentLink<Self>,
+}
+
+#[derive(Properties, Clone, Debug)]
+pub struct ConfigFormProps {
+    pub url: String,
+    pub max_items: u32,
+    /// Called with the tile's new `config::Data::Feed`.
+    pub onchange: Callback<config::Data>,
+}
+
+pub enum ConfigFormMsg {
+    SetUrl(ChangeData),
+    SetMaxItems(ChangeData),
+}
+
+impl Component for ConfigForm {
+    type Message = ConfigFormMsg;
+    type Properties = ConfigFormProps;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self { props, link }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        let mut url = self.props.url.clone();
+        let mut max_items = self.props.max_items;
+        match msg {
+            ConfigFormMsg::SetUrl(ChangeData::Value(value)) => {
+                let value = value.trim();
+                // Let feeds be typed without their scheme, like `example.com/feed.xml`.
+                url = if value.is_empty() || value.contains("://") {
+                    value.to_owned()
+                } else {
+                    format!("https://{}", value)
+                };
+            }
+            ConfigFormMsg::SetMaxItems(ChangeData::Value(value)) => match value.parse() {
+                Ok(value) => max_items = value,
+                Err(_) => return false,
+            },
+            _ => return false,
+        }
+        self.props
+            .onchange
+            .emit(config::Data::Feed { url, max_items });
+        true
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let invalid =
+            if self.props.url.is_empty() || trellis_core::links::host(&self.props.url).is_some() {
+                html! {}
+            } else {
+                html! { <p class="text-red-500">{"Not a web address"}</p> }
+            };
+        html! {
+            <div class="flex flex-col w-full">
+                <label>
+                    {"Feed URL"}
+                    <input
+                        type="text"
+                        class="w-full"
+                        placeholder="https://example.com/feed.xml"
+                        value=self.props.url.clone()
+                        onchange=self.link.callback(ConfigFormMsg::SetUrl)
+                    />
+                </label>
+                {invalid}
+                <label>
+                    {"Entries to show"}
+                    <input
+                        type="number"
+                        min="1"
+                        max=feed::MAX_ITEMS.to_string()
+                        value=self.props.max_items.to_string()
+                        onchange=self.link.callback(ConfigFormMsg::SetMaxItems)
+                    />
+                </label>
+            </div>
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use chrono::Local;
+use trellis_core::api;
+use uuid::Uuid;
+use yew::format::{Json, Nothing};
+use yew::prelude::*;
+use yew::services::console::ConsoleService;
+use yew::services::fetch::{FetchService, FetchTask, Request, Response};
+
+#[derive(Properties, Clone, Debug)]
+pub struct Props {
+    /// The slug of the board to list the history of.
+    pub board: String,
+    pub onrestore: Callback<()>,
+}
+
+pub struct HistoryList {
+    link: ComponentLink<Self>,
+    props: Props,
+    entries: Option<Vec<api::HistoryEntry>>,
+    fetch_task: Option<FetchTask>,
+    error: Option<String>,
+}
+
+pub enum Msg {
+    Fetch,
+    Fetched(Vec<api::HistoryEntry>),
+    Restore(Uuid),
+    Restored,
+    Failed(String),
+}
+
+impl Component for HistoryList {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        link.send_message(Msg::Fetch);
+        Self {
+            link,
+            props,
+            entries: None,
+            fetch_task: None,
+            error: None,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::Fetch => {
+                let req = Request::get(format!("/api/v1/boards/{}/history", self.props.board))
+                    .body(Nothing)
+                    .expect("could not build request");
+                let cb = self.link.callback(
+                    |res: Response<Json<anyhow::Result<Vec<api::HistoryEntry>>>>| {
+                        let Json(data) = res.into_body();
+                        match data {
+                            Ok(entries) => Msg::Fetched(entries),
+                            Err(err) => Msg::Failed(format!("could not load history: {}", err)),
+                        }
+                    },
+                );
+                let task = FetchService::fetch(req, cb).expect("could not start request");
+                self.fetch_task = Some(task);
+                false
+            }
+            Msg::Fetched(entries) => {
+                self.fetch_task = None;
+                self.entries = Some(entries);
+                self.error = None;
+                true
+            }
+            Msg::Restore(id) => {
+                let url = format!("/api/v1/boards/{}/history/{}/restore", self.props.board, id);
+                let req = Request::post(url)
+                    .body(Nothing)
+                    .expect("could not build request");
+                let cb = self.link.callback(|res: Response<anyhow::Result<String>>| {
+                    if res.status().is_success() {
+                        Msg::Restored
+                    } else {
+                        Msg::Failed("could not restore settings".to_owned())
+                    }
+                });
+                let task = FetchService::fetch(req, cb).expect("could not start request");
+                self.fetch_task = Some(task);
+                true
+            }
+            Msg::Restored => {
+                self.fetch_task = None;
+                self.props.onrestore.emit(());
+                self.link.send_message(Msg::Fetch);
+                false
+            }
+            Msg::Failed(err) => {
+                ConsoleService::error(&err);
+                self.fetch_task = None;
+                self.error = Some(err);
+                true
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        let switched = props.board != self.props.board;
+        self.props = props;
+        if switched {
+            self.entries = None;
+            self.error = None;
+            self.link.send_message(Msg::Fetch);
+        }
+        switched
+    }
+
+    fn view(&self) -> Html {
+        let body = match &self.entries {
+            None => html! { <p>{"Loading..."}</p> },
+            Some(entries) if entries.is_empty() => html! { <p>{"Nothing saved yet."}</p> },
+            Some(entries) => html! {
+                <ul>
+                    { for entries.iter().enumerate().map(|(i, e)| self.render_entry(i == 0, e)) }
+                </ul>
+            },
+        };
+        let error = match &self.error {
+            None => html! {},
+            Some(msg) => html! { <p class="text-red-500">{msg}</p> },
+        };
+
+        html! {
+            <div class="flex flex-col items-center">
+                <span class="font-bold">{"History"}</span>
+                {error}
+                {body}
+            </div>
+        }
+    }
+}
+
+impl HistoryList {
+    fn render_entry(&self, latest: bool, entry: &api::HistoryEntry) -> Html {
+        let id = entry.id;
+        let restore = self.link.callback(move |_| Msg::Restore(id));
+        let when = entry
+            .created_at
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S");
+        let action = if latest {
+            html! { <span class="text-gray-400">{"(current)"}</span> }
+        } else {
+            html! {
+                <button type="button" disabled=self.fetch_task.is_some() onclick=restore>
+                    {"Restore"}
+                </button>
+            }
+        };
+        html! {
+            <li class="flex space-x-4">
+                <span>{format!("#{}", entry.revision)}</span>
+                <span>{when}</span>
+                <span>{format!("{} tiles", entry.tile_count)}</span>
+                {action}
+            </li>
+        }
+    }
+}
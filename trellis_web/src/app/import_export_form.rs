@@ -0,0 +1,199 @@
+use trellis_core::api;
+use trellis_core::portable::Format;
+use yew::prelude::*;
+use yew::services::console::ConsoleService;
+use yew::services::fetch::{FetchService, FetchTask, Request, Response, StatusCode};
+use yew::services::reader::{File, FileData, ReaderService, ReaderTask};
+
+#[derive(Properties, Clone, Debug)]
+pub struct Props {
+    /// The slug of the board to export or import into.
+    pub board: String,
+    pub onimport: Callback<()>,
+}
+
+pub struct ImportExportForm {
+    link: ComponentLink<Self>,
+    props: Props,
+    include_secrets: bool,
+    file: Option<File>,
+    merge: bool,
+    reader_task: Option<ReaderTask>,
+    fetch_task: Option<FetchTask>,
+    error: Option<String>,
+}
+
+pub enum Msg {
+    ToggleSecrets,
+    ToggleMerge,
+    Choose(ChangeData),
+    Submit(FocusEvent),
+    Read(FileData),
+    Imported,
+    Failed(String),
+}
+
+impl Component for ImportExportForm {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self {
+            link,
+            props,
+            include_secrets: false,
+            file: None,
+            merge: false,
+            reader_task: None,
+            fetch_task: None,
+            error: None,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::ToggleSecrets => {
+                self.include_secrets = !self.include_secrets;
+                true
+            }
+            Msg::ToggleMerge => {
+                self.merge = !self.merge;
+                false
+            }
+            Msg::Choose(data) => {
+                if let ChangeData::Files(files) = data {
+                    self.file = files.get(0);
+                }
+                true
+            }
+            Msg::Submit(e) => {
+                e.prevent_default();
+                if let Some(file) = self.file.clone() {
+                    match ReaderService::read_file(file, self.link.callback(Msg::Read)) {
+                        Ok(task) => self.reader_task = Some(task),
+                        Err(err) => {
+                            self.link
+                                .send_message(Msg::Failed(format!("could not read file: {}", err)));
+                        }
+                    }
+                }
+                true
+            }
+            Msg::Read(data) => {
+                self.reader_task = None;
+                match String::from_utf8(data.content) {
+                    Ok(content) => self.upload(Format::from_file_name(&data.name), content),
+                    Err(_) => self
+                        .link
+                        .send_message(Msg::Failed("file is not a text file".to_owned())),
+                }
+                true
+            }
+            Msg::Imported => {
+                self.fetch_task = None;
+                self.error = None;
+                self.props.onimport.emit(());
+                true
+            }
+            Msg::Failed(err) => {
+                ConsoleService::error(&err);
+                self.reader_task = None;
+                self.fetch_task = None;
+                self.error = Some(err);
+                true
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        let switched = props.board != self.props.board;
+        self.props = props;
+        switched
+    }
+
+    fn view(&self) -> Html {
+        let busy = self.reader_task.is_some() || self.fetch_task.is_some();
+        let export_url = |format: Format| {
+            format!(
+                "/api/v1/boards/{}/export?format={}&secrets={}",
+                self.props.board, format, self.include_secrets
+            )
+        };
+        let error = match &self.error {
+            None => html! {},
+            Some(msg) => html! { <p class="text-red-500">{msg}</p> },
+        };
+
+        html! {
+            <div class="flex flex-col items-center space-y-1">
+                <span class="font-bold">{"Export"}</span>
+                <div class="flex space-x-4">
+                    <a href=export_url(Format::Json) download="">{"Download JSON"}</a>
+                    <a href=export_url(Format::Toml) download="">{"Download TOML"}</a>
+                </div>
+                <label>
+                    <input
+                        type="checkbox"
+                        checked=self.include_secrets
+                        onchange=self.link.callback(|_| Msg::ToggleSecrets)
+                    />
+                    {" Include secrets"}
+                </label>
+
+                <span class="font-bold">{"Import"}</span>
+                {error}
+                <form class="flex flex-col items-center space-y-1" onsubmit=self.link.callback(Msg::Submit)>
+                    <input type="file" accept=".json,.toml" onchange=self.link.callback(Msg::Choose) />
+                    <label>
+                        <input
+                            type="checkbox"
+                            checked=self.merge
+                            onchange=self.link.callback(|_| Msg::ToggleMerge)
+                        />
+                        {" Add to the current board instead of replacing it"}
+                    </label>
+                    <button type="submit" disabled=busy || self.file.is_none()>{"Import"}</button>
+                </form>
+            </div>
+        }
+    }
+}
+
+impl ImportExportForm {
+    fn upload(&mut self, format: Format, content: String) {
+        let mode = if self.merge { "merge" } else { "replace" };
+        let url = format!(
+            "/api/v1/boards/{}/import?format={}&mode={}",
+            self.props.board, format, mode
+        );
+        let req = Request::post(url)
+            .body(Ok(content))
+            .expect("could not build request");
+
+        let cb = self.link.callback(|res: Response<anyhow::Result<String>>| {
+            let (meta, body) = res.into_parts();
+            if meta.status.is_success() {
+                return Msg::Imported;
+            }
+            let message = if meta.status == StatusCode::UNPROCESSABLE_ENTITY
+                || meta.status == StatusCode::PAYLOAD_TOO_LARGE
+            {
+                match body.and_then(|b| Ok(serde_json::from_str::<api::Error>(&b)?)) {
+                    Ok(err) => {
+                        for problem in err.problems.iter() {
+                            ConsoleService::error(&problem.to_string());
+                        }
+                        format!("Could not import board: {}", err.message)
+                    }
+                    Err(_) => "Could not import board.".to_owned(),
+                }
+            } else {
+                "Could not import board.".to_owned()
+            };
+            Msg::Failed(message)
+        });
+
+        let task = FetchService::fetch(req, cb).expect("could not start request");
+        self.fetch_task = Some(task);
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::HashSet;
+use trellis_core::config;
+use trellis_core::links::{self, Link};
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+/// Shows a list of links, each with its site's icon if it asks for one.
+pub struct Links {
+    props: Props,
+    link: ComponentLink<Self>,
+    /// Sites whose icons couldn't be loaded, so they're shown without one.
+    missing_icons: HashSet<String>,
+}
+
+#[derive(Properties, Clone, Debug)]
+pub struct Props {
+    pub title: String,
+    pub links: Vec<Link>,
+    pub new_tab: bool,
+    /// The token of the share link the tile is seen through, if it is. Icons are then fetched
+    /// through the link.
+    #[prop_or_default]
+    pub share: Option<String>,
+}
+
+pub enum Msg {
+    IconFailed(String),
+}
+
+impl Component for Links {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self {
+            props,
+            link,
+            missing_icons: HashSet::new(),
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::IconFailed(host) => self.missing_icons.insert(host),
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let title = if self.props.title.is_empty() {
+            html! {}
+        } else {
+            html! { <h2 class="font-bold">{&self.props.title}</h2> }
+        };
+        let items = if self.props.links.is_empty() {
+            html! { <li class="text-gray-400">{"No links yet."}</li> }
+        } else {
+            self.props
+                .links
+                .iter()
+                .map(|l| self.render_link(l))
+                .collect::<Html>()
+        };
+        html! {
+            <div class="w-full h-full overflow-auto p-2">
+                {title}
+                <ul class="space-y-1">{items}</ul>
+            </div>
+        }
+    }
+}
+
+impl Links {
+    fn icon_url(&self, host: &str) -> String {
+        match &self.props.share {
+            Some(token) => format!("/api/v1/shared/{}/favicons/{}", token, host),
+            None => format!("/api/v1/favicons/{}", host),
+        }
+    }
+
+    fn render_link(&self, link: &Link) -> Html {
+        // Links that aren't web addresses can't be saved, but don't make them clickable if one
+        // turns up anyway.
+        let host = match links::host(&link.url) {
+            Some(host) => host,
+            None => return html! { <li>{link.text()}</li> },
+        };
+        let icon = if link.icon && !self.missing_icons.contains(&host) {
+            let failed = host.clone();
+            let onerror = self.link.callback(move |_| Msg::IconFailed(failed.clone()));
+            html! {
+                <img class="inline w-4 h-4 mr-1" src=self.icon_url(&host) alt="" onerror=onerror />
+            }
+        } else {
+            html! {}
+        };
+        let (target, rel) = if self.props.new_tab {
+            ("_blank", "noopener noreferrer")
+        } else {
+            ("", "")
+        };
+        html! {
+            <li>
+                <a href=link.url.clone() target=target rel=rel title=link.url.clone()>
+                    {icon}
+                    {link.text()}
+                </a>
+            </li>
+        }
+    }
+}
+
+/// Adds, edits, and reorders a links tile's links.
+pub struct ConfigForm {
+    props: ConfigFormProps,
+    link: ComponentLink<Self>,
+    label_ref: NodeRef,
+    url_ref: NodeRef,
+}
+
+#[derive(Properties, Clone, Debug)]
+pub struct ConfigFormProps {
+    pub title: String,
+    pub links: Vec<Link>,
+    pub new_tab: bool,
+    /// Called with the tile's new `config::Data::Links`.
+    pub onchange: Callback<config::Data>,
+}
+
+pub enum ConfigFormMsg {
+    SetTitle(ChangeData),
+    ToggleNewTab,
+    Add(FocusEvent),
+    SetLabel(usize, ChangeData),
+    SetUrl(usize, ChangeData),
+    ToggleIcon(usize),
+    MoveUp(usize),
+    MoveDown(usize),
+    Remove(usize),
+}
+
+/// Reads what was typed into a text input.
+fn typed(data: ChangeData) -> Option<String> {
+    match data {
+        ChangeData::Value(value) => Some(value.trim().to_owned()),
+        _ => None,
+    }
+}
+
+/// Lets links be typed without their scheme, like `example.com`.
+fn normalize_url(url: &str) -> String {
+    let url = url.trim();
+    if url.is_empty() || url.contains("://") {
+        url.to_owned()
+    } else {
+        format!("https://{}", url)
+    }
+}
+
+impl Component for ConfigForm {
+    type Message = ConfigFormMsg;
+    type Properties = ConfigFormProps;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self {
+            props,
+            link,
+            label_ref: NodeRef::default(),
+            url_ref: NodeRef::default(),
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        let mut title = self.props.title.clone();
+        let mut links = self.props.links.clone();
+        let mut new_tab = self.props.new_tab;
+        match msg {
+            ConfigFormMsg::SetTitle(data) => match typed(data) {
+                Some(value) => title = value,
+                None => return false,
+            },
+            ConfigFormMsg::ToggleNewTab => new_tab = !new_tab,
+            ConfigFormMsg::Add(e) => {
+                e.prevent_default();
+                let label_input = self.label_ref.cast::<HtmlInputElement>().unwrap();
+                let url_input = self.url_ref.cast::<HtmlInputElement>().unwrap();
+                let url = normalize_url(&url_input.value());
+                if url.is_empty() {
+                    return false;
+                }
+                links.push(Link {
+                    label: label_input.value().trim().to_owned(),
+                    url,
+                    icon: true,
+                });
+                label_input.set_value("");
+                url_input.set_value("");
+            }
+            ConfigFormMsg::SetLabel(idx, data) => match (links.get_mut(idx), typed(data)) {
+                (Some(link), Some(value)) => link.label = value,
+                _ => return false,
+            },
+            ConfigFormMsg::SetUrl(idx, data) => match (links.get_mut(idx), typed(data)) {
+                (Some(link), Some(value)) => link.url = normalize_url(&value),
+                _ => return false,
+            },
+            ConfigFormMsg::ToggleIcon(idx) => match links.get_mut(idx) {
+                Some(link) => link.icon = !link.icon,
+                None => return false,
+            },
+            ConfigFormMsg::MoveUp(idx) => {
+                if idx == 0 || idx >= links.len() {
+                    return false;
+                }
+                links.swap(idx - 1, idx);
+            }
+            ConfigFormMsg::MoveDown(idx) => {
+                if idx + 1 >= links.len() {
+                    return false;
+                }
+                links.swap(idx, idx + 1);
+            }
+            ConfigFormMsg::Remove(idx) => {
+                if idx >= links.len() {
+                    return false;
+                }
+                links.remove(idx);
+            }
+        }
+        self.props.onchange.emit(config::Data::Links {
+            title,
+            links,
+            new_tab,
+        });
+        true
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let rows = self
+            .props
+            .links
+            .iter()
+            .enumerate()
+            .map(|(i, link)| self.render_row(i, link))
+            .collect::<Html>();
+        let full = self.props.links.len() >= links::MAX_LINKS;
+
+        html! {
+            <div class="flex flex-col w-full">
+                <label>
+                    {"Title"}
+                    <input
+                        type="text"
+                        value=self.props.title.clone()
+                        onchange=self.link.callback(ConfigFormMsg::SetTitle)
+                    />
+                </label>
+                <label>
+                    <input
+                        type="checkbox"
+                        checked=self.props.new_tab
+                        onchange=self.link.callback(|_| ConfigFormMsg::ToggleNewTab)
+                    />
+                    {" Open links in a new tab"}
+                </label>
+                <ul>{rows}</ul>
+                <form class="flex" onsubmit=self.link.callback(ConfigFormMsg::Add)>
+                    <input type="text" class="w-1/3" placeholder="Label" ref=self.label_ref.clone() />
+                    <input
+                        type="text"
+                        class="w-full"
+                        placeholder="https://example.com"
+                        ref=self.url_ref.clone()
+                    />
+                    <button type="submit" disabled=full>{"Add"}</button>
+                </form>
+            </div>
+        }
+    }
+}
+
+impl ConfigForm {
+    fn render_row(&self, i: usize, link: &Link) -> Html {
+        let last = i + 1 == self.props.links.len();
+        let invalid = if links::host(&link.url).is_some() {
+            html! {}
+        } else {
+            html! { <p class="text-red-500">{"Not a web address"}</p> }
+        };
+        html! {
+            <li class="flex flex-col">
+                <div class="flex">
+                    <input
+                        type="text"
+                        class="w-1/3"
+                        aria-label="Label"
+                        value=link.label.clone()
+                        onchange=self.link.callback(move |data| ConfigFormMsg::SetLabel(i, data))
+                    />
+                    <input
+                        type="text"
+                        class="w-full"
+                        aria-label="URL"
+                        value=link.url.clone()
+                        onchange=self.link.callback(move |data| ConfigFormMsg::SetUrl(i, data))
+                    />
+                </div>
+                {invalid}
+                <div class="flex justify-between">
+                    <label>
+                        <input
+                            type="checkbox"
+                            checked=link.icon
+                            onchange=self.link.callback(move |_| ConfigFormMsg::ToggleIcon(i))
+                        />
+                        {" Icon"}
+                    </label>
+                    <div class="flex">
+                        <button
+                            type="button"
+                            disabled=i == 0
+                            onclick=self.link.callback(move |_| ConfigFormMsg::MoveUp(i))
+                        >
+                            {"↑"}
+                        </button>
+                        <button
+                            type="button"
+                            disabled=last
+                            onclick=self.link.callback(move |_| ConfigFormMsg::MoveDown(i))
+                        >
+                            {"↓"}
+                        </button>
+                        <button
+                            type="button"
+                            onclick=self.link.callback(move |_| ConfigFormMsg::Remove(i))
+                        >
+                            {"Remove"}
+                        </button>
+                    </div>
+                </div>
+            </li>
+        }
+    }
+}
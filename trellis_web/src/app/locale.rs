@@ -0,0 +1,13 @@
+use chrono::Locale;
+use std::convert::TryFrom;
+use trellis_core::preferences::Preferences;
+
+/// Finds the names of days and months for the preferred language. Chrono only knows languages
+/// with a region, so a bare language like `fr` is read as `fr-FR`. Anything chrono doesn't know
+/// at all gets American English.
+pub fn locale(prefs: &Preferences) -> Locale {
+    let name = prefs.locale.replace('-', "_");
+    Locale::try_from(name.as_str())
+        .or_else(|_| Locale::try_from(format!("{}_{}", name, name.to_uppercase()).as_str()))
+        .unwrap_or(Locale::en_US)
+}
@@ -0,0 +1,129 @@
+use serde_json::json;
+use web_sys::HtmlInputElement;
+use yew::format::Json;
+use yew::prelude::*;
+use yew::services::fetch::{FetchService, FetchTask, Request, Response, StatusCode};
+
+pub struct LoginPage {
+    link: ComponentLink<Self>,
+    email_ref: NodeRef,
+    state: State,
+    fetch_task: Option<FetchTask>,
+}
+
+enum State {
+    Ready,
+    Sending,
+    Sent(String),
+    Failed(String),
+}
+
+pub enum Msg {
+    Submit,
+    Sent(String),
+    Failed(String),
+}
+
+impl Component for LoginPage {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self {
+            link,
+            email_ref: NodeRef::default(),
+            state: State::Ready,
+            fetch_task: None,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::Submit => {
+                let email = self
+                    .email_ref
+                    .cast::<HtmlInputElement>()
+                    .unwrap()
+                    .value()
+                    .trim()
+                    .to_owned();
+                if email.is_empty() {
+                    return false;
+                }
+
+                let body = json!({ "email": email });
+                let req = Request::post("/api/v1/login")
+                    .header("Content-Type", "application/json")
+                    .body(Json(&body))
+                    .expect("could not build request");
+
+                let cb = self
+                    .link
+                    .callback(move |res: Response<anyhow::Result<String>>| {
+                        if res.status().is_success() {
+                            Msg::Sent(email.clone())
+                        } else if res.status() == StatusCode::TOO_MANY_REQUESTS {
+                            Msg::Failed(
+                                "Too many login links were asked for. Try again later.".to_owned(),
+                            )
+                        } else {
+                            Msg::Failed(
+                                "Could not send a login link. Is that email right?".to_owned(),
+                            )
+                        }
+                    });
+
+                let task = FetchService::fetch(req, cb).expect("could not start request");
+                self.fetch_task = Some(task);
+                self.state = State::Sending;
+                true
+            }
+            Msg::Sent(email) => {
+                self.fetch_task = None;
+                self.state = State::Sent(email);
+                true
+            }
+            Msg::Failed(err) => {
+                self.fetch_task = None;
+                self.state = State::Failed(err);
+                true
+            }
+        }
+    }
+
+    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
+        false
+    }
+
+    fn view(&self) -> Html {
+        if let State::Sent(email) = &self.state {
+            return html! {
+                <div class="mx-2 max-w-prose">
+                    <p class="text-xl">{"Check your email!"}</p>
+                    <p>{format!("We sent a login link to {}. It expires in a few minutes.", email)}</p>
+                </div>
+            };
+        }
+
+        let onsubmit = self.link.callback(|e: FocusEvent| {
+            e.prevent_default();
+            Msg::Submit
+        });
+        let error = match &self.state {
+            State::Failed(err) => html! { <p class="text-red-500">{err}</p> },
+            _ => html! {},
+        };
+        let sending = matches!(self.state, State::Sending);
+
+        html! {
+            <form class="mx-2 max-w-prose flex flex-col space-y-2" onsubmit=onsubmit>
+                <label>
+                    {"Email address"}
+                    <input type="email" class="w-full" required=true ref=self.email_ref.clone() />
+                </label>
+                {error}
+                <button type="submit" disabled=sending>{"Send me a login link"}</button>
+            </form>
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! Renders Markdown into virtual DOM nodes directly, rather than into an HTML string, so there's
+//! no markup to sanitize. Raw HTML in the source is shown as text, and only links that are
+//! relative or use a known-safe scheme get an `href`.
+
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use yew::prelude::*;
+use yew::virtual_dom::{VNode, VTag, VText};
+
+fn options() -> Options {
+    Options::ENABLE_TASKLISTS | Options::ENABLE_STRIKETHROUGH
+}
+
+/// Renders Markdown text. Task list checkboxes are numbered from zero in the order they appear,
+/// and `ontoggle` is called with that number when one is clicked.
+pub fn render(text: &str, ontoggle: &Callback<usize>) -> Html {
+    let mut stack = vec![VTag::new("div")];
+    let mut tasks = 0;
+    for event in Parser::new_ext(text, options()) {
+        let node = match event {
+            Event::Start(tag) => {
+                stack.push(open(&tag));
+                continue;
+            }
+            Event::End(_) => match stack.pop() {
+                Some(tag) => tag.into(),
+                None => continue,
+            },
+            Event::Text(text) | Event::Html(text) => VText::new(text.into_string()).into(),
+            Event::Code(code) => {
+                let mut tag = element("code", "font-mono bg-gray-100 dark:bg-gray-800");
+                tag.add_child(VText::new(code.into_string()).into());
+                tag.into()
+            }
+            Event::SoftBreak => VText::new("\n").into(),
+            Event::HardBreak => VTag::new("br").into(),
+            Event::Rule => element("hr", "my-2").into(),
+            Event::TaskListMarker(checked) => {
+                let n = tasks;
+                tasks += 1;
+                let onclick = ontoggle.reform(move |e: MouseEvent| {
+                    // The box gets redrawn from the saved text, so don't let it flip itself.
+                    e.prevent_default();
+                    n
+                });
+                html! { <input type="checkbox" class="mr-1" checked=checked onclick=onclick /> }
+            }
+            Event::FootnoteReference(_) => continue,
+        };
+        if let Some(parent) = stack.last_mut() {
+            parent.add_child(node);
+        }
+    }
+    // The parser balances every start with an end, so only the outer div is left.
+    stack
+        .into_iter()
+        .next()
+        .map_or_else(|| html! {}, VNode::from)
+}
+
+fn element(name: &'static str, class: &'static str) -> VTag {
+    let mut tag = VTag::new(name);
+    tag.add_attribute("class", class);
+    tag
+}
+
+fn open(tag: &Tag) -> VTag {
+    match tag {
+        Tag::Paragraph => element("p", "my-1"),
+        Tag::Heading(1) => element("h1", "text-3xl font-bold"),
+        Tag::Heading(2) => element("h2", "text-2xl font-bold"),
+        Tag::Heading(3) => element("h3", "text-xl font-bold"),
+        Tag::Heading(4) => element("h4", "text-lg font-bold"),
+        Tag::Heading(5) => element("h5", "font-bold"),
+        Tag::Heading(_) => element("h6", "font-bold"),
+        Tag::BlockQuote => element("blockquote", "pl-2 border-l-4 border-gray-300"),
+        Tag::CodeBlock(_) => element("pre", "p-2 font-mono bg-gray-100 dark:bg-gray-800"),
+        Tag::List(None) => element("ul", "pl-6 list-disc"),
+        Tag::List(Some(start)) => {
+            let mut list = element("ol", "pl-6 list-decimal");
+            list.add_attribute("start", start.to_string());
+            list
+        }
+        Tag::Item => VTag::new("li"),
+        Tag::Emphasis => VTag::new("em"),
+        Tag::Strong => VTag::new("strong"),
+        Tag::Strikethrough => VTag::new("del"),
+        // Images are shown as links to them, so that notes never load anything by themselves.
+        Tag::Link(_, url, title) | Tag::Image(_, url, title) => {
+            if !is_safe_url(url) {
+                return VTag::new("span");
+            }
+            let mut link = element("a", "text-blue-500 underline");
+            link.add_attribute("href", url.to_string());
+            link.add_attribute("target", "_blank");
+            link.add_attribute("rel", "noopener noreferrer");
+            if !title.is_empty() {
+                link.add_attribute("title", title.to_string());
+            }
+            link
+        }
+        // Tables and footnotes aren't enabled, so these never come up.
+        Tag::Table(_) | Tag::TableHead | Tag::TableRow | Tag::TableCell => VTag::new("div"),
+        Tag::FootnoteDefinition(_) => VTag::new("div"),
+    }
+}
+
+/// Whether a link is relative, or goes somewhere with a scheme that can't run script.
+fn is_safe_url(url: &str) -> bool {
+    let url = url.trim_start();
+    // A colon only starts a scheme if it comes before any path, query, or fragment.
+    match url.find([':', '/', '?', '#']) {
+        Some(i) if url[i..].starts_with(':') => {
+            let scheme = url[..i].to_ascii_lowercase();
+            matches!(scheme.as_str(), "http" | "https" | "mailto")
+        }
+        _ => true,
+    }
+}
+
+/// Checks the `n`th task in the text if it's unchecked, or unchecks it if it's checked. Tasks are
+/// numbered the same way as in `render`.
+pub fn toggle_task(text: &str, n: usize) -> String {
+    let marker = Parser::new_ext(text, options())
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::TaskListMarker(checked) => Some((checked, range)),
+            _ => None,
+        })
+        .nth(n);
+    let (checked, range) = match marker {
+        Some(marker) => marker,
+        None => return text.to_owned(),
+    };
+    // The marker is written as `[ ]` or `[x]`, but the range may include what's around it.
+    let start = match text[range.clone()].find('[') {
+        Some(i) => range.start + i,
+        None => return text.to_owned(),
+    };
+    let replacement = if checked { "[ ]" } else { "[x]" };
+    format!("{}{}{}", &text[..start], replacement, &text[start + 3..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_safe_urls() {
+        for url in &[
+            "https://example.com",
+            "HTTP://example.com",
+            "mailto:someone@example.com",
+            "  https://example.com",
+            "/boards",
+            "../notes?at=1:2",
+            "#top",
+            "notes/a:b",
+            "",
+        ] {
+            assert!(is_safe_url(url), "{:?}", url);
+        }
+        for url in &[
+            "javascript:alert(1)",
+            "JaVaScRiPt:alert(1)",
+            "  javascript:alert(1)",
+            "\tjavascript:alert(1)",
+            "\n\rjavascript:alert(1)",
+            "\u{1}javascript:alert(1)",
+            "java\tscript:alert(1)",
+            "data:text/html,<script>alert(1)</script>",
+            "vbscript:msgbox(1)",
+        ] {
+            assert!(!is_safe_url(url), "{:?}", url);
+        }
+    }
+
+    #[test]
+    fn test_toggle_task() {
+        let text = "- [ ] one\n- [x] two\n";
+        assert_eq!(toggle_task(text, 0), "- [x] one\n- [x] two\n");
+        assert_eq!(toggle_task(text, 1), "- [ ] one\n- [ ] two\n");
+    }
+
+    #[test]
+    fn test_toggle_nested_task() {
+        let text = "- [ ] one\n  - [ ] nested\n- [ ] two\n";
+        assert_eq!(
+            toggle_task(text, 1),
+            "- [ ] one\n  - [x] nested\n- [ ] two\n"
+        );
+        assert_eq!(
+            toggle_task(text, 2),
+            "- [ ] one\n  - [ ] nested\n- [x] two\n"
+        );
+    }
+
+    #[test]
+    fn test_toggle_task_skips_code() {
+        let text = "```\n- [ ] code\n```\n\n    - [ ] indented\n\n- [ ] task\n";
+        assert_eq!(
+            toggle_task(text, 0),
+            "```\n- [ ] code\n```\n\n    - [ ] indented\n\n- [x] task\n"
+        );
+    }
+
+    #[test]
+    fn test_toggle_missing_task() {
+        let text = "- [ ] one\n- not a task\n";
+        assert_eq!(toggle_task(text, 1), text);
+        assert_eq!(toggle_task(text, usize::MAX), text);
+        assert_eq!(toggle_task("", 0), "");
+    }
+}
@@ -0,0 +1,267 @@
+use trellis_core::api;
+use trellis_core::board::Role;
+use uuid::Uuid;
+use yew::format::{Json, Nothing, Text};
+use yew::prelude::*;
+use yew::services::console::ConsoleService;
+use yew::services::fetch::{FetchService, FetchTask, Request, Response};
+
+#[derive(Properties, Clone, Debug)]
+pub struct Props {
+    /// The slug of the board whose members to show.
+    pub board: String,
+}
+
+/// Lists the other people who can use a board, invites more of them, and changes what they can do.
+pub struct MembersForm {
+    link: ComponentLink<Self>,
+    props: Props,
+    members: Option<api::Members>,
+    /// What's typed in as the email address to invite.
+    email: String,
+    /// What the invited person will be able to do.
+    role: Role,
+    fetch_task: Option<FetchTask>,
+    error: Option<String>,
+}
+
+pub enum Msg {
+    Fetch,
+    Fetched(api::Members),
+    Email(InputData),
+    Role(ChangeData),
+    Invite(FocusEvent),
+    CancelInvite(Uuid),
+    ChangeRole(Uuid, ChangeData),
+    Remove(Uuid),
+    Failed(String),
+}
+
+/// Reads a role picked from a select.
+fn picked_role(data: ChangeData) -> Option<Role> {
+    match data {
+        ChangeData::Select(elt) => elt.value().parse().ok(),
+        _ => None,
+    }
+}
+
+impl Component for MembersForm {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        link.send_message(Msg::Fetch);
+        Self {
+            link,
+            props,
+            members: None,
+            email: String::new(),
+            role: Role::Viewer,
+            fetch_task: None,
+            error: None,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::Fetch => {
+                let req = Request::get(self.url("/members"))
+                    .body(Nothing.into())
+                    .expect("could not build request");
+                self.send(req, "load members");
+                false
+            }
+            Msg::Fetched(members) => {
+                self.fetch_task = None;
+                self.members = Some(members);
+                self.error = None;
+                true
+            }
+            Msg::Email(data) => {
+                self.email = data.value;
+                false
+            }
+            Msg::Role(data) => {
+                if let Some(role) = picked_role(data) {
+                    self.role = role;
+                }
+                false
+            }
+            Msg::Invite(e) => {
+                e.prevent_default();
+                let body = api::NewInvite {
+                    email: std::mem::take(&mut self.email),
+                    role: self.role,
+                };
+                let req = Request::post(self.url("/invites"))
+                    .header("Content-Type", "application/json")
+                    .body(Json(&body).into())
+                    .expect("could not build request");
+                self.send(req, "invite");
+                true
+            }
+            Msg::CancelInvite(id) => {
+                let req = Request::delete(self.url(&format!("/invites/{}", id)))
+                    .body(Nothing.into())
+                    .expect("could not build request");
+                self.send(req, "cancel invite");
+                true
+            }
+            Msg::ChangeRole(id, data) => {
+                let role = match picked_role(data) {
+                    Some(role) => role,
+                    None => return false,
+                };
+                let req = Request::put(self.url(&format!("/members/{}", id)))
+                    .header("Content-Type", "application/json")
+                    .body(Json(&api::MemberRole { role }).into())
+                    .expect("could not build request");
+                self.send(req, "change role");
+                true
+            }
+            Msg::Remove(id) => {
+                let req = Request::delete(self.url(&format!("/members/{}", id)))
+                    .body(Nothing.into())
+                    .expect("could not build request");
+                self.send(req, "remove member");
+                true
+            }
+            Msg::Failed(err) => {
+                ConsoleService::error(&err);
+                self.fetch_task = None;
+                self.error = Some(err);
+                true
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        let switched = props.board != self.props.board;
+        self.props = props;
+        if switched {
+            self.members = None;
+            self.error = None;
+            self.link.send_message(Msg::Fetch);
+        }
+        switched
+    }
+
+    fn view(&self) -> Html {
+        let body = match &self.members {
+            None => html! { <p>{"Loading..."}</p> },
+            Some(members) if members.members.is_empty() && members.invites.is_empty() => {
+                html! { <p>{"Nobody else can use this board."}</p> }
+            }
+            Some(members) => html! {
+                <ul>
+                    { for members.members.iter().map(|m| self.render_member(m)) }
+                    { for members.invites.iter().map(|i| self.render_invite(i)) }
+                </ul>
+            },
+        };
+        let error = match &self.error {
+            None => html! {},
+            Some(msg) => html! { <p class="text-red-500">{msg}</p> },
+        };
+
+        html! {
+            <div class="flex flex-col items-center space-y-1">
+                <span class="font-bold">{"Members"}</span>
+                {error}
+                {body}
+                <form class="flex space-x-2" onsubmit=self.link.callback(Msg::Invite)>
+                    <label for="invite_email" class="sr-only">{"Email address"}</label>
+                    <input
+                        id="invite_email"
+                        type="email"
+                        placeholder="Email address"
+                        value=self.email.clone()
+                        oninput=self.link.callback(Msg::Email)
+                    />
+                    <label for="invite_role" class="sr-only">{"Role"}</label>
+                    <select id="invite_role" onchange=self.link.callback(Msg::Role)>
+                        { self.role_options(self.role) }
+                    </select>
+                    <button type="submit" disabled=self.fetch_task.is_some()>{"Invite"}</button>
+                </form>
+                <p class="text-gray-400">
+                    {"Editors can change the board and its settings. Viewers can only look at it."}
+                </p>
+            </div>
+        }
+    }
+}
+
+impl MembersForm {
+    fn url(&self, rest: &str) -> String {
+        format!("/api/v1/boards/{}{}", self.props.board, rest)
+    }
+
+    /// Sends a request whose response is the board's members and invites.
+    fn send(&mut self, req: Request<Text>, action: &'static str) {
+        let cb = self.link.callback(move |res: Response<Text>| {
+            let (meta, body) = res.into_parts();
+            if meta.status.is_success() {
+                return match body.and_then(|b| Ok(serde_json::from_str(&b)?)) {
+                    Ok(members) => Msg::Fetched(members),
+                    Err(err) => Msg::Failed(format!("could not {}: {}", action, err)),
+                };
+            }
+            let reason = body
+                .and_then(|b| Ok(serde_json::from_str::<api::Error>(&b)?))
+                .map(|err| err.message)
+                .unwrap_or_else(|_| meta.status.to_string());
+            Msg::Failed(format!("Could not {}: {}", action, reason))
+        });
+        let task = FetchService::fetch(req, cb).expect("could not start request");
+        self.fetch_task = Some(task);
+    }
+
+    fn role_options(&self, selected: Role) -> Html {
+        Role::MEMBER_ROLES
+            .iter()
+            .map(|role| {
+                html! {
+                    <option value=role.as_str() selected=*role == selected>{role.label()}</option>
+                }
+            })
+            .collect()
+    }
+
+    fn render_member(&self, member: &api::Member) -> Html {
+        let id = member.user_id;
+        let onchange = self.link.callback(move |data| Msg::ChangeRole(id, data));
+        let remove = self.link.callback(move |_| Msg::Remove(id));
+        html! {
+            <li class="flex space-x-4">
+                <span>{&member.email}</span>
+                <select
+                    aria-label=format!("Role for {}", member.email)
+                    disabled=self.fetch_task.is_some()
+                    onchange=onchange
+                >
+                    { self.role_options(member.role) }
+                </select>
+                <button type="button" disabled=self.fetch_task.is_some() onclick=remove>
+                    {"Remove"}
+                </button>
+            </li>
+        }
+    }
+
+    fn render_invite(&self, invite: &api::Invite) -> Html {
+        let id = invite.id;
+        let cancel = self.link.callback(move |_| Msg::CancelInvite(id));
+        html! {
+            <li class="flex space-x-4">
+                <span>{&invite.email}</span>
+                <span class="text-gray-400">
+                    {format!("invited as {}", invite.role.label().to_lowercase())}
+                </span>
+                <button type="button" disabled=self.fetch_task.is_some() onclick=cancel>
+                    {"Cancel invite"}
+                </button>
+            </li>
+        }
+    }
+}
@@ -1,25 +1,48 @@
+use super::markdown;
 use std::time::Duration;
-use web_sys::HtmlTextAreaElement;
+use trellis_core::note::{Anchor, Site, Text};
+use uuid::Uuid;
+use wasm_bindgen::JsCast;
+use web_sys::{Element, HtmlTextAreaElement};
 use yew::prelude::*;
 use yew::services::timeout::{TimeoutService, TimeoutTask};
 
+/// Shows a note as Markdown, or as plain text for editing after it's clicked.
 pub struct Note {
     props: Props,
     link: ComponentLink<Self>,
     form_ref: NodeRef,
     textarea_ref: NodeRef,
     debounce: Option<TimeoutTask>,
+    /// This copy of the text, with edits that may not have been saved yet.
+    text: Text,
+    /// Who this copy's edits are from.
+    site: Site,
+    editing: bool,
+    /// Whether the textarea was just shown and should take focus once it's in the page.
+    focus: bool,
+    /// Where the selection in the textarea should go once merged edits are shown.
+    selection: Option<(Anchor, Anchor)>,
 }
 
 #[derive(Properties, Clone, Debug)]
 pub struct Props {
-    pub text: String,
-    pub onchange: Callback<String>,
+    /// The tile the note is on.
+    pub id: Uuid,
+    pub text: Text,
+    pub onchange: Callback<Text>,
+    /// Shows the note without letting it be edited.
+    #[prop_or_default]
+    pub readonly: bool,
 }
 
 pub enum Msg {
+    Edit(MouseEvent),
     Edited,
     Saved,
+    Preview,
+    /// Checks or unchecks a task from the preview.
+    Toggle(usize),
 }
 
 impl Component for Note {
@@ -28,17 +51,43 @@ impl Component for Note {
 
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
         Self {
+            text: props.text.clone(),
             props,
             link: link.clone(),
             textarea_ref: NodeRef::default(),
             form_ref: NodeRef::default(),
             debounce: None,
+            site: Uuid::new_v4().as_u128() as Site,
+            editing: false,
+            focus: false,
+            selection: None,
         }
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
+            Msg::Edit(_) | Msg::Toggle(_) if self.props.readonly => false,
+            Msg::Edit(e) => {
+                // Links and checkboxes do their own thing when clicked.
+                let on_control = e
+                    .target()
+                    .and_then(|t| t.dyn_into::<Element>().ok())
+                    .and_then(|elt| elt.closest("a, input").ok().flatten())
+                    .is_some();
+                if on_control {
+                    return false;
+                }
+                self.editing = true;
+                self.focus = true;
+                true
+            }
             Msg::Edited => {
+                let value = self
+                    .textarea_ref
+                    .cast::<HtmlTextAreaElement>()
+                    .unwrap()
+                    .value();
+                self.replace(&value);
                 // TODO: Prevent navigation while waiting for save
                 self.debounce = Some(TimeoutService::spawn(
                     Duration::from_millis(1000),
@@ -47,33 +96,121 @@ impl Component for Note {
                 true
             }
             Msg::Saved => {
-                self.debounce = None;
-                let text = self
-                    .textarea_ref
-                    .cast::<HtmlTextAreaElement>()
-                    .unwrap()
-                    .value();
-                self.props.onchange.emit(text);
+                self.save();
+                true
+            }
+            Msg::Preview => {
+                // Nothing more is being typed, so there's no need to wait out the debounce.
+                if self.debounce.is_some() {
+                    self.save();
+                }
+                self.editing = false;
+                true
+            }
+            Msg::Toggle(n) => {
+                let toggled = markdown::toggle_task(&self.text.to_string(), n);
+                self.replace(&toggled);
+                self.save();
                 true
             }
         }
     }
 
     fn change(&mut self, props: Self::Properties) -> ShouldRender {
-        // If a save is waiting, let it win.
-        //
-        // TODO: Maybe merge the text somehow?
-        match self.debounce {
-            Some(_) => false,
-            None => {
-                self.props = props;
-                true
+        if props.id != self.props.id {
+            // This is a different note now, so don't mix the two, but don't lose what's been
+            // typed in the old one either.
+            if self.debounce.is_some() {
+                self.save();
             }
+            self.text = props.text.clone();
+            self.props = props;
+            self.editing = false;
+            self.selection = None;
+            return true;
+        }
+
+        // Someone else's edits may have come in, so merge them with ours rather than letting
+        // either copy win, and keep the cursor next to the same characters.
+        let textarea = self.textarea_ref.cast::<HtmlTextAreaElement>();
+        let selection = textarea.as_ref().map(|textarea| {
+            let value = textarea.value();
+            let anchor = |offset: Result<Option<u32>, _>| {
+                let offset = offset.ok().flatten().unwrap_or(0);
+                self.text.anchor(char_index(&value, offset))
+            };
+            (
+                anchor(textarea.selection_start()),
+                anchor(textarea.selection_end()),
+            )
+        });
+        let before = self.text.to_string();
+        self.text.merge_since(&self.props.text, &props.text);
+        self.props = props;
+        let after = self.text.to_string();
+        if after == before {
+            return false;
+        }
+        if let Some(textarea) = textarea {
+            textarea.set_value(&after);
+            self.selection = selection;
+        }
+        true
+    }
+
+    fn rendered(&mut self, _first_render: bool) {
+        let textarea = match self.textarea_ref.cast::<HtmlTextAreaElement>() {
+            Some(textarea) => textarea,
+            None => return,
+        };
+        if self.focus {
+            self.focus = false;
+            let _ = textarea.focus();
+        }
+        if let Some((start, end)) = self.selection.take() {
+            let value = textarea.value();
+            let offset = |anchor| utf16_offset(&value, self.text.index(anchor));
+            let _ = textarea.set_selection_range(offset(start), offset(end));
         }
     }
 
     fn view(&self) -> Html {
-        let mut classes = classes!("w-full", "h-full", "resize-none", "border");
+        if self.editing {
+            self.view_editor()
+        } else {
+            self.view_preview()
+        }
+    }
+}
+
+impl Note {
+    /// Turns this copy's text into `value`, as edits by this copy's site.
+    fn replace(&mut self, value: &str) {
+        let old: Vec<char> = self.text.to_string().chars().collect();
+        let new: Vec<char> = value.chars().collect();
+        let prefix = old
+            .iter()
+            .zip(new.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let inserted: String = new[prefix..new.len() - suffix].iter().collect();
+        self.text
+            .splice(self.site, prefix, old.len() - prefix - suffix, &inserted);
+    }
+
+    fn save(&mut self) {
+        self.debounce = None;
+        self.props.onchange.emit(self.text.clone());
+    }
+
+    fn view_editor(&self) -> Html {
+        let mut classes = classes!("w-full", "flex-grow", "resize-none", "border");
         if self.debounce.is_some() {
             classes.extend(classes!(
                 "border-yellow-500",
@@ -83,17 +220,60 @@ impl Component for Note {
         }
         let input_callback = self.link.callback(|_| Msg::Edited);
         html! {
-            <form class="w-full h-full p-2" ref=self.form_ref.clone()>
+            <form class="flex flex-col w-full h-full p-2" ref=self.form_ref.clone()>
                 <label for="note_text" class="sr-only">{"Note text"}</label>
                 <textarea
                     id="note_text"
                     class=classes
                     ref=self.textarea_ref.clone()
                     oninput=input_callback
+                    value=self.text.to_string()
+                />
+                <button
+                    type="button"
+                    class="self-end mt-1"
+                    onclick=self.link.callback(|_| Msg::Preview)
                 >
-                    {self.props.text.clone()}
-                </textarea>
+                    {"Done"}
+                </button>
             </form>
         }
     }
+
+    fn view_preview(&self) -> Html {
+        let content = if self.text.is_empty() {
+            html! { <p class="text-gray-400">{"Click to write a note."}</p> }
+        } else {
+            markdown::render(&self.text.to_string(), &self.link.callback(Msg::Toggle))
+        };
+        if self.props.readonly {
+            return html! { <div class="w-full h-full p-2 overflow-auto">{content}</div> };
+        }
+        html! {
+            <div
+                class="w-full h-full p-2 overflow-auto cursor-text"
+                title="Click to edit"
+                onclick=self.link.callback(Msg::Edit)
+            >
+                {content}
+            </div>
+        }
+    }
+}
+
+/// Converts an offset into a string as the browser counts it, in UTF-16 code units, into a count
+/// of characters.
+fn char_index(s: &str, offset: u32) -> usize {
+    let mut units = 0;
+    s.chars()
+        .take_while(|c| {
+            units += c.len_utf16() as u32;
+            units <= offset
+        })
+        .count()
+}
+
+/// Converts a count of characters into an offset that the browser understands.
+fn utf16_offset(s: &str, index: usize) -> u32 {
+    s.chars().take(index).map(|c| c.len_utf16() as u32).sum()
 }
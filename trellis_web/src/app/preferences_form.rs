@@ -0,0 +1,182 @@
+use std::fmt;
+use std::str::FromStr;
+use trellis_core::preferences::{Overrides, Preferences, TimeFormat, Units};
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+/// Edits either the board's preferences or a single tile's overrides of them.
+///
+/// Either way, the form emits every value it holds as `Overrides`. For the board, every value is
+/// set, so the parent can apply them with `Preferences::with_overrides`.
+pub struct PreferencesForm {
+    props: Props,
+    link: ComponentLink<Self>,
+    locale_ref: NodeRef,
+}
+
+#[derive(Properties, Clone, Debug)]
+pub struct Props {
+    /// The board's preferences.
+    pub preferences: Preferences,
+    /// The tile's overrides, when editing a tile rather than the board.
+    #[prop_or_default]
+    pub overrides: Option<Overrides>,
+    pub onchange: Callback<Overrides>,
+}
+
+pub enum Msg {
+    ChooseUnits(ChangeData),
+    ChooseTimeFormat(ChangeData),
+    Locale,
+}
+
+impl Component for PreferencesForm {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self {
+            props,
+            link,
+            locale_ref: NodeRef::default(),
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        let mut values = self.values();
+        match msg {
+            Msg::ChooseUnits(data) => match choice(data) {
+                Some(units) => values.units = units,
+                None => return false,
+            },
+            Msg::ChooseTimeFormat(data) => match choice(data) {
+                Some(time_format) => values.time_format = time_format,
+                None => return false,
+            },
+            Msg::Locale => {
+                let locale = self.locale_ref.cast::<HtmlInputElement>().unwrap().value();
+                let locale = locale.trim();
+                values.locale = if locale.is_empty() && self.is_tile() {
+                    None
+                } else {
+                    Some(locale.to_owned())
+                };
+            }
+        }
+        self.props.onchange.emit(values);
+        true
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let values = self.values();
+        let prefs = &self.props.preferences;
+        // Tiles can go back to whatever the board uses.
+        let (units_default, time_format_default, locale_placeholder) = if self.is_tile() {
+            (
+                Some(format!("Board default ({})", prefs.units.name())),
+                Some(format!("Board default ({})", prefs.time_format.name())),
+                prefs.locale.clone(),
+            )
+        } else {
+            (None, None, String::new())
+        };
+
+        html! {
+            <div class="flex flex-col">
+                <label>
+                    {"Units"}
+                    <select onchange=self.link.callback(Msg::ChooseUnits)>
+                        {options(Units::ALL, Units::name, values.units, units_default)}
+                    </select>
+                </label>
+                <label>
+                    {"Time"}
+                    <select onchange=self.link.callback(Msg::ChooseTimeFormat)>
+                        {options(
+                            TimeFormat::ALL,
+                            TimeFormat::name,
+                            values.time_format,
+                            time_format_default,
+                        )}
+                    </select>
+                </label>
+                <label>
+                    {"Language"}
+                    <input
+                        type="text"
+                        value=values.locale.unwrap_or_default()
+                        placeholder=locale_placeholder
+                        ref=self.locale_ref.clone()
+                        oninput=self.link.callback(|_: InputData| Msg::Locale)
+                    />
+                </label>
+            </div>
+        }
+    }
+}
+
+impl PreferencesForm {
+    fn is_tile(&self) -> bool {
+        self.props.overrides.is_some()
+    }
+
+    fn values(&self) -> Overrides {
+        match &self.props.overrides {
+            Some(overrides) => overrides.clone(),
+            None => {
+                let prefs = &self.props.preferences;
+                Overrides {
+                    units: Some(prefs.units),
+                    time_format: Some(prefs.time_format),
+                    locale: Some(prefs.locale.clone()),
+                }
+            }
+        }
+    }
+}
+
+/// Reads a choice from a select box, where the empty value means no choice. Returns `None` if the
+/// value can't be read at all.
+fn choice<T: FromStr>(data: ChangeData) -> Option<Option<T>> {
+    match data {
+        ChangeData::Select(elt) => match elt.value().as_str() {
+            "" => Some(None),
+            value => value.parse().ok().map(Some),
+        },
+        _ => None,
+    }
+}
+
+/// Lists the options for a select box, with an optional empty option first.
+fn options<T: Copy + PartialEq + fmt::Display>(
+    all: &[T],
+    name: fn(T) -> &'static str,
+    selected: Option<T>,
+    default: Option<String>,
+) -> Html {
+    let default = match default {
+        None => html! {},
+        Some(label) => html! { <option value="" selected=selected.is_none()>{label}</option> },
+    };
+    let choices = all
+        .iter()
+        .map(|v| {
+            html! {
+                <option value=v.to_string() selected=selected == Some(*v)>
+                    {name(*v)}
+                </option>
+            }
+        })
+        .collect::<Html>();
+    html! {
+        <>
+            {default}
+            {choices}
+        </>
+    }
+}
@@ -1,65 +1,103 @@
-use trellis_core::config;
+use trellis_core::{api, config};
 use web_sys::HtmlInputElement;
+use yew::format::{Json, Nothing};
 use yew::prelude::*;
+use yew::services::console::ConsoleService;
+use yew::services::fetch::{FetchService, FetchTask, Request, Response};
 
+/// Sets and removes secrets. The server never sends secrets back, so this only shows whether each
+/// one is configured.
 pub struct SecretsForm {
     link: ComponentLink<Self>,
-    props: Props,
+    status: Option<api::SecretsStatus>,
     owm_api_key_ref: NodeRef,
-}
-
-#[derive(Properties, Clone, Debug)]
-pub struct Props {
-    pub secrets: config::Secrets,
-    pub onchange: Callback<config::Secrets>,
+    fetch_task: Option<FetchTask>,
+    error: Option<String>,
 }
 
 pub enum Msg {
-    Input,
+    Fetch,
+    Fetched(api::SecretsStatus),
     Submit,
+    Remove,
+    Saved,
+    Failed(String),
 }
 
 impl Component for SecretsForm {
     type Message = Msg;
-    type Properties = Props;
+    type Properties = ();
 
-    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+    fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        link.send_message(Msg::Fetch);
         Self {
             link,
-            props,
+            status: None,
             owm_api_key_ref: NodeRef::default(),
+            fetch_task: None,
+            error: None,
         }
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
-            Msg::Submit => {
-                self.props.onchange.emit(self.props.secrets.clone());
+            Msg::Fetch => {
+                let req = Request::get("/api/v1/secrets")
+                    .body(Nothing)
+                    .expect("could not build request");
+                let cb = self.link.callback(
+                    |res: Response<Json<anyhow::Result<api::SecretsStatus>>>| {
+                        let Json(data) = res.into_body();
+                        match data {
+                            Ok(status) => Msg::Fetched(status),
+                            Err(err) => Msg::Failed(format!("could not load secrets: {}", err)),
+                        }
+                    },
+                );
+                let task = FetchService::fetch(req, cb).expect("could not start request");
+                self.fetch_task = Some(task);
                 false
             }
-            Msg::Input => {
-                let owm_api_key = self
-                    .owm_api_key_ref
-                    .cast::<HtmlInputElement>()
-                    .unwrap()
-                    .value()
-                    .trim()
-                    .to_owned();
-                let mut secrets = self.props.secrets.clone();
-                secrets.owm_api_key = if owm_api_key.is_empty() {
-                    None
-                } else {
-                    Some(owm_api_key)
-                };
-                self.props.onchange.emit(secrets);
+            Msg::Fetched(status) => {
+                self.fetch_task = None;
+                self.status = Some(status);
+                self.error = None;
+                true
+            }
+            Msg::Submit => {
+                let input = self.owm_api_key_ref.cast::<HtmlInputElement>().unwrap();
+                let owm_api_key = input.value().trim().to_owned();
+                if owm_api_key.is_empty() {
+                    return false;
+                }
+                input.set_value("");
+                self.save(config::Secrets {
+                    owm_api_key: Some(owm_api_key),
+                });
+                true
+            }
+            Msg::Remove => {
+                // An empty value tells the server to forget the secret.
+                self.save(config::Secrets {
+                    owm_api_key: Some(String::new()),
+                });
+                true
+            }
+            Msg::Saved => {
+                self.link.send_message(Msg::Fetch);
                 false
             }
+            Msg::Failed(err) => {
+                ConsoleService::error(&err);
+                self.fetch_task = None;
+                self.error = Some(err);
+                true
+            }
         }
     }
 
-    fn change(&mut self, props: Self::Properties) -> ShouldRender {
-        self.props = props;
-        true
+    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
+        false
     }
 
     fn view(&self) -> Html {
@@ -67,25 +105,63 @@ impl Component for SecretsForm {
             e.prevent_default();
             Msg::Submit
         });
-
-        let oninput = self.link.callback(|_: InputData| Msg::Input);
+        let busy = self.fetch_task.is_some();
+        let configured = self.status.as_ref().map(|s| s.owm_api_key);
+        let status = match configured {
+            None => html! { <span class="text-gray-400">{"Loading..."}</span> },
+            Some(true) => html! {
+                <span>
+                    {"Configured "}
+                    <button type="button" disabled=busy onclick=self.link.callback(|_| Msg::Remove)>
+                        {"Remove"}
+                    </button>
+                </span>
+            },
+            Some(false) => html! { <span class="text-gray-400">{"Not set"}</span> },
+        };
+        let error = match &self.error {
+            None => html! {},
+            Some(msg) => html! { <p class="text-red-500">{msg}</p> },
+        };
 
         html! {
             <form
                 class="flex flex-col items-center justify-around w-full h-full"
                 onsubmit=onsubmit
             >
+                {error}
                 <label>
                     <a href="https://home.openweathermap.org/api_keys">{"OpenWeatherMap API Key"}</a>
-                    <input
-                        type="text"
-                        class="w-full"
-                        value=self.props.secrets.owm_api_key.clone()
-                        ref=self.owm_api_key_ref.clone()
-                        oninput=oninput
-                    />
+                    {" "}{status}
+                    <div class="flex">
+                        <input
+                            type="password"
+                            class="w-full"
+                            placeholder=if configured == Some(true) { "Replace key" } else { "" }
+                            ref=self.owm_api_key_ref.clone()
+                        />
+                        <button type="submit" disabled=busy>{"Save"}</button>
+                    </div>
                 </label>
             </form>
         }
     }
 }
+
+impl SecretsForm {
+    fn save(&mut self, changes: config::Secrets) {
+        let req = Request::put("/api/v1/secrets")
+            .header("Content-Type", "application/json")
+            .body(Json(&changes))
+            .expect("could not build request");
+        let cb = self.link.callback(|res: Response<anyhow::Result<String>>| {
+            if res.status().is_success() {
+                Msg::Saved
+            } else {
+                Msg::Failed("could not save secrets".to_owned())
+            }
+        });
+        let task = FetchService::fetch(req, cb).expect("could not start request");
+        self.fetch_task = Some(task);
+    }
+}
@@ -1,106 +1,397 @@
 use anyhow;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
-use trellis_core::config;
+use std::time::Duration;
+use trellis_core::outbox::{self, Outbox, Response, Status};
+use trellis_core::{api, board, config, migrate};
 use uuid;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{Event, EventSource, MessageEvent};
 use yew::format::{Json, Nothing};
 use yew::services::console::ConsoleService;
 use yew::services::fetch;
 use yew::services::storage::{Area, StorageService};
+use yew::services::timeout::{TimeoutService, TimeoutTask};
 use yew::worker::*;
+use yew::Callback;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum Request {
     Save(config::Config),
-    Load,
-    SaveSingle { id: uuid::Uuid, data: config::Data },
-    SaveSecrets { secrets: config::Secrets },
+    /// Asks for a board's settings, by slug, and loads them from the server again. Only one board
+    /// is open at a time, so this switches boards if it's a different one.
+    Load(String),
+    SaveSingle {
+        id: uuid::Uuid,
+        data: config::Data,
+    },
+}
+
+/// What subscribers are told about the settings.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum Output {
+    /// The settings changed, or a subscriber asked for them.
+    Config(config::Config),
+    /// Whether the settings have reached the server changed.
+    Status(Status),
 }
 
 pub struct Settings {
     link: AgentLink<Self>,
     subscribers: HashSet<HandlerId>,
     local: StorageService,
-    settings: config::Config,
+    /// The slug of the open board.
+    board: Option<String>,
+    /// The open board's settings, along with whatever edits haven't been saved yet. Kept in
+    /// LocalStorage so that unsaved edits outlive the page.
+    outbox: Outbox,
+    /// Whether `outbox` holds the open board yet. Boards this device hasn't seen before have to
+    /// come from the server first.
+    loaded: bool,
     save_req: Option<fetch::FetchTask>,
     load_req: Option<fetch::FetchTask>,
+    /// Waits to try a failed save again.
+    save_retry: Option<TimeoutTask>,
+    /// Waits to try a failed load again.
+    load_retry: Option<TimeoutTask>,
+    load_failures: u32,
+    /// Tells us when the settings are saved somewhere else.
+    events: Option<Events>,
+    /// Tries saving again as soon as the browser is back online.
+    #[allow(dead_code)]
+    on_online: Option<Closure<dyn FnMut(Event)>>,
+}
+
+/// An open stream of pushed settings, and the listeners that have to live as long as it does.
+struct Events {
+    source: EventSource,
+    #[allow(dead_code)]
+    on_settings: Closure<dyn FnMut(MessageEvent)>,
+    #[allow(dead_code)]
+    on_open: Closure<dyn FnMut(Event)>,
+}
+
+impl Drop for Events {
+    fn drop(&mut self) {
+        self.source.close();
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum Msg {
-    Saved,
-    Loaded(config::Config),
+    /// A save finished, one way or another.
+    Responded(Response),
+    Loaded(api::Snapshot),
+    LoadFailed,
+    /// The settings were saved somewhere, maybe here.
+    Pushed(api::Snapshot),
+    /// The event stream came back after dropping, so pushes may have been missed.
+    Reconnected,
+    /// It's time to try a failed save again.
+    RetrySave,
+    /// It's time to try a failed load again.
+    RetryLoad,
     Noop,
 }
 
 // TODO: Skip all the remote save/load stuff unless logged in
 
 impl Settings {
-    const KEY: &'static str = "trellis.settings";
+    /// Where the settings were kept before there were multiple boards.
+    const UNSCOPED_KEY: &'static str = "trellis.outbox";
+    /// Where the settings were kept before unsaved edits were tracked.
+    const OLD_KEY: &'static str = "trellis.settings";
+
+    fn key(board: &str) -> String {
+        format!("trellis.outbox.{}", board)
+    }
+
+    fn url(&self, action: &str) -> String {
+        format!(
+            "/api/v1/boards/{}/{}",
+            self.board.as_deref().unwrap_or(board::DEFAULT_SLUG),
+            action
+        )
+    }
 
-    fn broadcast(&self, s: config::Config) {
+    fn broadcast(&self, output: Output) {
         for sub in self.subscribers.iter() {
-            self.link.respond(*sub, s.clone());
+            self.link.respond(*sub, output.clone());
+        }
+    }
+
+    /// Reads the board as this device last had it, if it has seen the board before.
+    fn restore(local: &mut StorageService, board: &str) -> Option<Outbox> {
+        let read = |local: &StorageService, key: &str| {
+            local
+                .restore::<anyhow::Result<String>>(key)
+                .and_then(|s| Ok(Outbox::restore(&s)?))
+        };
+        if let Ok(outbox) = read(local, &Self::key(board)) {
+            return Some(outbox);
+        }
+        if board != board::DEFAULT_SLUG {
+            return None;
+        }
+
+        // Settings from before there were multiple boards are on the default board now.
+        if let Ok(outbox) = read(local, Self::UNSCOPED_KEY) {
+            local.remove(Self::UNSCOPED_KEY);
+            return Some(outbox);
+        }
+        let config = local
+            .restore::<anyhow::Result<String>>(Self::OLD_KEY)
+            .and_then(|s| Ok(migrate::from_str(&s)?))
+            .unwrap_or_default();
+        local.remove(Self::OLD_KEY);
+        Some(Outbox::new(api::Snapshot {
+            revision: 0,
+            config,
+        }))
+    }
+
+    /// Switches to another board. Edits to the board that was open stay in LocalStorage, and are
+    /// sent the next time it's opened.
+    fn open(&mut self, board: String) {
+        self.save_req = None;
+        self.load_req = None;
+        self.save_retry = None;
+        self.load_retry = None;
+        self.load_failures = 0;
+        self.events = None;
+        match Self::restore(&mut self.local, &board) {
+            Some(outbox) => {
+                self.outbox = outbox;
+                self.loaded = true;
+                self.broadcast(Output::Config(self.outbox.config().clone()));
+                self.broadcast(Output::Status(self.outbox.status()));
+            }
+            None => {
+                self.outbox = Outbox::default();
+                self.loaded = false;
+            }
+        }
+        self.board = Some(board);
+        self.flush();
+    }
+
+    /// Takes in a copy of the open board from the server.
+    fn accept(&mut self, snapshot: api::Snapshot) {
+        if self.loaded {
+            self.apply(|outbox| outbox.receive(snapshot));
+        } else {
+            self.apply(|outbox| {
+                *outbox = Outbox::new(snapshot);
+                Vec::new()
+            });
+        }
+    }
+
+    /// Changes the outbox, then stores it, tells subscribers what changed, and sends whatever
+    /// needs saving.
+    fn apply(&mut self, f: impl FnOnce(&mut Outbox) -> Vec<config::Conflict>) {
+        let board = match &self.board {
+            Some(board) => board.clone(),
+            None => return,
+        };
+        // Subscribers haven't been shown anything for a board that's only just arrived.
+        let shown = if self.loaded {
+            Some((self.outbox.config().clone(), self.outbox.status()))
+        } else {
+            None
+        };
+
+        for conflict in f(&mut self.outbox).iter() {
+            ConsoleService::warn(&format!("settings conflict: {:?}", conflict));
+        }
+        self.loaded = true;
+        self.local.store(&Self::key(&board), Json(&self.outbox));
+
+        if !matches!(&shown, Some((config, _)) if config == self.outbox.config()) {
+            self.broadcast(Output::Config(self.outbox.config().clone()));
         }
+        if !matches!(shown, Some((_, status)) if status == self.outbox.status()) {
+            self.broadcast(Output::Status(self.outbox.status()));
+        }
+        self.flush();
     }
 
-    fn save(&mut self, s: &config::Config) {
-        self.settings = s.clone();
-        self.local.store(Self::KEY, Json(s));
+    /// Sends the next save, unless one is on its way or a failed one is waiting to be retried.
+    fn flush(&mut self) {
+        if self.save_req.is_some() || self.save_retry.is_some() {
+            return;
+        }
+        // The last save failed, so give the server or the connection some time first.
+        if let Some(delay) = self.outbox.retry_after() {
+            self.save_retry = Some(Self::wait(delay, self.link.callback(|()| Msg::RetrySave)));
+            return;
+        }
+        self.send();
+    }
 
-        let req = fetch::Request::post("/api/v1/save")
+    /// Sends the next save right away.
+    fn send(&mut self) {
+        if self.save_req.is_some() || !self.loaded {
+            return;
+        }
+        let snapshot = match self.outbox.next_save() {
+            Some(snapshot) => snapshot,
+            None => return,
+        };
+        let req = fetch::Request::post(self.url("save"))
             .header("Content-Type", "application/json")
-            .body(Json(s))
+            .body(Json(&snapshot))
             .expect("could not build request");
 
         let cb = self
             .link
             .callback(|res: fetch::Response<anyhow::Result<String>>| {
-                if res.status().is_success() {
-                    Msg::Saved
-                } else {
-                    // TODO: Try again later?
-                    ConsoleService::error("could not save settings");
-                    Msg::Saved
-                }
+                Msg::Responded(read_save_response(res))
             });
-
-        let task = fetch::FetchService::fetch(req, cb).expect("could not start request");
-        self.save_req = Some(task);
+        match fetch::FetchService::fetch(req, cb) {
+            Ok(task) => self.save_req = Some(task),
+            Err(err) => {
+                ConsoleService::error(&format!("could not save settings: {}", err));
+                self.link.send_message(Msg::Responded(Response::Failed));
+            }
+        }
     }
 
-    fn load(&mut self) -> config::Config {
-        // Load local settings early to make things interactive faster.
-        self.settings = self
-            .local
-            .restore::<Json<anyhow::Result<config::Config>>>(Self::KEY)
-            .0
-            .unwrap_or_default();
-
-        // Load remote settings in the background and accept the flash of stale layout.  Ideally,
-        // syncs happen frequently enough that LocalStorage doesn't get _too_ desynced.
-
-        let req = fetch::Request::get("/api/v1/load")
+    /// Fetches the server copy of the open board.
+    fn fetch(&mut self) {
+        let req = fetch::Request::get(self.url("load"))
             .body(Nothing)
             .expect("could not build request");
 
         let cb = self.link.callback(
-            |res: fetch::Response<Json<anyhow::Result<config::Config>>>| {
+            |res: fetch::Response<Json<anyhow::Result<api::Snapshot>>>| {
                 let Json(data) = res.into_body();
                 match data {
-                    Ok(settings) => Msg::Loaded(settings),
+                    Ok(snapshot) => Msg::Loaded(snapshot),
                     Err(err) => {
-                        // TODO: Try again later?
                         ConsoleService::error(&format!("could not load settings: {}", err));
-                        Msg::Noop
+                        Msg::LoadFailed
                     }
                 }
             },
         );
         let task = fetch::FetchService::fetch(req, cb).expect("could not start request");
         self.load_req = Some(task);
+    }
+
+    /// Starts listening for the settings to be saved elsewhere, if we aren't already.
+    fn listen(&mut self) {
+        if self.events.is_some() {
+            return;
+        }
+        let source = match EventSource::new(&self.url("events")) {
+            Ok(source) => source,
+            Err(err) => {
+                ConsoleService::error(&format!("could not listen for changes: {:?}", err));
+                return;
+            }
+        };
+
+        let pushed = self.link.callback(Msg::Pushed);
+        let on_settings = Closure::wrap(Box::new(move |e: MessageEvent| {
+            let data = e.data().as_string().unwrap_or_default();
+            match serde_json::from_str::<api::Snapshot>(&data) {
+                Ok(snapshot) => pushed.emit(snapshot),
+                Err(err) => {
+                    ConsoleService::error(&format!("could not read pushed settings: {}", err))
+                }
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+
+        // The browser reconnects by itself, but anything saved while it was gone is lost.
+        let reconnected = self.link.callback(|()| Msg::Reconnected);
+        let mut opened = false;
+        let on_open = Closure::wrap(Box::new(move |_: Event| {
+            if opened {
+                reconnected.emit(());
+            }
+            opened = true;
+        }) as Box<dyn FnMut(Event)>);
+
+        let listen = source
+            .add_event_listener_with_callback("settings", on_settings.as_ref().unchecked_ref())
+            .and_then(|_| {
+                source.add_event_listener_with_callback("open", on_open.as_ref().unchecked_ref())
+            });
+        if let Err(err) = listen {
+            ConsoleService::error(&format!("could not listen for changes: {:?}", err));
+            source.close();
+            return;
+        }
+        self.events = Some(Events {
+            source,
+            on_settings,
+            on_open,
+        });
+    }
+
+    /// Retries saving whenever the browser says it's back online.
+    fn listen_online(link: &AgentLink<Self>) -> Option<Closure<dyn FnMut(Event)>> {
+        let retry = link.callback(|()| Msg::RetrySave);
+        let on_online =
+            Closure::wrap(Box::new(move |_: Event| retry.emit(())) as Box<dyn FnMut(Event)>);
+        let window = web_sys::window()?;
+        window
+            .add_event_listener_with_callback("online", on_online.as_ref().unchecked_ref())
+            .ok()?;
+        Some(on_online)
+    }
 
-        self.settings.clone()
+    fn wait(delay: Duration, callback: Callback<()>) -> TimeoutTask {
+        TimeoutService::spawn(delay, callback)
+    }
+}
+
+/// Works out how a save went from the server's response.
+fn read_save_response(res: fetch::Response<anyhow::Result<String>>) -> Response {
+    let status = res.status();
+    let body = res.into_body();
+    if status.is_success() {
+        match body.and_then(|b| Ok(serde_json::from_str::<api::Saved>(&b)?)) {
+            Ok(saved) => Response::Saved {
+                revision: saved.revision,
+            },
+            Err(err) => {
+                // It may well have saved, but sending it again will sort that out.
+                ConsoleService::error(&format!("could not read save response: {}", err));
+                Response::Failed
+            }
+        }
+    } else if status == fetch::StatusCode::CONFLICT {
+        match body.and_then(|b| Ok(serde_json::from_str::<api::Snapshot>(&b)?)) {
+            Ok(current) => Response::Conflict(current),
+            Err(err) => {
+                ConsoleService::error(&format!("could not read conflicting settings: {}", err));
+                Response::Failed
+            }
+        }
+    } else if status == fetch::StatusCode::UNPROCESSABLE_ENTITY {
+        match body.and_then(|b| Ok(serde_json::from_str::<api::Error>(&b)?)) {
+            Ok(err) => {
+                ConsoleService::error(&format!("could not save settings: {}", err.message));
+                for problem in err.problems.iter() {
+                    ConsoleService::error(&problem.to_string());
+                }
+            }
+            Err(err) => ConsoleService::error(&format!("could not save settings: {}", err)),
+        }
+        Response::Rejected
+    } else if status.is_server_error()
+        // Requests that never got an answer, such as while offline, come back as timeouts.
+        || status == fetch::StatusCode::REQUEST_TIMEOUT
+        || status == fetch::StatusCode::TOO_MANY_REQUESTS
+    {
+        ConsoleService::error(&format!("could not save settings: {}", status));
+        Response::Failed
+    } else {
+        ConsoleService::error(&format!("could not save settings: {}", status));
+        Response::Rejected
     }
 }
 
@@ -108,62 +399,94 @@ impl Agent for Settings {
     type Reach = Context<Self>;
     type Message = Msg;
     type Input = Request;
-    type Output = config::Config;
+    type Output = Output;
 
     fn create(link: AgentLink<Self>) -> Self {
+        let local = StorageService::new(Area::Local).expect("Could not connect to LocalStorage");
+        let on_online = Self::listen_online(&link);
         Self {
             link,
-            settings: config::Config::default(),
+            board: None,
+            outbox: Outbox::default(),
+            loaded: false,
             subscribers: HashSet::new(),
-            local: StorageService::new(Area::Local).expect("Could not connect to LocalStorage"),
+            local,
             save_req: None,
             load_req: None,
+            save_retry: None,
+            load_retry: None,
+            load_failures: 0,
+            events: None,
+            on_online,
         }
     }
 
     fn update(&mut self, msg: Self::Message) {
         match msg {
             Msg::Noop => (),
-            Msg::Saved => self.save_req = None,
-            Msg::Loaded(settings) => {
-                self.settings = settings;
+            Msg::Responded(response) => {
+                self.save_req = None;
+                self.apply(|outbox| outbox.respond(response));
+            }
+            Msg::RetrySave => {
+                self.save_retry = None;
+                self.send();
+            }
+            Msg::Loaded(snapshot) => {
+                self.load_req = None;
+                self.load_failures = 0;
+                self.accept(snapshot);
+            }
+            Msg::LoadFailed => {
                 self.load_req = None;
+                self.load_failures += 1;
+                self.load_retry = Some(Self::wait(
+                    outbox::backoff(self.load_failures),
+                    self.link.callback(|()| Msg::RetryLoad),
+                ));
+            }
+            Msg::RetryLoad => {
+                self.load_retry = None;
+                self.fetch();
             }
+            Msg::Pushed(snapshot) => self.accept(snapshot),
+            Msg::Reconnected => self.fetch(),
         }
     }
 
-    fn handle_input(&mut self, msg: Self::Input, _caller: HandlerId) {
+    fn handle_input(&mut self, msg: Self::Input, caller: HandlerId) {
         match msg {
-            Request::Save(s) => {
-                self.save(&s);
-                self.broadcast(s);
-            }
-            Request::Load => {
-                let cfg = self.load();
-                self.broadcast(cfg);
+            // Edits are made to what subscribers were shown, so there can't be any before then.
+            Request::Save(s) if self.loaded => self.apply(|outbox| {
+                outbox.edit(s);
+                Vec::new()
+            }),
+            Request::Save(_) => (),
+            Request::Load(board) => {
+                // Show what this device has right away, and whatever the server has once it
+                // arrives.
+                if self.board.as_ref() != Some(&board) {
+                    self.open(board);
+                } else if self.loaded {
+                    self.link
+                        .respond(caller, Output::Config(self.outbox.config().clone()));
+                    self.link
+                        .respond(caller, Output::Status(self.outbox.status()));
+                }
+                self.fetch();
+                self.listen();
             }
             Request::SaveSingle { id, data } => {
                 // TODO: Make this a method on Config
-                let mut new_tiles: Vec<config::Tile> = Vec::new();
-                for tile in self.settings.tiles.iter() {
-                    if tile.id != id {
-                        new_tiles.push(tile.clone());
-                    } else {
-                        new_tiles.push(config::Tile {
-                            id: tile.id,
-                            width: tile.width,
-                            height: tile.height,
-                            data: data.clone(),
-                        })
+                let mut settings = self.outbox.config().clone();
+                for tile in settings.tiles.iter_mut() {
+                    if tile.id == id {
+                        // A note may have been sent edits from elsewhere since it last saved, so
+                        // keep them rather than going back to the note's own copy.
+                        tile.data = data.merge(None, &tile.data).unwrap_or_else(|| data.clone());
                     }
                 }
-                self.settings.tiles = new_tiles;
-                self.link.send_input(Request::Save(self.settings.clone()));
-            }
-            Request::SaveSecrets { secrets } => {
-                let mut cfg = self.settings.clone();
-                cfg.secrets = secrets;
-                self.link.send_input(Request::Save(cfg));
+                self.link.send_input(Request::Save(settings));
             }
         }
     }
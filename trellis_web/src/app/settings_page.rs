@@ -1,29 +1,57 @@
+use super::account_form::AccountForm;
+use super::boards_form::BoardsForm;
 use super::config_form;
+use super::history_list::HistoryList;
+use super::import_export_form::ImportExportForm;
+use super::members_form::MembersForm;
 use super::settings;
+use super::share_links_form::ShareLinksForm;
+use super::sync_status;
+use trellis_core::board::Role;
 use trellis_core::config;
+use trellis_core::outbox::Status;
 use yew::prelude::*;
 
+#[derive(Properties, Clone, Debug)]
+pub struct Props {
+    /// The slug of the board to show.
+    pub board: String,
+    /// What the user can do with the board. Only owners can share it, and viewers can't change it.
+    #[prop_or_default]
+    pub role: Role,
+}
+
 pub struct SettingsPage {
     link: ComponentLink<Self>,
+    props: Props,
     settings: Option<config::Config>,
+    status: Status,
     settings_service: Box<dyn Bridge<settings::Settings>>,
 }
 
 pub enum Msg {
     Load(config::Config),
+    Status(Status),
     Save(config::Config),
+    Reload,
 }
 
 impl Component for SettingsPage {
     type Message = Msg;
-    type Properties = ();
+    type Properties = Props;
 
-    fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
-        let mut settings_service = settings::Settings::bridge(link.callback(Msg::Load));
-        settings_service.send(settings::Request::Load);
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let mut settings_service =
+            settings::Settings::bridge(link.callback(|output: settings::Output| match output {
+                settings::Output::Config(cfg) => Msg::Load(cfg),
+                settings::Output::Status(status) => Msg::Status(status),
+            }));
+        settings_service.send(settings::Request::Load(props.board.clone()));
         Self {
             link,
+            props,
             settings: None,
+            status: Status::Saved,
             settings_service,
         }
     }
@@ -34,15 +62,33 @@ impl Component for SettingsPage {
                 self.settings = Some(cfg);
                 true
             }
+            Msg::Status(status) => {
+                self.status = status;
+                true
+            }
             Msg::Save(cfg) => {
                 self.settings_service.send(settings::Request::Save(cfg));
                 false
             }
+            Msg::Reload => {
+                self.settings_service
+                    .send(settings::Request::Load(self.props.board.clone()));
+                false
+            }
         }
     }
 
-    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
-        false
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        if props.board == self.props.board {
+            let changed = props.role != self.props.role;
+            self.props = props;
+            return changed;
+        }
+        self.props = props;
+        self.settings = None;
+        self.settings_service
+            .send(settings::Request::Load(self.props.board.clone()));
+        true
     }
 
     fn view(&self) -> Html {
@@ -50,11 +96,59 @@ impl Component for SettingsPage {
             None => html! { <p class="text-xl">{"Loading..."}</p> },
             Some(cfg) => {
                 let onsubmit = self.link.callback(Msg::Save);
+                let onrestore = self.link.callback(|_| Msg::Reload);
+                let onimport = self.link.callback(|_| Msg::Reload);
+                let divider = html! {
+                    <div class="w-4/5 mx-auto my-2 border-t border-gray-200 dark:border-gray-700"></div>
+                };
+                let role = self.props.role;
+                let board = self.props.board.clone();
+                let editing = if role.can_edit() {
+                    html! { <>
+                        <div class="flex justify-end px-2">{sync_status::view(self.status)}</div>
+                        <config_form::ConfigForm
+                            config=cfg.clone()
+                            onsubmit=onsubmit
+                        />
+                        {divider.clone()}
+                    </> }
+                } else {
+                    html! { <>
+                        <p class="text-center text-gray-400">
+                            {"You can view this board, but only its owner and editors can change it."}
+                        </p>
+                        {divider.clone()}
+                    </> }
+                };
+                let sharing = if role == Role::Owner {
+                    html! { <>
+                        <MembersForm board=board.clone() />
+                        {divider.clone()}
+                        <ShareLinksForm board=board.clone() />
+                        {divider.clone()}
+                    </> }
+                } else {
+                    html! {}
+                };
+                let saving = if role.can_edit() {
+                    html! { <>
+                        <ImportExportForm board=board.clone() onimport=onimport />
+                        {divider.clone()}
+                        <HistoryList board=board.clone() onrestore=onrestore />
+                        {divider.clone()}
+                    </> }
+                } else {
+                    html! {}
+                };
                 html! {
-                    <config_form::ConfigForm
-                        config=cfg.clone()
-                        onsubmit=onsubmit
-                    />
+                    <>
+                        {editing}
+                        <BoardsForm board=board role=role />
+                        {divider}
+                        {sharing}
+                        {saving}
+                        <AccountForm />
+                    </>
                 }
             }
         }
@@ -0,0 +1,232 @@
+use chrono::Local;
+use trellis_core::api;
+use uuid::Uuid;
+use yew::format::{Json, Nothing};
+use yew::prelude::*;
+use yew::services::console::ConsoleService;
+use yew::services::fetch::{FetchService, FetchTask, Request, Response};
+
+#[derive(Properties, Clone, Debug)]
+pub struct Props {
+    /// The slug of the board to share.
+    pub board: String,
+}
+
+/// Lists the links that show a board to people without an account, and makes and revokes them.
+pub struct ShareLinksForm {
+    link: ComponentLink<Self>,
+    props: Props,
+    links: Option<Vec<api::ShareLink>>,
+    /// What's typed in as the label of a new link.
+    label: String,
+    /// The URL of the link that was just made. It can't be shown again later.
+    created: Option<String>,
+    fetch_task: Option<FetchTask>,
+    error: Option<String>,
+}
+
+pub enum Msg {
+    Fetch,
+    Fetched(Vec<api::ShareLink>),
+    Label(InputData),
+    Create(FocusEvent),
+    Created(api::CreatedShareLink),
+    Revoke(Uuid),
+    Failed(String),
+}
+
+impl Component for ShareLinksForm {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        link.send_message(Msg::Fetch);
+        Self {
+            link,
+            props,
+            links: None,
+            label: String::new(),
+            created: None,
+            fetch_task: None,
+            error: None,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::Fetch => {
+                let req = Request::get(self.url(""))
+                    .body(Nothing)
+                    .expect("could not build request");
+                self.send(req, "load share links");
+                false
+            }
+            Msg::Fetched(links) => {
+                self.fetch_task = None;
+                self.links = Some(links);
+                self.error = None;
+                true
+            }
+            Msg::Label(data) => {
+                self.label = data.value;
+                false
+            }
+            Msg::Create(e) => {
+                e.prevent_default();
+                let body = api::NewShareLink {
+                    label: std::mem::take(&mut self.label),
+                };
+                let req = Request::post(self.url(""))
+                    .header("Content-Type", "application/json")
+                    .body(Json(&body))
+                    .expect("could not build request");
+                let cb = self.link.callback(
+                    |res: Response<Json<anyhow::Result<api::CreatedShareLink>>>| {
+                        let Json(data) = res.into_body();
+                        match data {
+                            Ok(created) => Msg::Created(created),
+                            Err(err) => Msg::Failed(format!("could not share board: {}", err)),
+                        }
+                    },
+                );
+                let task = FetchService::fetch(req, cb).expect("could not start request");
+                self.fetch_task = Some(task);
+                true
+            }
+            Msg::Created(created) => {
+                self.fetch_task = None;
+                let origin = yew::utils::window().location().origin().unwrap_or_default();
+                self.created = Some(format!("{}/s/{}", origin, created.token));
+                self.links.get_or_insert_with(Vec::new).push(created.link);
+                self.error = None;
+                true
+            }
+            Msg::Revoke(id) => {
+                let req = Request::delete(self.url(&format!("/{}", id)))
+                    .body(Nothing)
+                    .expect("could not build request");
+                self.send(req, "revoke share link");
+                true
+            }
+            Msg::Failed(err) => {
+                ConsoleService::error(&err);
+                self.fetch_task = None;
+                self.error = Some(err);
+                true
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        let switched = props.board != self.props.board;
+        self.props = props;
+        if switched {
+            self.links = None;
+            self.created = None;
+            self.error = None;
+            self.link.send_message(Msg::Fetch);
+        }
+        switched
+    }
+
+    fn view(&self) -> Html {
+        let body = match &self.links {
+            None => html! { <p>{"Loading..."}</p> },
+            Some(links) if links.is_empty() => html! { <p>{"Not shared with anyone."}</p> },
+            Some(links) => html! {
+                <ul>
+                    { for links.iter().map(|l| self.render_link(l)) }
+                </ul>
+            },
+        };
+        let created = match &self.created {
+            None => html! {},
+            Some(url) => html! {
+                <div class="flex flex-col items-center">
+                    <input type="text" class="w-96" readonly=true value=url.clone() />
+                    <p class="text-gray-400">
+                        {"Anyone with this link can see the board. Copy it now; it won't be shown again."}
+                    </p>
+                </div>
+            },
+        };
+        let error = match &self.error {
+            None => html! {},
+            Some(msg) => html! { <p class="text-red-500">{msg}</p> },
+        };
+
+        html! {
+            <div class="flex flex-col items-center space-y-1">
+                <span class="font-bold">{"Share links"}</span>
+                {error}
+                {body}
+                {created}
+                <form class="flex space-x-2" onsubmit=self.link.callback(Msg::Create)>
+                    <label for="share_label" class="sr-only">{"Link label"}</label>
+                    <input
+                        id="share_label"
+                        type="text"
+                        placeholder="What it's for (optional)"
+                        value=self.label.clone()
+                        oninput=self.link.callback(Msg::Label)
+                    />
+                    <button type="submit" disabled=self.fetch_task.is_some()>
+                        {"Make read-only link"}
+                    </button>
+                </form>
+            </div>
+        }
+    }
+}
+
+impl ShareLinksForm {
+    fn url(&self, rest: &str) -> String {
+        format!("/api/v1/boards/{}/shares{}", self.props.board, rest)
+    }
+
+    /// Sends a request whose response is the board's share links.
+    fn send(&mut self, req: Request<Nothing>, action: &'static str) {
+        let cb = self.link.callback(
+            move |res: Response<Json<anyhow::Result<Vec<api::ShareLink>>>>| {
+                let Json(data) = res.into_body();
+                match data {
+                    Ok(links) => Msg::Fetched(links),
+                    Err(err) => Msg::Failed(format!("could not {}: {}", action, err)),
+                }
+            },
+        );
+        let task = FetchService::fetch(req, cb).expect("could not start request");
+        self.fetch_task = Some(task);
+    }
+
+    fn render_link(&self, link: &api::ShareLink) -> Html {
+        let id = link.id;
+        let revoke = self.link.callback(move |_| Msg::Revoke(id));
+        let label = if link.label.is_empty() {
+            html! { <span class="text-gray-400">{"No label"}</span> }
+        } else {
+            html! { <span>{&link.label}</span> }
+        };
+        let created = link
+            .created_at
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M");
+        let viewed = match link.last_viewed_at {
+            Some(at) => format!(
+                "last viewed {}",
+                at.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+            ),
+            None => "never viewed".to_owned(),
+        };
+        html! {
+            <li class="flex space-x-4">
+                {label}
+                <span>{format!("made {}", created)}</span>
+                <span>{viewed}</span>
+                <button type="button" disabled=self.fetch_task.is_some() onclick=revoke>
+                    {"Revoke"}
+                </button>
+            </li>
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use super::board::view_tile;
+use super::grid::Grid;
+use trellis_core::api;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{Event, EventSource, MessageEvent};
+use yew::format::{Json, Nothing};
+use yew::prelude::*;
+use yew::services::console::ConsoleService;
+use yew::services::fetch::{FetchService, FetchTask, Request, Response};
+
+#[derive(Properties, Clone, Debug)]
+pub struct Props {
+    /// The token from the share link.
+    pub token: String,
+}
+
+/// Shows a board that someone shared, read-only, and keeps it up to date as they change it.
+pub struct SharedBoard {
+    link: ComponentLink<Self>,
+    props: Props,
+    board: Option<api::SharedBoard>,
+    error: Option<String>,
+    fetch_task: Option<FetchTask>,
+    events: Option<Events>,
+}
+
+/// An open stream of saved boards, and the listeners that have to live as long as it does.
+struct Events {
+    source: EventSource,
+    #[allow(dead_code)]
+    on_settings: Closure<dyn FnMut(MessageEvent)>,
+    #[allow(dead_code)]
+    on_error: Closure<dyn FnMut(Event)>,
+}
+
+impl Drop for Events {
+    fn drop(&mut self) {
+        self.source.close();
+    }
+}
+
+pub enum Msg {
+    Loaded(api::SharedBoard),
+    Pushed(api::Snapshot),
+    /// The browser gave up on the stream, which happens once the link is revoked.
+    Disconnected,
+    /// The link was revoked, or never worked.
+    NotFound,
+    Failed(String),
+}
+
+impl Component for SharedBoard {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let mut shared = Self {
+            link,
+            props,
+            board: None,
+            error: None,
+            fetch_task: None,
+            events: None,
+        };
+        shared.fetch();
+        shared
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::Loaded(board) => {
+                self.fetch_task = None;
+                self.board = Some(board);
+                self.error = None;
+                self.listen();
+                true
+            }
+            Msg::Pushed(snapshot) => match &mut self.board {
+                Some(board) if snapshot.revision > board.snapshot.revision => {
+                    board.snapshot = snapshot;
+                    true
+                }
+                _ => false,
+            },
+            Msg::Disconnected => {
+                self.events = None;
+                self.fetch();
+                false
+            }
+            Msg::NotFound => {
+                self.fetch_task = None;
+                self.events = None;
+                self.board = None;
+                self.error = Some("This link doesn't show a board anymore.".to_owned());
+                true
+            }
+            Msg::Failed(err) => {
+                ConsoleService::error(&err);
+                self.fetch_task = None;
+                self.error = Some(err);
+                true
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        if props.token == self.props.token {
+            return false;
+        }
+        self.props = props;
+        self.board = None;
+        self.error = None;
+        self.events = None;
+        self.fetch();
+        true
+    }
+
+    fn view(&self) -> Html {
+        let board = match (&self.board, &self.error) {
+            (Some(board), _) => board,
+            (None, Some(err)) => return html! { <p class="text-xl text-red-500">{err}</p> },
+            (None, None) => return html! { <p class="text-xl">{"Loading..."}</p> },
+        };
+        let config = &board.snapshot.config;
+        html! {
+            <>
+                <h1 class="px-2 text-gray-500">{&board.name}</h1>
+                <Grid>
+                    { for config.tiles.iter().map(|t| {
+                        view_tile(&config.preferences, t.clone(), Some(&self.props.token), None)
+                    }) }
+                </Grid>
+            </>
+        }
+    }
+}
+
+impl SharedBoard {
+    fn url(&self, action: &str) -> String {
+        format!("/api/v1/shared/{}{}", self.props.token, action)
+    }
+
+    fn fetch(&mut self) {
+        let req = Request::get(self.url(""))
+            .body(Nothing)
+            .expect("could not build request");
+        let cb = self
+            .link
+            .callback(|res: Response<Json<anyhow::Result<api::SharedBoard>>>| {
+                let (meta, Json(data)) = res.into_parts();
+                match data {
+                    Ok(board) => Msg::Loaded(board),
+                    Err(_) if meta.status.as_u16() == 404 => Msg::NotFound,
+                    Err(err) => Msg::Failed(format!("could not load board: {}", err)),
+                }
+            });
+        let task = FetchService::fetch(req, cb).expect("could not start request");
+        self.fetch_task = Some(task);
+    }
+
+    /// Starts listening for the board to be saved, if we aren't already.
+    fn listen(&mut self) {
+        if self.events.is_some() {
+            return;
+        }
+        let source = match EventSource::new(&self.url("/events")) {
+            Ok(source) => source,
+            Err(err) => {
+                ConsoleService::error(&format!("could not listen for changes: {:?}", err));
+                return;
+            }
+        };
+
+        let pushed = self.link.callback(Msg::Pushed);
+        let on_settings = Closure::wrap(Box::new(move |e: MessageEvent| {
+            let data = e.data().as_string().unwrap_or_default();
+            match serde_json::from_str::<api::Snapshot>(&data) {
+                Ok(snapshot) => pushed.emit(snapshot),
+                Err(err) => ConsoleService::error(&format!("could not read pushed board: {}", err)),
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+
+        // The browser reconnects by itself unless the server turns it away.
+        let disconnected = self.link.callback(|()| Msg::Disconnected);
+        let closed = source.clone();
+        let on_error = Closure::wrap(Box::new(move |_: Event| {
+            if closed.ready_state() == EventSource::CLOSED {
+                disconnected.emit(());
+            }
+        }) as Box<dyn FnMut(Event)>);
+
+        let listen = source
+            .add_event_listener_with_callback("settings", on_settings.as_ref().unchecked_ref())
+            .and_then(|_| {
+                source.add_event_listener_with_callback("error", on_error.as_ref().unchecked_ref())
+            });
+        if let Err(err) = listen {
+            ConsoleService::error(&format!("could not listen for changes: {:?}", err));
+            source.close();
+            return;
+        }
+        self.events = Some(Events {
+            source,
+            on_settings,
+            on_error,
+        });
+    }
+}
@@ -0,0 +1,22 @@
+use trellis_core::outbox::Status;
+use yew::prelude::*;
+
+/// Shows whether the user's settings have reached the server.
+pub fn view(status: Status) -> Html {
+    let (label, color, title) = match status {
+        Status::Saved => ("Saved", "text-gray-400", "Your settings are saved."),
+        Status::Pending => (
+            "Saving...",
+            "text-yellow-500",
+            "Your changes will be saved once the server can be reached.",
+        ),
+        Status::Error => (
+            "Not saved",
+            "text-red-500",
+            "Your changes could not be saved. They are kept on this device for now.",
+        ),
+    };
+    html! {
+        <span class=classes!("text-sm", color) title=title role="status">{label}</span>
+    }
+}
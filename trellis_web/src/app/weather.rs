@@ -1,24 +1,29 @@
+use super::locale::locale;
 use anyhow::{self, format_err};
-use chrono::{DateTime, Local};
-use lazy_static::lazy_static;
+use chrono::{DateTime, Local, TimeZone, Utc};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
-use url::Url;
+use trellis_core::preferences::Preferences;
+use trellis_core::weather::{
+    DailyForecast, Forecast, HourlyForecast, Mode, Place, Provider, WeatherReport,
+};
+use trellis_core::{api, config};
+use url::form_urlencoded;
 use web_sys::HtmlInputElement;
-use yew::format::{Json, Nothing};
+use yew::format::Nothing;
 use yew::prelude::*;
 use yew::services::fetch::{FetchService, FetchTask, Request, Response};
 use yew::services::interval::{IntervalService, IntervalTask};
 
-lazy_static! {
-    static ref OWM_URL: Url =
-        Url::parse("https://api.openweathermap.org").expect("could not parse URL");
-}
+/// How many hourly forecast columns fit in each grid cell of a tile's width.
+const HOURS_PER_CELL: usize = 4;
 
 pub struct Weather {
     props: Props,
     link: ComponentLink<Self>,
-    location: Location,
+    report: Option<WeatherReport>,
+    forecast: Option<Forecast>,
     #[allow(dead_code)]
     ticker: IntervalTask,
     fetch_task: Option<FetchTask>,
@@ -26,36 +31,29 @@ pub struct Weather {
     error: Option<anyhow::Error>,
 }
 
-#[derive(Serialize, Deserialize, Properties, Clone, Debug)]
+#[derive(Serialize, Deserialize, Properties, Clone, Debug, PartialEq)]
 pub struct Props {
+    pub provider: Provider,
     pub location_id: String,
-    pub owm_api_key: String,
+    /// Shown instead of the provider's name for the location, unless it's empty.
+    pub location_name: String,
+    pub mode: Mode,
+    /// The tile's span in grid cells, which decides how much of a forecast fits.
+    #[prop_or(1)]
+    pub width: u32,
+    #[prop_or(1)]
+    pub height: u32,
+    pub prefs: Preferences,
+    /// The token of the share link the tile is seen through, if it is. The weather is then
+    /// fetched through the link, with the board owner's API key.
+    #[prop_or_default]
+    pub share: Option<String>,
 }
 
 pub enum Msg {
     Fetch,
-    Receive(Result<FetchResponse, anyhow::Error>),
-}
-
-#[derive(Clone)]
-struct Location {
-    id: String,
-    name: String,
-    icon: String,
-    description: String,
-    temperature: String,
-}
-
-impl Location {
-    fn empty() -> Self {
-        Self {
-            id: String::new(),
-            name: String::new(),
-            icon: String::new(),
-            description: String::new(),
-            temperature: String::new(),
-        }
-    }
+    Receive(Result<WeatherReport, anyhow::Error>),
+    ReceiveForecast(Result<Forecast, anyhow::Error>),
 }
 
 impl Component for Weather {
@@ -69,7 +67,8 @@ impl Component for Weather {
         Self {
             props,
             link: link.clone(),
-            location: Location::empty(),
+            report: None,
+            forecast: None,
             ticker: IntervalService::spawn(Duration::from_secs(60), link.callback(|_| Msg::Fetch)),
             fetch_task: None,
             last_updated_at: None,
@@ -80,70 +79,58 @@ impl Component for Weather {
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
             Msg::Fetch => {
-                if self.props.owm_api_key.is_empty() {
-                    self.error = Some(format_err!("Missing OpenWeatherMap API key"));
-                    return true;
-                }
                 if self.props.location_id.is_empty() {
                     self.error = Some(format_err!("Missing location ID"));
                     return true;
                 }
 
-                let mut url = OWM_URL.clone();
-                url.set_path("data/2.5/weather");
-                url.query_pairs_mut()
-                    .append_pair("id", &self.props.location_id)
-                    .append_pair("appid", &self.props.owm_api_key)
-                    .append_pair("units", "imperial");
-                let request = Request::get(url.as_str())
-                    .body(Nothing)
-                    .expect("could not build request");
-
-                let callback = self.link.callback(
-                    |response: Response<Json<Result<FetchResponse, anyhow::Error>>>| {
-                        let Json(data) = response.into_body();
-                        Msg::Receive(data)
-                    },
-                );
-
-                let task = FetchService::fetch(request, callback).expect("could not start request");
+                // The server fetches the weather, since it has the API key.
+                let query = form_urlencoded::Serializer::new(String::new())
+                    .append_pair("provider", &self.props.provider.to_string())
+                    .append_pair("location_id", &self.props.location_id)
+                    .finish();
+                let base = match &self.props.share {
+                    Some(token) => format!("/api/v1/shared/{}/weather", token),
+                    None => "/api/v1/weather".to_owned(),
+                };
+                let task = match self.props.mode {
+                    Mode::Current => fetch(&self.link, format!("{}?{}", base, query), Msg::Receive),
+                    Mode::Forecast => fetch(
+                        &self.link,
+                        format!("{}/forecast?{}", base, query),
+                        Msg::ReceiveForecast,
+                    ),
+                };
                 self.fetch_task = Some(task);
                 false
             }
             Msg::Receive(res) => {
-                match res {
-                    Ok(data) => {
-                        // The weather comes back as an array.  This is probably one item per
-                        // weather station, so taking the first one should be fine for now.
-                        //
-                        // TODO: Figure out how to handle this list better.
-                        let w = &data.weather[0];
-                        let mut icon_url = OWM_URL.clone();
-                        icon_url.set_path(&format!("img/w/{}.png", w.icon));
-
-                        self.location = Location {
-                            id: format!("{}", data.id),
-                            name: data.name,
-                            temperature: format!("{:.0}℉", data.main.temp),
-                            description: w.description.clone(),
-                            icon: icon_url.to_string(),
-                        };
-                        self.error = None;
-                    }
-                    Err(error) => {
-                        self.error = Some(error);
-                        self.location = Location::empty();
-                    }
-                }
-                self.last_updated_at = Some(Local::now());
-                self.fetch_task = None;
+                self.report = self.receive(res);
+                true
+            }
+            Msg::ReceiveForecast(res) => {
+                self.forecast = self.receive(res);
                 true
             }
         }
     }
 
-    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
-        false
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        if self.props == props {
+            return false;
+        }
+        // Resizing the tile or changing its preferences only changes how the weather is shown,
+        // but anything else needs different weather.
+        let refetch = self.props.provider != props.provider
+            || self.props.location_id != props.location_id
+            || self.props.mode != props.mode;
+        self.props = props;
+        if refetch {
+            self.report = None;
+            self.forecast = None;
+            self.link.send_message(Msg::Fetch);
+        }
+        true
     }
 
     fn view(&self) -> Html {
@@ -154,7 +141,49 @@ impl Component for Weather {
     }
 }
 
+/// Fetches JSON from the server, reading the error message out of the body of a failed response.
+pub(super) fn fetch<C, T, F>(link: &ComponentLink<C>, url: String, into_msg: F) -> FetchTask
+where
+    C: Component,
+    T: DeserializeOwned,
+    F: Fn(Result<T, anyhow::Error>) -> C::Message + 'static,
+{
+    let request = Request::get(url)
+        .body(Nothing)
+        .expect("could not build request");
+
+    let callback = link.callback(move |response: Response<Result<String, anyhow::Error>>| {
+        let (meta, body) = response.into_parts();
+        let res = body.and_then(|body| {
+            if meta.status.is_success() {
+                Ok(serde_json::from_str::<T>(&body)?)
+            } else {
+                let err = serde_json::from_str::<api::Error>(&body)?;
+                Err(format_err!("{}", err.message))
+            }
+        });
+        into_msg(res)
+    });
+
+    FetchService::fetch(request, callback).expect("could not start request")
+}
+
 impl Weather {
+    fn receive<T>(&mut self, res: Result<T, anyhow::Error>) -> Option<T> {
+        self.last_updated_at = Some(Local::now());
+        self.fetch_task = None;
+        match res {
+            Ok(value) => {
+                self.error = None;
+                Some(value)
+            }
+            Err(error) => {
+                self.error = Some(error);
+                None
+            }
+        }
+    }
+
     fn view_loading(&self) -> Html {
         html! {
             <div class="flex flex-col items-center justify-around w-full h-full">
@@ -167,35 +196,35 @@ impl Weather {
     }
 
     fn view_weather(&self) -> Html {
-        let last_updated = self
-            .last_updated_at
-            .map_or(String::from("never"), |t| t.format("%H:%M:%S").to_string());
-
-        let owm_url = {
-            let mut url = OWM_URL.clone();
-            url.path_segments_mut()
-                .expect("cannot be base")
-                .extend(&["city", &self.props.location_id]);
-            url.to_string()
-        };
+        let time_pattern = self.props.prefs.time_format.pattern(true);
+        let last_updated = self.last_updated_at.map_or(String::from("never"), |t| {
+            t.format(time_pattern).to_string()
+        });
 
-        let location = self.location.clone();
-        let name = location.name;
-        let icon = location.icon;
-        let description = location.description;
-        let temperature = location.temperature;
+        let (body, source) = match self.props.mode {
+            Mode::Current => self.report.as_ref().map(|report| {
+                (
+                    self.view_report(report),
+                    view_source(&report.source_name, &report.source_url),
+                )
+            }),
+            Mode::Forecast => self.forecast.as_ref().map(|forecast| {
+                (
+                    self.view_forecast(forecast),
+                    view_source(&forecast.source_name, &forecast.source_url),
+                )
+            }),
+        }
+        .unwrap_or_else(|| (html! {}, html! {}));
 
         html! {
             <div class="flex flex-col items-center justify-around w-full h-full">
-                <span class="text-3xl">{name}</span>
-                <img src=icon alt=description.clone() />
-                <span class="text-2xl">{description}</span>
-                <div class="text-2xl">{temperature}</div>
+                {body}
                 <div class="text-xl text-red-500">
                     {self.error.as_ref().map_or(String::new(), |e| e.to_string())}
                 </div>
                 <div class="flex items-center justify-around self-stretch text-gray-400">
-                    <a href=owm_url>{"OpenWeatherMap"}</a>
+                    {source}
                     <div>
                         {format!("Last updated at: {}", last_updated)}
                     </div>
@@ -203,42 +232,161 @@ impl Weather {
             </div>
         }
     }
-}
 
-#[derive(Deserialize, Clone)]
-pub struct FetchResponse {
-    weather: Vec<WeatherResponse>,
-    main: MainResponse,
-    name: String,
-    id: u64,
+    fn view_report(&self, report: &WeatherReport) -> Html {
+        let icon = match &report.icon_url {
+            None => html! {},
+            Some(url) => html! { <img src=url.clone() alt=report.description.clone() /> },
+        };
+        html! {
+            <>
+                <span class="text-3xl">{self.location_name(&report.location_name)}</span>
+                {icon}
+                <span class="text-2xl">{&report.description}</span>
+                <div class="text-2xl">
+                    {self.props.prefs.units.format_temperature(report.temperature)}
+                </div>
+            </>
+        }
+    }
+
+    /// Prefers the name the user chose for the location over the provider's.
+    fn location_name<'a>(&'a self, provider_name: &'a str) -> &'a str {
+        if self.props.location_name.is_empty() {
+            provider_name
+        } else {
+            &self.props.location_name
+        }
+    }
+
+    fn view_forecast(&self, forecast: &Forecast) -> Html {
+        // A single row is only tall enough for the daily strip.
+        let hourly = if self.props.height > 1 {
+            self.view_hourly(&forecast.hourly)
+        } else {
+            html! {}
+        };
+        html! {
+            <>
+                <span class="text-3xl">{self.location_name(&forecast.location_name)}</span>
+                {hourly}
+                {self.view_daily(&forecast.daily)}
+            </>
+        }
+    }
+
+    fn view_hourly(&self, hours: &[HourlyForecast]) -> Html {
+        // Spread the columns that fit across the whole day, rather than showing only the next
+        // few hours.
+        let columns = (self.props.width as usize * HOURS_PER_CELL).max(1);
+        let step = hours.len().div_ceil(columns).max(1);
+        let prefs = &self.props.prefs;
+        let locale = locale(prefs);
+        let hours = hours
+            .iter()
+            .step_by(step)
+            .map(|hour| {
+                let time = hour
+                    .time
+                    .with_timezone(&Local)
+                    .format_localized(prefs.time_format.pattern(false), locale);
+                html! {
+                    <div class="flex flex-col items-center">
+                        <span class="text-gray-400">{time}</span>
+                        <span class="text-xl">{prefs.units.format_temperature(hour.temperature)}</span>
+                        <span class="text-blue-400">{precipitation(prefs, hour)}</span>
+                    </div>
+                }
+            })
+            .collect::<Html>();
+        html! {
+            <div class="flex justify-around self-stretch">{hours}</div>
+        }
+    }
+
+    fn view_daily(&self, days: &[DailyForecast]) -> Html {
+        // Narrow tiles get the work week, and wider ones get the whole week with descriptions.
+        let wide = self.props.width > 1;
+        let count = if wide { 7 } else { 5 };
+        let prefs = &self.props.prefs;
+        let locale = locale(prefs);
+        let days = days
+            .iter()
+            .take(count)
+            .map(|day| {
+                let description = if wide {
+                    html! { <span class="text-sm">{&day.description}</span> }
+                } else {
+                    html! {}
+                };
+                html! {
+                    <div class="flex flex-col items-center" title=day.description.clone()>
+                        <span class="text-gray-400">
+                            {Utc.from_utc_date(&day.date).format_localized("%a", locale)}
+                        </span>
+                        {description}
+                        <span>
+                            {format!(
+                                "{} / {}",
+                                prefs.units.format_temperature(day.high),
+                                prefs.units.format_temperature(day.low),
+                            )}
+                        </span>
+                    </div>
+                }
+            })
+            .collect::<Html>();
+        html! {
+            <div class="flex justify-around self-stretch">{days}</div>
+        }
+    }
 }
 
-#[derive(Deserialize, Clone)]
-struct WeatherResponse {
-    description: String,
-    icon: String,
+fn view_source(name: &str, url: &Option<String>) -> Html {
+    match url {
+        None => html! { <span>{name}</span> },
+        Some(url) => html! { <a href=url.clone()>{name}</a> },
+    }
 }
 
-#[derive(Deserialize, Clone)]
-struct MainResponse {
-    temp: f64,
+/// Describes the precipitation expected in an hour, with whatever the provider forecasts.
+fn precipitation(prefs: &Preferences, hour: &HourlyForecast) -> String {
+    let mut parts = Vec::new();
+    if let Some(chance) = hour.precipitation_chance {
+        parts.push(format!("{:.0}%", chance));
+    }
+    match hour.precipitation {
+        Some(mm) if mm > 0.0 => parts.push(prefs.units.format_precipitation(mm)),
+        _ => {}
+    }
+    parts.join(" ")
 }
 
 pub struct ConfigForm {
     props: ConfigFormProps,
     link: ComponentLink<Self>,
-    lid_ref: NodeRef,
+    query_ref: NodeRef,
+    fetch_task: Option<FetchTask>,
+    results: Option<Vec<Place>>,
+    error: Option<anyhow::Error>,
 }
 
 #[derive(Properties, Clone, Debug)]
 pub struct ConfigFormProps {
+    pub provider: Provider,
     pub location_id: String,
-    pub onchange: Callback<String>,
+    pub location_name: String,
+    pub mode: Mode,
+    /// Called with the tile's new `config::Data::Weather`.
+    pub onchange: Callback<config::Data>,
 }
 
 pub enum ConfigFormMsg {
-    Submit,
-    Input,
+    Search,
+    Found(Result<Vec<Place>, anyhow::Error>),
+    Choose(usize),
+    ChooseProvider(ChangeData),
+    ChooseMode(ChangeData),
 }
 
 impl Component for ConfigForm {
@@ -249,20 +397,94 @@ impl Component for ConfigForm {
         Self {
             props,
             link,
-            lid_ref: NodeRef::default(),
+            query_ref: NodeRef::default(),
+            fetch_task: None,
+            results: None,
+            error: None,
         }
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
-            ConfigFormMsg::Submit => {
-                let lid = self.lid_ref.cast::<HtmlInputElement>().unwrap().value();
-                self.props.onchange.emit(lid);
+            ConfigFormMsg::Search => {
+                let search = self.query_ref.cast::<HtmlInputElement>().unwrap().value();
+                if search.trim().is_empty() {
+                    return false;
+                }
+                // The server does the searching, since some providers need the API key for it.
+                let query = form_urlencoded::Serializer::new(String::new())
+                    .append_pair("provider", &self.props.provider.to_string())
+                    .append_pair("query", &search)
+                    .finish();
+                let task = fetch(
+                    &self.link,
+                    format!("/api/v1/weather/locations?{}", query),
+                    ConfigFormMsg::Found,
+                );
+                self.fetch_task = Some(task);
                 true
             }
-            ConfigFormMsg::Input => {
-                let lid = self.lid_ref.cast::<HtmlInputElement>().unwrap().value();
-                self.props.onchange.emit(lid);
+            ConfigFormMsg::Found(res) => {
+                self.fetch_task = None;
+                match res {
+                    Ok(places) => {
+                        self.results = Some(places);
+                        self.error = None;
+                    }
+                    Err(error) => {
+                        self.results = None;
+                        self.error = Some(error);
+                    }
+                }
+                true
+            }
+            ConfigFormMsg::Choose(idx) => {
+                let place = match self.results.take().and_then(|r| r.into_iter().nth(idx)) {
+                    Some(place) => place,
+                    None => return false,
+                };
+                self.query_ref
+                    .cast::<HtmlInputElement>()
+                    .unwrap()
+                    .set_value("");
+                self.emit(
+                    self.props.provider,
+                    place.location_id(),
+                    place.name,
+                    self.props.mode,
+                );
+                true
+            }
+            ConfigFormMsg::ChooseProvider(data) => {
+                if let ChangeData::Select(elt) = data {
+                    if let Ok(provider) = elt.value().parse() {
+                        // Every provider understands coordinates, but city IDs only mean
+                        // something to OpenWeatherMap.
+                        if self.props.location_id.contains(',') {
+                            self.emit(
+                                provider,
+                                self.props.location_id.clone(),
+                                self.props.location_name.clone(),
+                                self.props.mode,
+                            );
+                        } else {
+                            self.emit(provider, String::new(), String::new(), self.props.mode);
+                        }
+                    }
+                }
+                true
+            }
+            ConfigFormMsg::ChooseMode(data) => {
+                if let ChangeData::Select(elt) = data {
+                    if let Ok(mode) = elt.value().parse() {
+                        self.emit(
+                            self.props.provider,
+                            self.props.location_id.clone(),
+                            self.props.location_name.clone(),
+                            mode,
+                        );
+                    }
+                }
                 true
             }
         }
@@ -276,23 +498,111 @@ impl Component for ConfigForm {
     fn view(&self) -> Html {
         let onsubmit = self.link.callback(|e: FocusEvent| {
             e.prevent_default();
-            ConfigFormMsg::Submit
+            ConfigFormMsg::Search
         });
-        let oninput = self.link.callback(|_: InputData| ConfigFormMsg::Input);
+        let onchange = self.link.callback(ConfigFormMsg::ChooseProvider);
+        let onchange_mode = self.link.callback(ConfigFormMsg::ChooseMode);
+        let busy = self.fetch_task.is_some();
+
+        let options = Provider::ALL
+            .iter()
+            .map(|p| {
+                html! {
+                    <option value=p.to_string() selected=*p == self.props.provider>
+                        {p.name()}
+                    </option>
+                }
+            })
+            .collect::<Html>();
+        let mode_options = Mode::ALL
+            .iter()
+            .map(|m| {
+                html! {
+                    <option value=m.to_string() selected=*m == self.props.mode>
+                        {m.name()}
+                    </option>
+                }
+            })
+            .collect::<Html>();
+        let location = match (
+            self.props.location_name.as_str(),
+            self.props.location_id.as_str(),
+        ) {
+            (_, "") => html! { <span class="text-gray-400">{"Not set"}</span> },
+            ("", id) => html! { <span>{id}</span> },
+            (name, _) => html! { <span>{name}</span> },
+        };
+        let results = match &self.results {
+            None => html! {},
+            Some(places) if places.is_empty() => html! { <p>{"No places found."}</p> },
+            Some(places) => {
+                let items = places
+                    .iter()
+                    .enumerate()
+                    .map(|(i, place)| {
+                        let onclick = self.link.callback(move |_| ConfigFormMsg::Choose(i));
+                        html! {
+                            <li>
+                                <button type="button" onclick=onclick>{&place.name}</button>
+                            </li>
+                        }
+                    })
+                    .collect::<Html>();
+                html! { <ul>{items}</ul> }
+            }
+        };
+        let error = match &self.error {
+            None => html! {},
+            Some(err) => html! { <p class="text-red-500">{err.to_string()}</p> },
+        };
+        let key_note = if self.props.provider.needs_api_key() {
+            html! { <p>{"This tile also uses the OWM API key from the global settings."}</p> }
+        } else {
+            html! {}
+        };
 
         html! {
             <form class="w-full h-full" onsubmit=onsubmit>
                 <label>
-                    <a href="https://openweathermap.org/find">{"Location ID"}</a>
+                    {"Provider"}
+                    <select onchange=onchange>
+                        {options}
+                    </select>
+                </label>
+                <div>
+                    {"Location: "}
+                    {location}
+                </div>
+                <div class="flex">
                     <input
-                    type="text"
-                    value=self.props.location_id.clone()
-                    ref=self.lid_ref.clone()
-                    oninput=oninput
-                />
+                        type="search"
+                        class="w-full"
+                        placeholder="City, country or latitude, longitude"
+                        ref=self.query_ref.clone()
+                    />
+                    <button type="submit" disabled=busy>{"Search"}</button>
+                </div>
+                {results}
+                {error}
+                <label>
+                    {"Show"}
+                    <select onchange=onchange_mode>
+                        {mode_options}
+                    </select>
                 </label>
-                <p>{"This tile also uses the OWM API key from the global settings."}</p>
+                {key_note}
             </form>
         }
     }
 }
+
+impl ConfigForm {
+    fn emit(&self, provider: Provider, location_id: String, location_name: String, mode: Mode) {
+        self.props.onchange.emit(config::Data::Weather {
+            provider,
+            location_id,
+            location_name,
+            mode,
+        });
+    }
+}
@@ -1,3 +1,7 @@
+// `html!` checks each component prop with a statement like `props.name;`, spanned to the prop, which
+// clippy takes for code that does nothing.
+#![allow(clippy::unnecessary_operation)]
+
 use wasm_bindgen::prelude::*;
 
 mod app;